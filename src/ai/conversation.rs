@@ -0,0 +1,395 @@
+use bevy::{
+    prelude::{
+        Commands, Component, Entity, EventWriter, Name, Query, Res, Transform, Vec2, Visibility,
+        With, Without,
+    },
+    reflect::{FromReflect, Reflect},
+    time::{Time, Timer, TimerMode},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    clock::GameClock,
+    maps::{Coordinate, EntityGridMap},
+    sprites::{FaceDirection, Facing},
+    ui::{request_chatgpt, summarize_line, BarkEvent, PrintConsoleEvent, TasksRuntime},
+    units::{CurrentInteractingNPC, NPC},
+};
+
+use super::{format_memories, DailyPlan, Memory, MemoryKind, MemoryScorer, MemoryStream};
+
+// NPCs start talking when they are within this many grid cells of each other.
+const CONVERSATION_RANGE: i32 = 1;
+// Lines in a conversation, counting both NPCs.
+const MAX_LINES: usize = 6;
+// Conversations running at once. Every line is a chatGPT request, so this is kept low.
+const MAX_CONVERSATIONS: usize = 2;
+const COOLDOWN_SECONDS: f32 = 120.;
+// A conversation ends when chatGPT doesn't reply in time, e.g. when the request failed.
+const REPLY_TIMEOUT_SECONDS: f32 = 30.;
+const PROMPT_MEMORIES: usize = 5;
+
+// Who an NPC is, used in prompts. Set by the `persona` field of LDtk entities.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct Persona(pub String);
+
+#[derive(Clone, Debug, Default, Reflect, FromReflect, Serialize, Deserialize)]
+pub struct Relationship {
+    // Grows when conversations go well, and shrinks when they don't.
+    pub affinity: i32,
+    pub conversations: u32,
+}
+
+// How an NPC feels about others, by their names. Names are used instead of entities, so it survives level reloads.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct Relationships(pub HashMap<String, Relationship>);
+
+// Conversation between two NPCs, spawned as its own entity. They take turns, starting with `speakers[0]`.
+#[derive(Component, Debug)]
+pub struct Conversation {
+    pub speakers: [Entity; 2],
+    // Names of the speakers, and their lines.
+    pub lines: Vec<(String, String)>,
+    // Sum of the moods of every line. Applied to both relationships when the conversation ends.
+    pub mood: i32,
+    // Whether a line was requested from chatGPT, and not answered yet.
+    pub waiting: bool,
+    pub timeout: Timer,
+}
+
+impl Conversation {
+    fn next_speaker(&self) -> (Entity, Entity) {
+        let [first, second] = self.speakers;
+        if self.lines.len().is_multiple_of(2) {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+}
+
+// Added to NPCs while they talk.
+#[derive(Component, Debug)]
+pub struct InConversation;
+
+// NPCs don't start another conversation until this finishes.
+#[derive(Component, Debug)]
+pub struct ConversationCooldown(pub Timer);
+
+// Next line of a conversation, spawned when chatGPT replies. Like `GPTResponse` for the player's questions.
+#[derive(Component, Debug)]
+pub struct ConversationReply {
+    pub conversation: Entity,
+    pub message: String,
+}
+
+// Starts conversations between NPCs that are near each other in `EntityGridMap`.
+#[allow(clippy::type_complexity)]
+pub fn start_npc_conversations(
+    mut commands: Commands,
+    entity_map: Res<EntityGridMap>,
+    conversation_query: Query<(), With<Conversation>>,
+    interacting_query: Query<&CurrentInteractingNPC>,
+    npc_query: Query<
+        (Entity, &Coordinate, &Visibility),
+        (
+            With<NPC>,
+            Without<InConversation>,
+            Without<ConversationCooldown>,
+        ),
+    >,
+    mut facing_query: Query<(&Transform, &mut Facing)>,
+) {
+    let mut active = conversation_query.iter().count();
+    // NPCs talking to the player, and NPCs that started talking in this frame.
+    let mut busy: Vec<Entity> = interacting_query
+        .iter()
+        .filter_map(|interacting_npc| interacting_npc.0)
+        .collect();
+    let is_free = |npc: Entity, busy: &Vec<Entity>| {
+        !busy.contains(&npc)
+            && npc_query
+                .get(npc)
+                .is_ok_and(|(_, _, visibility)| *visibility != Visibility::Hidden)
+    };
+
+    for (npc, coordinate, _) in npc_query.iter() {
+        if active >= MAX_CONVERSATIONS {
+            break;
+        }
+        if !is_free(npc, &busy) {
+            continue;
+        }
+
+        let partner = (coordinate.min_x - CONVERSATION_RANGE
+            ..=coordinate.max_x + CONVERSATION_RANGE)
+            .flat_map(|x| {
+                (coordinate.min_y - CONVERSATION_RANGE..=coordinate.max_y + CONVERSATION_RANGE)
+                    .map(move |y| (x, y))
+            })
+            .filter_map(|cell| entity_map.get(cell))
+            .flatten()
+            .copied()
+            .find(|&other| other != npc && is_free(other, &busy));
+        let Some(partner) = partner else {
+            continue;
+        };
+
+        commands.spawn(Conversation {
+            speakers: [npc, partner],
+            lines: Vec::new(),
+            mood: 0,
+            waiting: false,
+            timeout: Timer::from_seconds(REPLY_TIMEOUT_SECONDS, TimerMode::Once),
+        });
+        commands.entity(npc).insert(InConversation);
+        commands.entity(partner).insert(InConversation);
+        busy.extend([npc, partner]);
+        active += 1;
+
+        // They turn to each other.
+        if let Ok([(npc_transform, mut npc_facing), (partner_transform, mut partner_facing)]) =
+            facing_query.get_many_mut([npc, partner])
+        {
+            let direction = (partner_transform.translation - npc_transform.translation).truncate();
+            if direction != Vec2::ZERO {
+                npc_facing.direction = FaceDirection::from_vector(direction);
+                partner_facing.direction = FaceDirection::from_vector(-direction);
+            }
+        }
+    }
+}
+
+// Prompt for the next line of `speaker`, with what they remember about `listener` and the conversation so far.
+fn build_prompt(
+    speaker: (&Name, &Persona, &DailyPlan, &Relationships),
+    listener: (&Name, &Persona),
+    memories: &[Memory],
+    conversation: &Conversation,
+) -> String {
+    let (speaker_name, speaker_persona, plan, relationships) = speaker;
+    let (listener_name, listener_persona) = listener;
+    let relationship = relationships
+        .0
+        .get(listener_name.as_str())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut prompt = format!(
+        "You are {}, a villager. {}\nYou meet {}. {}\n",
+        speaker_name, speaker_persona.0, listener_name, listener_persona.0
+    );
+    if !plan.0.is_empty() {
+        prompt += &format!("Your plan for today: {}\n", plan.0);
+    }
+    prompt += &format!(
+        "You have talked {} times before. How much you like them, from -10 to 10: {}\n",
+        relationship.conversations, relationship.affinity
+    );
+
+    if !memories.is_empty() {
+        prompt += &format!("What you remember:\n{}", format_memories(memories));
+    }
+
+    if !conversation.lines.is_empty() {
+        prompt += "The conversation so far:\n";
+        for (name, line) in conversation.lines.iter() {
+            prompt += &format!("{}: {}\n", name, line);
+        }
+    }
+
+    prompt += &format!(
+        "Say your next line to {} in one or two short sentences, without your name. ",
+        listener_name
+    );
+    if conversation.lines.len() + 1 >= MAX_LINES {
+        prompt += "It is the last line, so say goodbye. ";
+    }
+    prompt += "End it with [+] if you are enjoying the conversation, or [-] if you are not.";
+    prompt
+}
+
+// Asks chatGPT for the next line of each conversation, in the voice of the NPC whose turn it is.
+pub fn request_conversation_lines(
+    runtime: Res<TasksRuntime>,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    mut conversation_query: Query<(Entity, &mut Conversation)>,
+    mut npc_query: Query<(
+        &Name,
+        &Persona,
+        &DailyPlan,
+        &Relationships,
+        &mut MemoryStream,
+    )>,
+) {
+    for (entity, mut conversation) in conversation_query.iter_mut() {
+        if conversation.waiting || conversation.lines.len() >= MAX_LINES {
+            continue;
+        }
+
+        let (speaker, listener) = conversation.next_speaker();
+        let Ok(
+            [(speaker_name, speaker_persona, plan, relationships, mut memory), (listener_name, listener_persona, ..)],
+        ) = npc_query.get_many_mut([speaker, listener])
+        else {
+            continue;
+        };
+
+        // Memories about the listener and the last line are the most relevant.
+        let query = match conversation.lines.last() {
+            Some((_, line)) => format!("{} {}", listener_name, line),
+            None => listener_name.to_string(),
+        };
+        let memories = memory.retrieve(&query, clock.total_minutes(), PROMPT_MEMORIES, &scorer);
+        let prompt = build_prompt(
+            (speaker_name, speaker_persona, plan, relationships),
+            (listener_name, listener_persona),
+            &memories,
+            &conversation,
+        );
+
+        conversation.waiting = true;
+        conversation.timeout.reset();
+        request_chatgpt(&runtime, prompt, move |world, message| {
+            world.spawn(ConversationReply {
+                conversation: entity,
+                message,
+            });
+        });
+    }
+}
+
+// Splits the `[+]` or `[-]` mood tag off a line.
+fn take_mood(message: &str) -> (String, i32) {
+    let line = message.trim();
+    if let Some(line) = line.strip_suffix("[+]") {
+        (line.trim_end().to_string(), 1)
+    } else if let Some(line) = line.strip_suffix("[-]") {
+        (line.trim_end().to_string(), -1)
+    } else {
+        (line.to_string(), 0)
+    }
+}
+
+// Adds replies to their conversations. Both NPCs remember the line, and the speaker says it in a speech bubble.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_conversation_replies(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    reply_query: Query<(Entity, &ConversationReply)>,
+    mut conversation_query: Query<&mut Conversation>,
+    mut memory_query: Query<(&Name, &mut MemoryStream)>,
+    mut bark_writer: EventWriter<BarkEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    for (entity, reply) in reply_query.iter() {
+        commands.entity(entity).despawn();
+
+        let Ok(mut conversation) = conversation_query.get_mut(reply.conversation) else {
+            continue;
+        };
+        let (speaker, listener) = conversation.next_speaker();
+        let (line, mood) = take_mood(&reply.message);
+        conversation.waiting = false;
+        // The reply is dropped if one of them is gone. `end_conversations` ends the conversation then.
+        let Ok([(speaker_name, mut speaker_memory), (listener_name, mut listener_memory)]) =
+            memory_query.get_many_mut([speaker, listener])
+        else {
+            continue;
+        };
+        conversation.mood += mood;
+        conversation
+            .lines
+            .push((speaker_name.to_string(), line.clone()));
+
+        let timestamp = clock.total_minutes();
+        speaker_memory.record(
+            timestamp,
+            MemoryKind::Conversation,
+            format!("I said to {}: \"{}\"", listener_name, line),
+            &scorer,
+        );
+        listener_memory.record(
+            timestamp,
+            MemoryKind::Conversation,
+            format!("{} said to me: \"{}\"", speaker_name, line),
+            &scorer,
+        );
+
+        // The whole conversation can be read in the consoles of both NPCs.
+        for npc in [speaker, listener] {
+            console_writer.send(PrintConsoleEvent {
+                npc,
+                message: format!("{}: {}", speaker_name, line),
+            });
+        }
+
+        bark_writer.send(BarkEvent {
+            speaker,
+            message: summarize_line(&line),
+        });
+    }
+}
+
+// Ends conversations after the last line, or when one of the NPCs is gone. Both relationships are updated.
+pub fn end_conversations(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut conversation_query: Query<(Entity, &mut Conversation)>,
+    visibility_query: Query<&Visibility, With<NPC>>,
+    mut relationship_query: Query<(&Name, &mut Relationships)>,
+) {
+    for (entity, mut conversation) in conversation_query.iter_mut() {
+        let timed_out = conversation.waiting && conversation.timeout.tick(time.delta()).finished();
+        let finished = !conversation.waiting && conversation.lines.len() >= MAX_LINES;
+        let left = conversation.speakers.iter().any(|&speaker| {
+            visibility_query
+                .get(speaker)
+                .map_or(true, |visibility| *visibility == Visibility::Hidden)
+        });
+        if !timed_out && !finished && !left {
+            continue;
+        }
+
+        if !conversation.lines.is_empty() {
+            if let Ok([(first_name, mut first), (second_name, mut second)]) =
+                relationship_query.get_many_mut(conversation.speakers)
+            {
+                let mood = conversation.mood.signum();
+                for (relationships, other) in [(&mut first, second_name), (&mut second, first_name)]
+                {
+                    let relationship = relationships.0.entry(other.to_string()).or_default();
+                    relationship.conversations += 1;
+                    relationship.affinity = (relationship.affinity + mood).clamp(-10, 10);
+                }
+            }
+        }
+
+        for speaker in conversation.speakers {
+            if let Some(mut speaker_commands) = commands.get_entity(speaker) {
+                speaker_commands
+                    .remove::<InConversation>()
+                    .insert(ConversationCooldown(Timer::from_seconds(
+                        COOLDOWN_SECONDS,
+                        TimerMode::Once,
+                    )));
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn tick_conversation_cooldowns(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ConversationCooldown)>,
+) {
+    for (entity, mut cooldown) in query.iter_mut() {
+        if cooldown.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<ConversationCooldown>();
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use bevy::{
+    prelude::{Commands, Component, Entity, EventReader, Name, Query, Res, Resource},
+    reflect::{FromReflect, Reflect},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    clock::GameClock,
+    combat::DamageEvent,
+    ui::{request_chatgpt, TasksRuntime},
+};
+
+use super::Persona;
+
+// Memories over this count are forgotten, starting from the old and unimportant ones.
+const MAX_MEMORIES: usize = 500;
+// Recency is multiplied by this for every in-game hour since a memory was last retrieved.
+const RECENCY_DECAY_PER_HOUR: f64 = 0.99;
+// NPCs reflect when the importance of their memories since the last reflection adds up to this.
+const REFLECTION_THRESHOLD: f32 = 50.;
+const REFLECTION_MEMORIES: usize = 10;
+// In-game minutes between reflections of an NPC, so a fight doesn't ask chatGPT after every swing.
+const REFLECTION_COOLDOWN_MINUTES: f64 = 60.;
+// Attacks by the same unit within these in-game minutes are remembered as one.
+const ATTACK_MEMORY_MINUTES: f64 = 10.;
+
+const STOP_WORDS: [&str; 16] = [
+    "the", "and", "you", "your", "are", "was", "were", "for", "that", "this", "with", "have",
+    "but", "not", "said", "what",
+];
+// Words that make a memory more important for the `KeywordScorer`.
+const IMPORTANT_WORDS: [&str; 16] = [
+    "attack", "hit", "fight", "hurt", "die", "love", "hate", "friend", "party", "gift", "help",
+    "secret", "sorry", "thank", "marry", "birthday",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum MemoryKind {
+    // Something that happened to the NPC, like being attacked.
+    Observation,
+    // A line said or heard.
+    Conversation,
+    // Insight drawn from other memories by chatGPT.
+    Reflection,
+    Plan,
+}
+
+#[derive(Clone, Debug, Reflect, FromReflect, Serialize, Deserialize)]
+pub struct Memory {
+    // `GameClock::total_minutes` when it was recorded.
+    pub timestamp: f64,
+    // Memories that are recalled often stay recent.
+    pub last_accessed: f64,
+    pub kind: MemoryKind,
+    pub text: String,
+    // From 1, mundane, to 10, life changing.
+    pub importance: f32,
+}
+
+impl Memory {
+    // From 0 to 1.
+    fn recency(&self, now: f64) -> f32 {
+        let hours = (now - self.last_accessed).max(0.) / 60.;
+        RECENCY_DECAY_PER_HOUR.powf(hours) as f32
+    }
+}
+
+// Scores memories for `MemoryStream`. Replace `MemoryScorer` to use another one, e.g. with embedding similarity.
+pub trait ScoreMemory: Send + Sync {
+    // From 1 to 10.
+    fn importance(&self, kind: MemoryKind, text: &str) -> f32;
+    // How much `text` is about `query`, from 0 to 1.
+    fn relevance(&self, query: &str, text: &str) -> f32;
+}
+
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+// Scores by shared words, so memories work offline without any model.
+pub struct KeywordScorer;
+
+impl ScoreMemory for KeywordScorer {
+    fn importance(&self, kind: MemoryKind, text: &str) -> f32 {
+        let base = match kind {
+            MemoryKind::Observation => 2.,
+            MemoryKind::Conversation => 3.,
+            MemoryKind::Plan => 4.,
+            MemoryKind::Reflection => 6.,
+        };
+        let words = keywords(text);
+        let important = IMPORTANT_WORDS
+            .iter()
+            .filter(|important| words.iter().any(|word| word.starts_with(*important)))
+            .count();
+        (base + 2. * important as f32).min(10.)
+    }
+
+    fn relevance(&self, query: &str, text: &str) -> f32 {
+        let query = keywords(query);
+        if query.is_empty() {
+            return 0.;
+        }
+        let text = keywords(text);
+        query.intersection(&text).count() as f32 / query.len() as f32
+    }
+}
+
+#[derive(Resource)]
+pub struct MemoryScorer(pub Box<dyn ScoreMemory>);
+
+impl Default for MemoryScorer {
+    fn default() -> Self {
+        MemoryScorer(Box::new(KeywordScorer))
+    }
+}
+
+// Timestamped memories of an NPC, oldest first. Memories are retrieved for prompts like in generative agents,
+// by how recent, important and relevant they are.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct MemoryStream {
+    pub memories: Vec<Memory>,
+    // Sum of the importance of memories since the last reflection.
+    pub unreflected_importance: f32,
+    // `GameClock::total_minutes` of the last reflection.
+    pub last_reflection: Option<f64>,
+}
+
+impl MemoryStream {
+    pub fn record(&mut self, now: f64, kind: MemoryKind, text: String, scorer: &MemoryScorer) {
+        let importance = scorer.0.importance(kind, &text).clamp(1., 10.);
+        if kind != MemoryKind::Reflection {
+            self.unreflected_importance += importance;
+        }
+        self.memories.push(Memory {
+            timestamp: now,
+            last_accessed: now,
+            kind,
+            text,
+            importance,
+        });
+
+        if self.memories.len() > MAX_MEMORIES {
+            let forgotten = self
+                .memories
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    (a.recency(now) * a.importance).total_cmp(&(b.recency(now) * b.importance))
+                })
+                .map(|(index, _)| index);
+            if let Some(index) = forgotten {
+                self.memories.remove(index);
+            }
+        }
+    }
+
+    // `count` memories with the highest sum of recency, importance and relevance to `query`, oldest first.
+    // They are marked as accessed, so they stay recent.
+    pub fn retrieve(
+        &mut self,
+        query: &str,
+        now: f64,
+        count: usize,
+        scorer: &MemoryScorer,
+    ) -> Vec<Memory> {
+        let mut scores: Vec<(usize, f32)> = self
+            .memories
+            .iter()
+            .enumerate()
+            .map(|(index, memory)| {
+                let score = memory.recency(now)
+                    + memory.importance / 10.
+                    + scorer.0.relevance(query, &memory.text);
+                (index, score)
+            })
+            .collect();
+        scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        scores.truncate(count);
+        scores.sort_by_key(|&(index, _)| index);
+
+        scores
+            .into_iter()
+            .map(|(index, _)| {
+                let memory = &mut self.memories[index];
+                memory.last_accessed = now;
+                memory.clone()
+            })
+            .collect()
+    }
+}
+
+// Lines for prompts, like `- (Day 2 13:05) Maria said to me: "Hi!"`.
+pub fn format_memories(memories: &[Memory]) -> String {
+    memories
+        .iter()
+        .map(|memory| {
+            let clock = GameClock::at(memory.timestamp);
+            format!("- ({}) {}\n", clock.display(), memory.text)
+        })
+        .collect()
+}
+
+// Insights from chatGPT, spawned like `GPTResponse`.
+#[derive(Component, Debug)]
+pub struct ReflectionReply {
+    pub npc: Entity,
+    pub message: String,
+}
+
+// NPCs remember who attacked them, once for each attacker in a fight.
+pub fn observe_damage(
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    mut damage_events: EventReader<DamageEvent>,
+    name_query: Query<&Name>,
+    mut memory_query: Query<&mut MemoryStream>,
+) {
+    let now = clock.total_minutes();
+    for event in damage_events.iter() {
+        let Ok(mut memory) = memory_query.get_mut(event.damaged_entity) else {
+            continue;
+        };
+        let attacker = name_query
+            .get(event.damageing_entity)
+            .map_or("Someone", |name| name.as_str());
+        let text = format!("{} attacked me.", attacker);
+        let remembered = memory.memories.iter().rev().any(|memory| {
+            now - memory.timestamp < ATTACK_MEMORY_MINUTES
+                && memory.kind == MemoryKind::Observation
+                && memory.text == text
+        });
+        if !remembered {
+            memory.record(now, MemoryKind::Observation, text, &scorer);
+        }
+    }
+}
+
+// Asks chatGPT for insights on recent memories, once enough important things happened.
+pub fn reflect_on_memories(
+    clock: Res<GameClock>,
+    runtime: Res<TasksRuntime>,
+    mut npc_query: Query<(Entity, &Name, &Persona, &mut MemoryStream)>,
+) {
+    let now = clock.total_minutes();
+    for (npc, name, persona, mut memory) in npc_query.iter_mut() {
+        if memory.unreflected_importance < REFLECTION_THRESHOLD
+            || memory
+                .last_reflection
+                .is_some_and(|last| now - last < REFLECTION_COOLDOWN_MINUTES)
+        {
+            continue;
+        }
+        memory.unreflected_importance = 0.;
+        memory.last_reflection = Some(now);
+
+        let mut recent: Vec<Memory> = memory
+            .memories
+            .iter()
+            .rev()
+            .filter(|memory| memory.kind != MemoryKind::Reflection)
+            .take(REFLECTION_MEMORIES)
+            .cloned()
+            .collect();
+        recent.reverse();
+
+        let prompt = format!(
+            "You are {}. {}\nYour recent memories:\n{}\
+             What are one or two insights about yourself or others you can draw from them? \
+             Write each in one short sentence on its own line, as your own thoughts.",
+            name,
+            persona.0,
+            format_memories(&recent)
+        );
+        request_chatgpt(&runtime, prompt, move |world, message| {
+            world.spawn(ReflectionReply { npc, message });
+        });
+    }
+}
+
+pub fn handle_reflection_replies(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    reply_query: Query<(Entity, &ReflectionReply)>,
+    mut memory_query: Query<&mut MemoryStream>,
+) {
+    for (entity, reply) in reply_query.iter() {
+        commands.entity(entity).despawn();
+
+        let Ok(mut memory) = memory_query.get_mut(reply.npc) else {
+            continue;
+        };
+        for line in reply.message.lines() {
+            let insight = line.trim_start_matches(|c: char| {
+                c == '-' || c == '*' || c == '.' || c.is_ascii_digit() || c.is_whitespace()
+            });
+            if !insight.is_empty() {
+                memory.record(
+                    clock.total_minutes(),
+                    MemoryKind::Reflection,
+                    insight.to_string(),
+                    &scorer,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(texts: &[(MemoryKind, &str)]) -> MemoryStream {
+        let scorer = MemoryScorer::default();
+        let mut stream = MemoryStream::default();
+        for (minute, (kind, text)) in texts.iter().enumerate() {
+            stream.record(minute as f64, *kind, text.to_string(), &scorer);
+        }
+        stream
+    }
+
+    #[test]
+    fn keyword_scorer_rates_important_words() {
+        let scorer = KeywordScorer;
+        assert_eq!(
+            scorer.importance(MemoryKind::Observation, "It rained today."),
+            2.
+        );
+        assert_eq!(
+            scorer.importance(MemoryKind::Observation, "Bob attacked me."),
+            4.
+        );
+        assert_eq!(
+            scorer.importance(
+                MemoryKind::Reflection,
+                "I hate fights, they hurt my friend."
+            ),
+            10.
+        );
+    }
+
+    #[test]
+    fn keyword_scorer_relevance_ignores_case_and_stop_words() {
+        let scorer = KeywordScorer;
+        assert_eq!(scorer.relevance("the apples", "Apples are red."), 1.);
+        assert_eq!(scorer.relevance("apples and pears", "I sold apples."), 0.5);
+        assert_eq!(scorer.relevance("what was that", "I sold apples."), 0.);
+    }
+
+    #[test]
+    fn retrieve_returns_relevant_memories_oldest_first() {
+        let mut stream = stream(&[
+            (MemoryKind::Conversation, "Maria talked about apples."),
+            (MemoryKind::Observation, "It rained today."),
+            (MemoryKind::Conversation, "Bob bought apples at the market."),
+            (MemoryKind::Observation, "The bakery opened."),
+        ]);
+        let scorer = MemoryScorer::default();
+
+        let memories = stream.retrieve("apples", 10., 2, &scorer);
+        let texts: Vec<&str> = memories.iter().map(|memory| memory.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Maria talked about apples.",
+                "Bob bought apples at the market."
+            ]
+        );
+        assert!(stream
+            .memories
+            .iter()
+            .filter(|memory| memory.text.contains("apples"))
+            .all(|memory| memory.last_accessed == 10.));
+        assert_eq!(stream.memories[1].last_accessed, 1.);
+    }
+
+    #[test]
+    fn record_forgets_unimportant_memories_over_the_limit() {
+        let scorer = MemoryScorer::default();
+        let mut stream = MemoryStream::default();
+        stream.record(
+            0.,
+            MemoryKind::Reflection,
+            "I love Maria.".to_string(),
+            &scorer,
+        );
+        for minute in 1..=MAX_MEMORIES {
+            stream.record(
+                minute as f64,
+                MemoryKind::Observation,
+                "It rained.".to_string(),
+                &scorer,
+            );
+        }
+        assert_eq!(stream.memories.len(), MAX_MEMORIES);
+        assert_eq!(stream.memories[0].text, "I love Maria.");
+    }
+}
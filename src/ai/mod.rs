@@ -1,12 +1,27 @@
-use bevy::prelude::{App, IntoSystemConfig, IntoSystemConfigs, Plugin, Transform};
+use bevy::prelude::{
+    App, IntoSystemConfig, IntoSystemConfigs, IntoSystemSetConfig, OnUpdate, Plugin, Transform,
+};
 use big_brain::{BigBrainPlugin, BigBrainSet};
 use seldom_map_nav::prelude::MapNavPlugin;
 
-use crate::maps::insert_wall;
+use crate::{
+    input::update_cursor_pos,
+    maps::insert_terrain,
+    state::AppState,
+    ui::{world_running, ConsoleCommandAppExt, ConsoleCommandSpec},
+};
 
+pub mod conversation;
+pub mod memory;
+pub mod party;
 pub mod path_finder;
+pub mod planning;
 pub mod state_machine;
+pub use conversation::*;
+pub use memory::*;
+pub use party::*;
 pub use path_finder::*;
+pub use planning::*;
 pub use state_machine::*;
 
 pub struct AIPlugin;
@@ -18,15 +33,65 @@ impl Plugin for AIPlugin {
             // The type parameter is the position component that you use
             .add_plugin(MapNavPlugin::<Transform>::default())
             .add_plugin(BigBrainPlugin)
-            .init_resource::<CursorPos>()
+            // Thinkers would keep picking actions, and actions keep moving NPCs, while the game is paused.
+            .configure_set(BigBrainSet::Scorers.run_if(world_running))
+            .configure_set(BigBrainSet::Thinkers.run_if(world_running))
+            .configure_set(BigBrainSet::Actions.run_if(world_running))
             .add_event::<OrderMovementEvent>()
-            .add_system(setup.after(insert_wall))
-            .add_systems((update_cursor_pos, move_player_when_mouse_click).chain())
+            .add_event::<PartyEvent>()
+            .init_resource::<MemoryScorer>()
+            .add_console_command(
+                ConsoleCommandSpec::new("recruit", "ask npc to join your party"),
+                recruit_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("dismiss", "let npc leave your party"),
+                dismiss_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("wait", "order party member to wait here"),
+                wait_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("follow", "order party member to follow you"),
+                follow_command,
+            )
+            .add_system(setup.after(insert_terrain))
+            .add_system(move_player_when_mouse_click.after(update_cursor_pos))
             .add_system(processing_order_movement_event)
+            .add_system(follow_waypoints)
+            .add_system(apply_terrain_speed)
             .add_system(setup_thinkers)
             .add_systems((push_target_in_range, update_distance_from_target).chain())
             .add_system(remove_target_if_out_of_range)
-            .add_system(move_toward_target.in_set(BigBrainSet::Actions))
-            .add_system(distance_scorer.in_set(BigBrainSet::Scorers));
+            .add_systems(
+                (
+                    handle_party_events,
+                    update_formation_slots,
+                    teleport_stragglers,
+                )
+                    .chain(),
+            )
+            .add_system(assist_leader_on_damage)
+            .add_system(start_npc_conversations.in_set(OnUpdate(AppState::MainGame)))
+            .add_systems(
+                (
+                    handle_conversation_replies,
+                    end_conversations,
+                    request_conversation_lines,
+                )
+                    .chain()
+                    .after(start_npc_conversations),
+            )
+            .add_system(tick_conversation_cooldowns)
+            .add_system(observe_damage)
+            .add_systems((handle_reflection_replies, reflect_on_memories).chain())
+            .add_systems((plan_day, handle_plan_replies))
+            .add_systems(
+                (move_toward_target, follow_leader, assist_leader).in_set(BigBrainSet::Actions),
+            )
+            .add_systems(
+                (distance_scorer, follow_leader_scorer, assist_scorer).in_set(BigBrainSet::Scorers),
+            );
     }
 }
@@ -0,0 +1,595 @@
+use bevy::{
+    prelude::{
+        debug, info, BuildChildren, Commands, Component, DespawnRecursiveExt, Entity, EventReader,
+        EventWriter, In, Name, Query, Transform, Vec2, Visibility, With, Without,
+    },
+    reflect::Reflect,
+    time::{Timer, TimerMode},
+    utils::HashMap,
+};
+use bevy_rapier2d::prelude::CollisionGroups;
+use big_brain::{
+    prelude::{ActionBuilder, ActionState, ScorerBuilder},
+    scorers::Score,
+    thinker::{ActionSpan, Actor},
+};
+use seldom_map_nav::prelude::{Nav, PathTarget, Pathfind};
+
+use crate::{
+    combat::{Attack, BodyLayers, DamageEvent, Delay},
+    constants::GRID_SIZE,
+    sprites::{AnimationIndices, AnimationState, FaceDirection, Facing},
+    ui::{ConsoleCommandInput, PrintConsoleEvent},
+    units::{Player, NPC},
+};
+
+use super::OrderMovementEvent;
+
+// Members further than this from their formation slot are teleported next to it.
+// This usually happens when the leader crosses a level transition.
+const TELEPORT_DISTANCE: f32 = 20. * GRID_SIZE;
+// Members start walking back to their slot when they drift further than this.
+const FOLLOW_DISTANCE: f32 = 2. * GRID_SIZE;
+const ATTACK_DISTANCE: f32 = 1.5 * GRID_SIZE;
+
+// Formation offsets from the leader. Members take the first free one.
+const FORMATION_OFFSETS: [(f32, f32); 4] = [(-1.5, -1.), (1.5, -1.), (-1.5, 1.), (1.5, 1.)];
+
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct PartyMember {
+    pub leader: Entity,
+    // `FormationSlot` entity that the member follows with `PathTarget::Dynamic`.
+    pub slot: Entity,
+    pub order: PartyOrder,
+    // Entity that the member fights, taken from `DamageEvent`s involving the leader.
+    pub assist_target: Option<Entity>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum PartyOrder {
+    #[default]
+    Follow,
+    Wait,
+}
+
+// Invisible entity that moves along with the leader at a fixed offset.
+// It is not a child of the leader, because `MapNavPlugin::<Transform>` reads the local `Transform` of dynamic targets.
+#[derive(Component, Clone, Debug)]
+pub struct FormationSlot {
+    pub leader: Entity,
+    // Place in the formation, see `formation_offset`.
+    pub index: usize,
+    pub offset: Vec2,
+}
+
+// Members after the first four take the same places further from the leader.
+fn formation_offset(index: usize) -> Vec2 {
+    let (x, y) = FORMATION_OFFSETS[index % FORMATION_OFFSETS.len()];
+    let ring = (index / FORMATION_OFFSETS.len() + 1) as f32;
+    Vec2::new(x, y) * ring * GRID_SIZE
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartyCommand {
+    Recruit { leader: Entity },
+    Dismiss,
+    Wait,
+    Follow,
+}
+
+// Sent by console commands or dialogue outcomes to manage the party.
+pub struct PartyEvent {
+    pub member: Entity,
+    pub command: PartyCommand,
+}
+
+pub fn handle_party_events(
+    mut commands: Commands,
+    mut events: EventReader<PartyEvent>,
+    mut members: Query<&mut PartyMember>,
+    npcs: Query<(), With<NPC>>,
+    transforms: Query<&Transform>,
+    slots: Query<(Entity, &FormationSlot)>,
+) {
+    // Slots spawned and despawned by earlier events of this frame, which `slots` doesn't see yet.
+    let mut new_slots: Vec<(Entity, usize)> = Vec::new();
+    let mut removed_slots: Vec<Entity> = Vec::new();
+
+    for PartyEvent { member, command } in events.iter() {
+        if !npcs.contains(*member) {
+            continue;
+        }
+
+        match command {
+            PartyCommand::Recruit { leader } => {
+                if members.contains(*member) {
+                    continue;
+                }
+                let Ok(leader_transform) = transforms.get(*leader) else {
+                    continue;
+                };
+
+                let taken: Vec<usize> = slots
+                    .iter()
+                    .filter(|(entity, slot)| {
+                        slot.leader == *leader && !removed_slots.contains(entity)
+                    })
+                    .map(|(_, slot)| slot.index)
+                    .chain(
+                        new_slots
+                            .iter()
+                            .filter(|(slot_leader, _)| slot_leader == leader)
+                            .map(|(_, index)| *index),
+                    )
+                    .collect();
+                let index = (0..).find(|index| !taken.contains(index)).unwrap();
+                let offset = formation_offset(index);
+                new_slots.push((*leader, index));
+
+                let slot = commands
+                    .spawn((
+                        FormationSlot {
+                            leader: *leader,
+                            index,
+                            offset,
+                        },
+                        Transform::from_translation(
+                            leader_transform.translation + offset.extend(0.),
+                        ),
+                    ))
+                    .id();
+                commands.entity(*member).insert(PartyMember {
+                    leader: *leader,
+                    slot,
+                    order: PartyOrder::Follow,
+                    assist_target: None,
+                });
+                info!("{:?} joined the party of {:?}", member, leader);
+            }
+            PartyCommand::Dismiss => {
+                if let Ok(party_member) = members.get(*member) {
+                    if let Some(slot) = commands.get_entity(party_member.slot) {
+                        slot.despawn_recursive();
+                    }
+                    removed_slots.push(party_member.slot);
+                    commands
+                        .entity(*member)
+                        .remove::<(PartyMember, Nav, Pathfind)>();
+                    info!("{:?} left the party", member);
+                }
+            }
+            PartyCommand::Wait => {
+                if let Ok(mut party_member) = members.get_mut(*member) {
+                    party_member.order = PartyOrder::Wait;
+                    party_member.assist_target = None;
+                    commands.entity(*member).remove::<(Nav, Pathfind)>();
+                }
+            }
+            PartyCommand::Follow => {
+                if let Ok(mut party_member) = members.get_mut(*member) {
+                    party_member.order = PartyOrder::Follow;
+                }
+            }
+        }
+    }
+}
+
+pub fn update_formation_slots(
+    mut slots: Query<(&FormationSlot, &mut Transform)>,
+    leaders: Query<&Transform, Without<FormationSlot>>,
+) {
+    for (slot, mut transform) in slots.iter_mut() {
+        if let Ok(leader_transform) = leaders.get(slot.leader) {
+            transform.translation = leader_transform.translation + slot.offset.extend(0.);
+        }
+    }
+}
+
+// Brings back members that fell too far behind, or dismisses them if the leader is gone.
+pub fn teleport_stragglers(
+    mut commands: Commands,
+    mut members: Query<(Entity, &PartyMember, &mut Transform), Without<FormationSlot>>,
+    slots: Query<&Transform, With<FormationSlot>>,
+    mut party_writer: EventWriter<PartyEvent>,
+) {
+    for (entity, party_member, mut transform) in members.iter_mut() {
+        let Ok(slot_transform) = slots.get(party_member.slot) else {
+            party_writer.send(PartyEvent {
+                member: entity,
+                command: PartyCommand::Dismiss,
+            });
+            continue;
+        };
+
+        if party_member.order == PartyOrder::Follow
+            && transform.translation.distance(slot_transform.translation) > TELEPORT_DISTANCE
+        {
+            transform.translation.x = slot_transform.translation.x;
+            transform.translation.y = slot_transform.translation.y;
+            commands.entity(entity).remove::<(Nav, Pathfind)>();
+        }
+    }
+}
+
+// Leader of the party that `entity` belongs to. Units outside of a party lead themselves.
+fn party_leader(members: &Query<&PartyMember>, entity: Entity) -> Entity {
+    members
+        .get(entity)
+        .map_or(entity, |party_member| party_member.leader)
+}
+
+// Whether `a` and `b` are the same unit, or a leader and members of the same party.
+pub fn same_party(members: &Query<&PartyMember>, a: Entity, b: Entity) -> bool {
+    party_leader(members, a) == party_leader(members, b)
+}
+
+// Party members fight whoever the leader hits, or whoever hits the leader.
+// Hits between the leader, the player and members of the party are ignored.
+pub fn assist_leader_on_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut members: Query<(Entity, &mut PartyMember)>,
+    players: Query<(), With<Player>>,
+) {
+    let leaders: HashMap<Entity, Entity> = members
+        .iter()
+        .map(|(entity, party_member)| (entity, party_member.leader))
+        .collect();
+    let leader_of = |entity| leaders.get(&entity).copied().unwrap_or(entity);
+
+    for event in damage_events.iter() {
+        if leader_of(event.damageing_entity) == leader_of(event.damaged_entity) {
+            continue;
+        }
+
+        for (entity, mut party_member) in members.iter_mut() {
+            if party_member.order == PartyOrder::Wait {
+                continue;
+            }
+
+            let target = if event.damageing_entity == party_member.leader {
+                event.damaged_entity
+            } else if event.damaged_entity == party_member.leader {
+                event.damageing_entity
+            } else {
+                continue;
+            };
+
+            if players.contains(target) {
+                continue;
+            }
+            if target != entity && party_member.assist_target != Some(target) {
+                party_member.assist_target = Some(target);
+            }
+        }
+    }
+}
+
+// Scorer
+#[derive(Clone, Component, Debug, ScorerBuilder)]
+pub struct FollowLeaderChecker;
+
+pub fn follow_leader_scorer(
+    members: Query<(&PartyMember, &Transform)>,
+    slots: Query<&Transform, With<FormationSlot>>,
+    mut scorer_query: Query<(&Actor, &mut Score), With<FollowLeaderChecker>>,
+) {
+    for (Actor(actor), mut score) in scorer_query.iter_mut() {
+        let Ok((party_member, transform)) = members.get(*actor) else {
+            score.set(0.);
+            continue;
+        };
+
+        if party_member.order != PartyOrder::Follow || party_member.assist_target.is_some() {
+            score.set(0.);
+            continue;
+        }
+
+        if let Ok(slot_transform) = slots.get(party_member.slot) {
+            // score increases as the member drifts away from its slot.
+            let distance = transform.translation.distance(slot_transform.translation);
+            score.set((distance / FOLLOW_DISTANCE).clamp(0., 1.));
+        }
+    }
+}
+
+// Scorer
+#[derive(Clone, Component, Debug, ScorerBuilder)]
+pub struct AssistChecker;
+
+pub fn assist_scorer(
+    members: Query<&PartyMember>,
+    visibilities: Query<&Visibility>,
+    mut scorer_query: Query<(&Actor, &mut Score), With<AssistChecker>>,
+) {
+    for (Actor(actor), mut score) in scorer_query.iter_mut() {
+        let has_target = members.get(*actor).is_ok_and(|party_member| {
+            party_member.assist_target.is_some_and(|target| {
+                visibilities
+                    .get(target)
+                    .is_ok_and(|visibility| *visibility != Visibility::Hidden)
+            })
+        });
+        score.set(if has_target { 1. } else { 0. });
+    }
+}
+
+// Action
+#[derive(Clone, Component, Debug, ActionBuilder, Reflect)]
+pub struct Follow {
+    pub speed: f32,
+}
+
+pub fn follow_leader(
+    mut actor_query: Query<(&Actor, &mut ActionState, &ActionSpan, &Follow)>,
+    members: Query<(&PartyMember, &Transform)>,
+    slots: Query<&Transform, With<FormationSlot>>,
+    mut movement_writer: EventWriter<OrderMovementEvent>,
+) {
+    for (Actor(actor), mut state, span, follow) in actor_query.iter_mut() {
+        let _guard = span.span().enter();
+
+        let slot = members
+            .get(*actor)
+            .ok()
+            .and_then(|(party_member, transform)| {
+                let slot_transform = slots.get(party_member.slot).ok()?;
+                (party_member.order == PartyOrder::Follow).then_some((
+                    party_member.slot,
+                    transform.translation.distance(slot_transform.translation),
+                ))
+            });
+
+        match *state {
+            ActionState::Requested => {
+                let Some((slot, _)) = slot else {
+                    *state = ActionState::Cancelled;
+                    continue;
+                };
+                debug!("Follow Start!");
+                movement_writer.send(OrderMovementEvent {
+                    mover: *actor,
+                    destination: PathTarget::Dynamic(slot),
+                    speed: follow.speed,
+                });
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => match slot {
+                None => *state = ActionState::Cancelled,
+                Some((_, distance)) if distance < GRID_SIZE / 2. => {
+                    debug!("Follow End!");
+                    *state = ActionState::Success;
+                }
+                _ => {}
+            },
+            ActionState::Cancelled => {
+                debug!("Follow Cancelled!");
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Action
+#[derive(Clone, Component, Debug, ActionBuilder, Reflect)]
+pub struct AssistLeader {
+    pub speed: f32,
+}
+
+pub fn assist_leader(
+    mut commands: Commands,
+    mut actor_query: Query<(&Actor, &mut ActionState, &ActionSpan, &AssistLeader)>,
+    mut members: Query<(
+        &mut PartyMember,
+        &Transform,
+        &mut Facing,
+        &mut AnimationIndices,
+        &mut Delay,
+    )>,
+    targets: Query<(&Transform, &Visibility)>,
+    navigating: Query<(), With<Nav>>,
+    mut movement_writer: EventWriter<OrderMovementEvent>,
+) {
+    for (Actor(actor), mut state, span, assist) in actor_query.iter_mut() {
+        let _guard = span.span().enter();
+
+        let Ok((mut party_member, transform, mut facing, mut indices, mut delay)) =
+            members.get_mut(*actor)
+        else {
+            *state = ActionState::Cancelled;
+            continue;
+        };
+
+        let target = party_member.assist_target.and_then(|target| {
+            let (target_transform, visibility) = targets.get(target).ok()?;
+            (*visibility != Visibility::Hidden).then_some((target, target_transform.translation))
+        });
+
+        match *state {
+            ActionState::Requested => {
+                let Some((target, _)) = target else {
+                    *state = ActionState::Cancelled;
+                    continue;
+                };
+                debug!("Assist Start!");
+                movement_writer.send(OrderMovementEvent {
+                    mover: *actor,
+                    destination: PathTarget::Dynamic(target),
+                    speed: assist.speed,
+                });
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let Some((target, target_translation)) = target else {
+                    debug!("Assist End!");
+                    party_member.assist_target = None;
+                    *state = ActionState::Success;
+                    continue;
+                };
+
+                let diff = (target_translation - transform.translation).truncate();
+                if diff.length() > ATTACK_DISTANCE {
+                    // Members stop walking to swing, so they chase the target again once it gets away.
+                    if !navigating.contains(*actor) {
+                        movement_writer.send(OrderMovementEvent {
+                            mover: *actor,
+                            destination: PathTarget::Dynamic(target),
+                            speed: assist.speed,
+                        });
+                    }
+                    continue;
+                }
+                // `Delay` only ticks while an attack is alive, so compare elapsed time instead of `finished`.
+                if delay.elapsed() < delay.duration() {
+                    continue;
+                }
+
+                facing.direction = if diff.x.abs() > diff.y.abs() {
+                    if diff.x > 0. {
+                        FaceDirection::Right
+                    } else {
+                        FaceDirection::Left
+                    }
+                } else if diff.y > 0. {
+                    FaceDirection::Up
+                } else {
+                    FaceDirection::Down
+                };
+
+                // Same attack as `combat::melee_attack`, but it hits on the party's side.
+                // It lands on the `hit` frame of the attack clip, so the member stops navigating, which would play walking instead.
+                indices.restart(AnimationState::Attack);
+                commands.entity(*actor).remove::<(Nav, Pathfind)>();
+                let attack_entity = commands
+                    .spawn(CollisionGroups::new(
+                        BodyLayers::PLAYER_ATTACK,
+                        BodyLayers::ENEMY,
+                    ))
+                    .insert(Attack {
+                        damage: 1,
+                        pushback: Vec2::ZERO,
+                        hitstun_duration: 1.,
+                        hit_entities: Vec::new(),
+                    })
+                    .id();
+                *delay = Delay(Timer::from_seconds(0.8, TimerMode::Once));
+                commands.entity(*actor).push_children(&[attack_entity]);
+            }
+            ActionState::Cancelled => {
+                debug!("Assist Cancelled!");
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn recruit_command(
+    In(input): In<ConsoleCommandInput>,
+    player_query: Query<Entity, With<Player>>,
+    members: Query<&PartyMember>,
+    name_query: Query<&Name>,
+    mut party_writer: EventWriter<PartyEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    send_party_command(
+        input.console,
+        PartyCommand::Recruit { leader: player },
+        &members,
+        &name_query,
+        &mut party_writer,
+        &mut console_writer,
+    );
+}
+
+pub fn dismiss_command(
+    In(input): In<ConsoleCommandInput>,
+    members: Query<&PartyMember>,
+    name_query: Query<&Name>,
+    mut party_writer: EventWriter<PartyEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    send_party_command(
+        input.console,
+        PartyCommand::Dismiss,
+        &members,
+        &name_query,
+        &mut party_writer,
+        &mut console_writer,
+    );
+}
+
+pub fn wait_command(
+    In(input): In<ConsoleCommandInput>,
+    members: Query<&PartyMember>,
+    name_query: Query<&Name>,
+    mut party_writer: EventWriter<PartyEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    send_party_command(
+        input.console,
+        PartyCommand::Wait,
+        &members,
+        &name_query,
+        &mut party_writer,
+        &mut console_writer,
+    );
+}
+
+pub fn follow_command(
+    In(input): In<ConsoleCommandInput>,
+    members: Query<&PartyMember>,
+    name_query: Query<&Name>,
+    mut party_writer: EventWriter<PartyEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    send_party_command(
+        input.console,
+        PartyCommand::Follow,
+        &members,
+        &name_query,
+        &mut party_writer,
+        &mut console_writer,
+    );
+}
+
+fn send_party_command(
+    npc: Entity,
+    command: PartyCommand,
+    members: &Query<&PartyMember>,
+    name_query: &Query<&Name>,
+    party_writer: &mut EventWriter<PartyEvent>,
+    console_writer: &mut EventWriter<PrintConsoleEvent>,
+) {
+    // Commands that wouldn't change anything are ignored without a reply.
+    let order = members.get(npc).ok().map(|party_member| party_member.order);
+    let changes = match command {
+        PartyCommand::Recruit { .. } => order.is_none(),
+        PartyCommand::Dismiss => order.is_some(),
+        PartyCommand::Wait => order.is_some_and(|order| order != PartyOrder::Wait),
+        PartyCommand::Follow => order.is_some_and(|order| order != PartyOrder::Follow),
+    };
+    if !changes {
+        return;
+    }
+
+    party_writer.send(PartyEvent {
+        member: npc,
+        command,
+    });
+
+    let reply = match command {
+        PartyCommand::Recruit { .. } => "I'll come with you.",
+        PartyCommand::Dismiss => "Take care. I'll stay around here.",
+        PartyCommand::Wait => "I'll wait here.",
+        PartyCommand::Follow => "Right behind you.",
+    };
+    let npc_name = name_query.get(npc).map_or("NPC", |name| name.as_str());
+    console_writer.send(PrintConsoleEvent {
+        npc,
+        message: format!("{}: {}", npc_name, reply),
+    });
+}
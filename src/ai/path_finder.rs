@@ -1,26 +1,195 @@
 // In this game, the player navigates to wherever you click
 
-use bevy::{
-    prelude::{
-        error, Camera, Camera2d, Commands, Deref, DerefMut, DetectChanges, Entity, EventReader,
-        EventWriter, GlobalTransform, Input, MouseButton, Query, Res, ResMut, Resource, UVec2,
-        Vec2, With,
-    },
-    reflect::Reflect,
-    window::Window,
+use bevy::prelude::{
+    error, Commands, Component, DetectChanges, Entity, EventReader, EventWriter, Query, Ref, Res,
+    Transform, UVec2, Vec2, With,
 };
 use seldom_map_nav::prelude::{
     Nav, NavBundle, NavPathMode, NavQuery, Navability, Navmeshes, PathTarget, Pathfind,
 };
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+};
 
 use crate::{
-    constants::{GRID_SIZE, UNIT_SIZE},
-    maps::{TileGridMap, TileType},
+    constants::{GRID_OFFSET, GRID_SIZE, UNIT_SIZE},
+    input::{Action, ActionInput, CursorPos},
+    maps::{walkable_terrain_at, MapConfig, TileGridMap},
     units::Player,
 };
 
+// Terrain paths give up after searching this many cells, and the navmesh path is used instead.
+const MAX_SEARCHED_CELLS: usize = 20_000;
+// Step of checking whether a straight line between path corners stays on cheap terrain.
+const LINE_CHECK_STEP: f32 = GRID_SIZE / 4.;
+
+// Corners of a path through cheap terrain, which `Nav` goes to one after another.
+// The navmesh only knows solid and navable tiles, so it can't weigh terrain by itself.
+#[derive(Component, Debug, Default)]
+pub struct Waypoints(pub VecDeque<Vec2>);
+
+// Speed of `OrderMovementEvent`. `Nav::speed` is this multiplied by the terrain under the mover.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BaseNavSpeed(pub f32);
+
+// Cell to search in `find_terrain_path`. The heap pops the lowest priority first.
+struct Frontier {
+    priority: f32,
+    coordinate: (i32, i32),
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for Frontier {}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+fn cell_center((x, y): (i32, i32)) -> Vec2 {
+    Vec2::new(
+        x as f32 * GRID_SIZE + GRID_OFFSET,
+        y as f32 * GRID_SIZE + GRID_OFFSET,
+    )
+}
+
+// Cost of walking through the cell, or `None` if it's blocking or outside of the map.
+fn path_cost(
+    tile_grid_map: &TileGridMap,
+    map_config: &MapConfig,
+    coordinate: (i32, i32),
+) -> Option<f32> {
+    let (_, tile_type) = tile_grid_map.tile_map.get(&coordinate)?;
+    let terrain = map_config.terrain(*tile_type);
+    (!terrain.blocking).then_some(terrain.path_cost.max(0.01))
+}
+
+// Cheapest path from `start` to `goal` by `Terrain::path_cost`, as corners ending with `goal`.
+// Corners are dropped where a straight line doesn't cross costlier terrain than the path around them.
+pub fn find_terrain_path(
+    tile_grid_map: &TileGridMap,
+    map_config: &MapConfig,
+    start: Vec2,
+    goal: Vec2,
+) -> Option<VecDeque<Vec2>> {
+    let cost = |coordinate| path_cost(tile_grid_map, map_config, coordinate);
+    let start_cell = TileGridMap::coordinate_of(start);
+    let goal_cell = TileGridMap::coordinate_of(goal);
+    cost(goal_cell)?;
+
+    // The cheapest terrain keeps the estimate under the real cost, so the found path is the cheapest.
+    let min_cost = map_config
+        .terrains
+        .values()
+        .filter(|terrain| !terrain.blocking)
+        .map(|terrain| terrain.path_cost.max(0.01))
+        .fold(1., f32::min);
+    let estimate =
+        |(x, y): (i32, i32)| ((x - goal_cell.0).abs() + (y - goal_cell.1).abs()) as f32 * min_cost;
+
+    let mut costs = HashMap::from([(start_cell, 0.)]);
+    let mut came_from = HashMap::new();
+    let mut frontier = BinaryHeap::from([Frontier {
+        priority: estimate(start_cell),
+        coordinate: start_cell,
+    }]);
+    while let Some(Frontier { coordinate, .. }) = frontier.pop() {
+        if coordinate == goal_cell {
+            break;
+        }
+        if costs.len() > MAX_SEARCHED_CELLS {
+            return None;
+        }
+        let current_cost = costs[&coordinate];
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let next = (coordinate.0 + dx, coordinate.1 + dy);
+            let Some(step_cost) = cost(next) else {
+                continue;
+            };
+            let next_cost = current_cost + step_cost;
+            if costs
+                .get(&next)
+                .is_none_or(|&old_cost| next_cost < old_cost)
+            {
+                costs.insert(next, next_cost);
+                came_from.insert(next, coordinate);
+                frontier.push(Frontier {
+                    priority: next_cost + estimate(next),
+                    coordinate: next,
+                });
+            }
+        }
+    }
+
+    let mut cells = vec![goal_cell];
+    while let Some(&previous) = came_from.get(cells.last()?) {
+        cells.push(previous);
+    }
+    if cells.last() != Some(&start_cell) {
+        return None;
+    }
+    cells.reverse();
+
+    // Whether a straight line between the cells doesn't cross blocking terrain or terrain costlier than `max_cost`.
+    // The cell it starts from is skipped, since the mover is already there.
+    let is_straight = |from_cell: (i32, i32), to_cell: (i32, i32), max_cost: f32| {
+        let (from, to) = (cell_center(from_cell), cell_center(to_cell));
+        let steps = (from.distance(to) / LINE_CHECK_STEP).ceil() as usize;
+        (0..=steps).all(|step| {
+            let coordinate =
+                TileGridMap::coordinate_of(from.lerp(to, step as f32 / steps.max(1) as f32));
+            coordinate == from_cell
+                || cost(coordinate).is_some_and(|step_cost| step_cost <= max_cost)
+        })
+    };
+    let mut waypoints = VecDeque::new();
+    let mut anchor = 0;
+    let mut max_cost = 0.;
+    for (index, &cell) in cells.iter().enumerate().skip(1) {
+        max_cost = cost(cell).map_or(max_cost, |step_cost| f32::max(max_cost, step_cost));
+        if !is_straight(cells[anchor], cell, max_cost) {
+            waypoints.push_back(cell_center(cells[index - 1]));
+            anchor = index - 1;
+            max_cost = cost(cell).unwrap_or(0.);
+        }
+    }
+    waypoints.push_back(goal);
+    Some(waypoints)
+}
+
+fn nav_bundle(navmeshes: Entity, target: PathTarget, speed: f32) -> NavBundle {
+    // Add `NavBundle` to start navigating to that position
+    // If you want to write your own movement, but still want paths generated,
+    // only insert `Pathfind`.
+    NavBundle {
+        pathfind: Pathfind::new(
+            navmeshes,
+            UNIT_SIZE - 0.01,
+            None,
+            target,
+            NavQuery::Accuracy,
+            NavPathMode::Accuracy,
+        ),
+        nav: Nav::new(speed),
+    }
+}
+
 pub fn setup(
     mut commands: Commands,
+    map_config: Res<MapConfig>,
     tile_grid_map: Res<TileGridMap>,
     navmesheses: Query<Entity, With<Navmeshes>>,
 ) {
@@ -32,9 +201,10 @@ pub fn setup(
         for y in 0..=max_y {
             for x in 0..=max_x {
                 if let Some((_, tile)) = tile_grid_map.tile_map.get(&(x, y)) {
-                    match tile {
-                        TileType::Floor => tilemap.push(Navability::Navable),
-                        TileType::Wall => tilemap.push(Navability::Solid),
+                    if map_config.terrain(*tile).blocking {
+                        tilemap.push(Navability::Solid);
+                    } else {
+                        tilemap.push(Navability::Navable);
                     }
                 } else {
                     tilemap.push(Navability::Solid);
@@ -65,30 +235,14 @@ pub fn setup(
     }
 }
 
-// TODO: CursorPos should be moduled into `input.rs`
-#[derive(Default, Deref, DerefMut, Resource, Reflect)]
-pub struct CursorPos(Option<Vec2>);
-
-pub fn update_cursor_pos(
-    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
-    windows: Query<&Window>,
-    mut pos: ResMut<CursorPos>,
-) {
-    let (camera, camera_transform) = camera_query.single();
-    let Some(cursor_position) = windows.single().cursor_position() else {return;};
-    let cursor_world_position = camera.viewport_to_world_2d(camera_transform, cursor_position);
-
-    **pos = cursor_world_position;
-}
-
 // Navigate the player to wherever you click
 pub fn move_player_when_mouse_click(
     players: Query<Entity, With<Player>>,
     cursor_pos: Res<CursorPos>,
-    mouse: Res<Input<MouseButton>>,
+    action_input: Res<ActionInput>,
     mut movement_writer: EventWriter<OrderMovementEvent>,
 ) {
-    if mouse.just_pressed(MouseButton::Right) {
+    if action_input.just_pressed(Action::MoveToCursor) {
         if let Some(cursor_pos) = **cursor_pos {
             // Clicked somewhere on the screen!
             movement_writer.send(OrderMovementEvent {
@@ -106,10 +260,15 @@ pub struct OrderMovementEvent {
     pub speed: f32,
 }
 
+// Static destinations are reached through `Waypoints` of the cheapest terrain path.
+// Entities are followed straight by the navmesh, since they keep moving.
 pub fn processing_order_movement_event(
     mut commands: Commands,
+    map_config: Res<MapConfig>,
+    tile_grid_map: Res<TileGridMap>,
     mut events: EventReader<OrderMovementEvent>,
     navmesheses: Query<Entity, With<Navmeshes>>,
+    transform_query: Query<&Transform>,
 ) {
     for OrderMovementEvent {
         mover: entity,
@@ -117,19 +276,157 @@ pub fn processing_order_movement_event(
         speed,
     } in events.iter()
     {
-        // Add `NavBundle` to start navigating to that position
-        // If you want to write your own movement, but still want paths generated,
-        // only insert `Pathfind`.
-        commands.entity(*entity).insert(NavBundle {
-            pathfind: Pathfind::new(
+        let mut target = *destination;
+        let mut waypoints = VecDeque::new();
+        if let (PathTarget::Static(goal), Ok(transform)) =
+            (destination, transform_query.get(*entity))
+        {
+            let start = transform.translation.truncate();
+            if let Some(mut path) = find_terrain_path(&tile_grid_map, &map_config, start, *goal) {
+                if let Some(first) = path.pop_front() {
+                    target = PathTarget::Static(first);
+                    waypoints = path;
+                }
+            }
+        }
+
+        commands.entity(*entity).insert((
+            nav_bundle(navmesheses.single(), target, *speed),
+            Waypoints(waypoints),
+            BaseNavSpeed(*speed),
+        ));
+    }
+}
+
+pub fn follow_waypoints(
+    mut commands: Commands,
+    navmesheses: Query<Entity, With<Navmeshes>>,
+    mut query: Query<(Entity, Ref<Nav>, &BaseNavSpeed, &mut Waypoints)>,
+) {
+    for (entity, nav, speed, mut waypoints) in query.iter_mut() {
+        // `Nav` may be done before its first path is generated.
+        if !nav.done || nav.is_added() {
+            continue;
+        }
+        if let Some(next) = waypoints.0.pop_front() {
+            commands.entity(entity).insert(nav_bundle(
                 navmesheses.single(),
-                UNIT_SIZE - 0.01,
-                None,
-                *destination,
-                NavQuery::Accuracy,
-                NavPathMode::Accuracy,
-            ),
-            nav: Nav::new(*speed),
-        });
+                PathTarget::Static(next),
+                speed.0,
+            ));
+        }
+    }
+}
+
+pub fn apply_terrain_speed(
+    map_config: Res<MapConfig>,
+    tile_grid_map: Res<TileGridMap>,
+    mut query: Query<(&Transform, &BaseNavSpeed, &mut Nav)>,
+) {
+    for (transform, base_speed, mut nav) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let terrain = walkable_terrain_at(&map_config, &tile_grid_map, position);
+        let speed = base_speed.0 * terrain.speed_multiplier;
+        if nav.speed != speed {
+            nav.speed = speed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::TileType;
+
+    // Floor of `width` x `height` cells with `tiles` over it.
+    fn grid_map(width: i32, height: i32, tiles: &[((i32, i32), TileType)]) -> TileGridMap {
+        let mut tile_grid_map = TileGridMap::new();
+        for x in 0..width {
+            for y in 0..height {
+                tile_grid_map.insert((x, y), Entity::from_raw(0), TileType::Floor);
+            }
+        }
+        for &(coordinate, tile_type) in tiles {
+            tile_grid_map.insert(coordinate, Entity::from_raw(0), tile_type);
+        }
+        tile_grid_map
+    }
+
+    #[test]
+    fn straight_path_on_floor_is_only_the_goal() {
+        let tile_grid_map = grid_map(6, 3, &[]);
+        let goal = cell_center((5, 0));
+        let path = find_terrain_path(
+            &tile_grid_map,
+            &MapConfig::default(),
+            cell_center((0, 0)),
+            goal,
+        );
+        assert_eq!(path, Some(VecDeque::from([goal])));
+    }
+
+    #[test]
+    fn path_goes_around_costly_terrain() {
+        // Mud is 3 cells wide on the two lower rows, so walking around it on the top row is cheaper.
+        let mud = (2..=4)
+            .flat_map(|x| [((x, 0), TileType::Mud), ((x, 1), TileType::Mud)])
+            .collect::<Vec<_>>();
+        let tile_grid_map = grid_map(7, 3, &mud);
+        let goal = cell_center((6, 0));
+        let path = find_terrain_path(
+            &tile_grid_map,
+            &MapConfig::default(),
+            cell_center((0, 0)),
+            goal,
+        )
+        .unwrap();
+
+        assert_eq!(path.back(), Some(&goal));
+        assert!(path
+            .iter()
+            .any(|corner| TileGridMap::coordinate_of(*corner).1 == 2));
+        assert!(path
+            .iter()
+            .all(|corner| tile_grid_map.tile_type_at(*corner) != Some(TileType::Mud)));
+    }
+
+    #[test]
+    fn path_crosses_costly_terrain_when_there_is_no_way_around() {
+        let mud = (0..3).map(|y| ((2, y), TileType::Mud)).collect::<Vec<_>>();
+        let tile_grid_map = grid_map(5, 3, &mud);
+        let goal = cell_center((4, 1));
+        let path = find_terrain_path(
+            &tile_grid_map,
+            &MapConfig::default(),
+            cell_center((0, 1)),
+            goal,
+        );
+        assert_eq!(path, Some(VecDeque::from([goal])));
+    }
+
+    #[test]
+    fn blocked_or_walled_in_goal_has_no_path() {
+        let walls = [
+            ((3, 0), TileType::Wall),
+            ((3, 1), TileType::Wall),
+            ((3, 2), TileType::Wall),
+            ((1, 1), TileType::DeepWater),
+        ];
+        let tile_grid_map = grid_map(5, 3, &walls);
+        let map_config = MapConfig::default();
+        let start = cell_center((0, 0));
+
+        assert_eq!(
+            find_terrain_path(&tile_grid_map, &map_config, start, cell_center((1, 1))),
+            None
+        );
+        assert_eq!(
+            find_terrain_path(&tile_grid_map, &map_config, start, cell_center((4, 1))),
+            None
+        );
+        assert_eq!(
+            find_terrain_path(&tile_grid_map, &map_config, start, cell_center((9, 9))),
+            None
+        );
     }
 }
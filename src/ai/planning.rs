@@ -0,0 +1,80 @@
+use bevy::{
+    prelude::{Commands, Component, Entity, EventReader, Name, Query, Res, With},
+    reflect::Reflect,
+};
+
+use crate::{
+    clock::{GameClock, NewDayEvent},
+    ui::{request_chatgpt, TasksRuntime},
+    units::NPC,
+};
+
+use super::{format_memories, MemoryKind, MemoryScorer, MemoryStream, Persona};
+
+const PLAN_MEMORIES: usize = 10;
+
+// What an NPC intends to do today, in their own words. Made by chatGPT at the start of every day.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct DailyPlan(pub String);
+
+#[derive(Component, Debug)]
+pub struct PlanReply {
+    pub npc: Entity,
+    pub message: String,
+}
+
+// Asks chatGPT for the plan of every NPC when a new day starts, from what they remember.
+pub fn plan_day(
+    runtime: Res<TasksRuntime>,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    mut new_day_events: EventReader<NewDayEvent>,
+    mut npc_query: Query<(Entity, &Name, &Persona, &mut MemoryStream), With<NPC>>,
+) {
+    let Some(NewDayEvent { day }) = new_day_events.iter().last() else {
+        return;
+    };
+
+    for (npc, name, persona, mut memory) in npc_query.iter_mut() {
+        let memories = memory.retrieve(
+            "plan today yesterday",
+            clock.total_minutes(),
+            PLAN_MEMORIES,
+            &scorer,
+        );
+        let prompt = format!(
+            "You are {}. {}\nIt is the morning of day {}. What you remember:\n{}\
+             Write your plan for today in one or two short sentences, as your own thoughts.",
+            name,
+            persona.0,
+            day,
+            format_memories(&memories)
+        );
+        request_chatgpt(&runtime, prompt, move |world, message| {
+            world.spawn(PlanReply { npc, message });
+        });
+    }
+}
+
+pub fn handle_plan_replies(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
+    reply_query: Query<(Entity, &PlanReply)>,
+    mut npc_query: Query<(&mut DailyPlan, &mut MemoryStream)>,
+) {
+    for (entity, reply) in reply_query.iter() {
+        commands.entity(entity).despawn();
+
+        let Ok((mut plan, mut memory)) = npc_query.get_mut(reply.npc) else {
+            continue;
+        };
+        plan.0 = reply.message.trim().to_string();
+        memory.record(
+            clock.total_minutes(),
+            MemoryKind::Plan,
+            format!("My plan for today: {}", plan.0),
+            &scorer,
+        );
+    }
+}
@@ -17,7 +17,9 @@ use crate::{
     units::{Player, NPC},
 };
 
-use super::OrderMovementEvent;
+use super::{
+    AssistChecker, AssistLeader, Follow, FollowLeaderChecker, OrderMovementEvent, PartyMember,
+};
 
 // TODO: Distance & Approach should be refactored. Redesign Component to be more suitable at big-brain pattern.
 // Score
@@ -215,9 +217,16 @@ pub struct DistanceChecker;
 
 pub fn distance_scorer(
     distances: Query<&Distance>,
+    party_members: Query<(), With<PartyMember>>,
     mut scorer_query: Query<(&Actor, &mut Score, &ScorerSpan), With<DistanceChecker>>,
 ) {
     for (Actor(actor), mut score, span) in scorer_query.iter_mut() {
+        // Party members keep their formation instead of approaching the player.
+        if party_members.contains(*actor) {
+            score.set(0.);
+            continue;
+        }
+
         if let Ok(distance) = distances.get(*actor) {
             if !distance.has_target() {
                 continue;
@@ -241,7 +250,9 @@ pub fn setup_thinkers(mut commands: Commands, npc: Query<Entity, Added<NPC>>) {
             Thinker::build()
                 .label("NPC Brain")
                 .picker(Highest)
-                .when(DistanceChecker, Approach { speed: 100. }),
+                .when(DistanceChecker, Approach { speed: 100. })
+                .when(FollowLeaderChecker, Follow { speed: 100. })
+                .when(AssistChecker, AssistLeader { speed: 100. }),
         ));
     }
 }
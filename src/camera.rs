@@ -1,83 +1,294 @@
-use bevy::prelude::{
-    App, Assets, Camera2dBundle, Commands, Handle, OrthographicProjection, Plugin, Query, Res,
-    Transform, Vec2, With, Without,
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::{
+        App, Assets, Camera2dBundle, Commands, Component, Entity, EventReader, Handle,
+        IntoSystemConfig, OrthographicProjection, Plugin, Query, Res, State, Transform, Vec2, With,
+        Without,
+    },
+    reflect::Reflect,
+    render::camera::ScalingMode,
+    time::Time,
+    window::{PrimaryWindow, Window},
 };
 use bevy_ecs_ldtk::{LdtkLevel, LevelSelection};
+use rand::Rng;
 
-use crate::{constants::ASPECT_RATIO, units::Player};
+use crate::{
+    combat::DamageEvent,
+    state::AppState,
+    ui::{ActiveConsole, CONSOLE_HEIGHT},
+    units::{Player, NPC},
+};
+
+// The player moves this far from the center before the camera follows.
+const DEADZONE: Vec2 = Vec2::new(24., 16.);
+// How fast the camera catches up with its target. Higher is snappier.
+const FOLLOW_SHARPNESS: f32 = 6.;
+const FOCUS_SHARPNESS: f32 = 3.;
+const ZOOM_SHARPNESS: f32 = 10.;
+// Targets further than this, like after level transitions and loading saves, are jumped to.
+const SNAP_DISTANCE: f32 = 256.;
+// Zoom 1 fits the level in the window on its shorter side.
+const MIN_ZOOM: f32 = 1.;
+const MAX_ZOOM: f32 = 3.;
+const ZOOM_PER_LINE: f32 = 0.1;
+const ZOOM_PER_PIXEL: f32 = 0.005;
+// Zoom multiplier while focusing on an NPC.
+const FOCUS_ZOOM: f32 = 1.5;
+// Trauma added by damage. Damage to or by the player shakes harder.
+const DAMAGE_TRAUMA: f32 = 0.15;
+const PLAYER_DAMAGE_TRAUMA: f32 = 0.45;
+const TRAUMA_DECAY: f32 = 1.5;
+const MAX_SHAKE_OFFSET: f32 = 6.;
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup)
-            .add_system(camera_fit_inside_current_level);
+            .add_system(update_camera_mode)
+            .add_system(zoom_camera)
+            .add_system(shake_camera_on_damage)
+            .add_system(
+                move_camera
+                    .after(update_camera_mode)
+                    .after(zoom_camera)
+                    .after(shake_camera_on_damage),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub enum CameraMode {
+    #[default]
+    FollowPlayer,
+    // Cinematic framing of the player and an NPC they talk to. The view is shifted above the console.
+    FocusNpc(Entity),
+}
+
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct GameCamera {
+    pub mode: CameraMode,
+    // Where the camera looks, before shaking and keeping inside the level.
+    pub center: Vec2,
+    pub zoom: f32,
+    // Zoom set by the mouse wheel. `zoom` eases towards it, or further in while focusing on an NPC.
+    pub target_zoom: f32,
+    // Strength of screen shake from 0 to 1. The offset grows with its square, so small hits barely shake.
+    pub trauma: f32,
+    // Set when `center` hasn't been placed yet, so the camera jumps to its first target.
+    snap: bool,
+}
+
+impl Default for GameCamera {
+    fn default() -> Self {
+        GameCamera {
+            mode: CameraMode::default(),
+            center: Vec2::ZERO,
+            zoom: MIN_ZOOM,
+            target_zoom: MIN_ZOOM,
+            trauma: 0.,
+            snap: true,
+        }
     }
 }
 
 pub fn setup(mut commands: Commands) {
-    let camera = Camera2dBundle::default();
-    commands.spawn(camera);
+    commands.spawn((Camera2dBundle::default(), GameCamera::default()));
 }
 
-#[allow(clippy::type_complexity)]
-pub fn camera_fit_inside_current_level(
-    mut camera_query: Query<
-        (
-            &mut bevy::render::camera::OrthographicProjection,
-            &mut Transform,
-        ),
-        Without<Player>,
-    >,
-    player_query: Query<&Transform, With<Player>>,
-    level_query: Query<
-        (&Transform, &Handle<LdtkLevel>),
-        (Without<OrthographicProjection>, Without<Player>),
-    >,
+// Focuses on the NPC whose console is open, and follows the player otherwise.
+pub fn update_camera_mode(
+    active_console: Res<ActiveConsole>,
+    npc_query: Query<(), With<NPC>>,
+    mut camera_query: Query<&mut GameCamera>,
+) {
+    let mode = match active_console.0 {
+        Some(npc) if npc_query.contains(npc) => CameraMode::FocusNpc(npc),
+        _ => CameraMode::FollowPlayer,
+    };
+    for mut camera in camera_query.iter_mut() {
+        if camera.mode != mode {
+            camera.mode = mode;
+        }
+    }
+}
+
+// Scrolling only zooms in game, since consoles and menus scroll their own lists.
+pub fn zoom_camera(
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    app_state: Res<State<AppState>>,
+    mut camera_query: Query<&mut GameCamera>,
+) {
+    if app_state.0 != AppState::MainGame {
+        mouse_wheel_events.clear();
+        return;
+    }
+    let scroll: f32 = mouse_wheel_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * ZOOM_PER_LINE,
+            MouseScrollUnit::Pixel => event.y * ZOOM_PER_PIXEL,
+        })
+        .sum();
+    if scroll == 0. {
+        return;
+    }
+    for mut camera in camera_query.iter_mut() {
+        camera.target_zoom = (camera.target_zoom + scroll).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+pub fn shake_camera_on_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    player_query: Query<(), With<Player>>,
+    mut camera_query: Query<&mut GameCamera>,
+) {
+    // An attack hits everything in range on the same frame, so the camera shakes once for each attacker.
+    let mut attacks: Vec<(Entity, f32)> = Vec::new();
+    for event in damage_events.iter() {
+        let trauma = if player_query.contains(event.damaged_entity)
+            || player_query.contains(event.damageing_entity)
+        {
+            PLAYER_DAMAGE_TRAUMA
+        } else {
+            DAMAGE_TRAUMA
+        };
+        match attacks
+            .iter_mut()
+            .find(|(attacker, _)| *attacker == event.damageing_entity)
+        {
+            Some((_, attack_trauma)) => *attack_trauma = attack_trauma.max(trauma),
+            None => attacks.push((event.damageing_entity, trauma)),
+        }
+    }
+
+    for (_, trauma) in attacks {
+        for mut camera in camera_query.iter_mut() {
+            camera.trauma = (camera.trauma + trauma).min(1.);
+        }
+    }
+}
+
+// Eases the camera towards its target, keeps the view inside the current level and applies screen shake.
+// The view keeps the aspect ratio of the window, and fits the level on its shorter side at zoom 1.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn move_camera(
+    time: Res<Time>,
     level_selection: Res<LevelSelection>,
     ldtk_levels: Res<Assets<LdtkLevel>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&mut GameCamera, &mut OrthographicProjection, &mut Transform)>,
+    player_query: Query<&Transform, (With<Player>, Without<GameCamera>)>,
+    npc_query: Query<&Transform, (With<NPC>, Without<GameCamera>)>,
+    level_query: Query<(&Transform, &Handle<LdtkLevel>), Without<GameCamera>>,
 ) {
-    if let Ok(Transform {
-        translation: player_translation,
-        ..
-    }) = player_query.get_single()
-    {
-        let player_translation = *player_translation;
-
-        let (mut orthographic_projection, mut camera_transform) = camera_query.single_mut();
-
-        for (level_transform, level_handle) in &level_query {
-            if let Some(ldtk_level) = ldtk_levels.get(level_handle) {
-                let level = &ldtk_level.level;
-                if level_selection.is_match(&0, level) {
-                    let level_ratio = level.px_wid as f32 / ldtk_level.level.px_hei as f32;
-                    orthographic_projection.viewport_origin = Vec2::ZERO;
-                    if level_ratio > ASPECT_RATIO {
-                        // level is wider than the screen
-                        let height = (level.px_hei as f32 / 9.).round() * 9.;
-                        let width = height * ASPECT_RATIO;
-                        orthographic_projection.scaling_mode =
-                            bevy::render::camera::ScalingMode::Fixed { width, height };
-                        camera_transform.translation.x =
-                            (player_translation.x - level_transform.translation.x - width / 2.)
-                                .clamp(0., level.px_wid as f32 - width);
-                        camera_transform.translation.y = 0.;
-                    } else {
-                        // level is taller than the screen
-                        let width = (level.px_wid as f32 / 16.).round() * 16.;
-                        let height = width / ASPECT_RATIO;
-                        orthographic_projection.scaling_mode =
-                            bevy::render::camera::ScalingMode::Fixed { width, height };
-                        camera_transform.translation.y =
-                            (player_translation.y - level_transform.translation.y - height / 2.)
-                                .clamp(0., level.px_hei as f32 - height);
-                        camera_transform.translation.x = 0.;
-                    }
-
-                    camera_transform.translation.x += level_transform.translation.x;
-                    camera_transform.translation.y += level_transform.translation.y;
-                }
+    let (Ok(window), Ok(player_transform)) = (window_query.get_single(), player_query.get_single())
+    else {
+        return;
+    };
+    if window.height() <= 0. {
+        return;
+    }
+    let aspect_ratio = window.width() / window.height();
+    let player_position = player_transform.translation.truncate();
+
+    // Bottom left corner and size of the current level.
+    let level = level_query
+        .iter()
+        .find_map(|(level_transform, level_handle)| {
+            let level = &ldtk_levels.get(level_handle)?.level;
+            level_selection.is_match(&0, level).then(|| {
+                (
+                    level_transform.translation.truncate(),
+                    Vec2::new(level.px_wid as f32, level.px_hei as f32),
+                )
+            })
+        });
+    let Some((level_origin, level_size)) = level else {
+        return;
+    };
+
+    let delta = time.delta_seconds();
+    let ease = |sharpness: f32| 1. - (-sharpness * delta).exp();
+
+    for (mut camera, mut projection, mut transform) in camera_query.iter_mut() {
+        let focus = match camera.mode {
+            CameraMode::FocusNpc(npc) => npc_query.get(npc).ok(),
+            CameraMode::FollowPlayer => None,
+        };
+        let zoom = if focus.is_some() {
+            camera.target_zoom * FOCUS_ZOOM
+        } else {
+            camera.target_zoom
+        };
+        camera.zoom += (zoom - camera.zoom) * ease(ZOOM_SHARPNESS);
+        if (zoom - camera.zoom).abs() < 0.001 {
+            camera.zoom = zoom;
+        }
+
+        let fitted_size = if level_size.x / level_size.y > aspect_ratio {
+            Vec2::new(level_size.y * aspect_ratio, level_size.y)
+        } else {
+            Vec2::new(level_size.x, level_size.x / aspect_ratio)
+        };
+        let view_size = fitted_size / camera.zoom;
+
+        let (target, sharpness) = match focus {
+            Some(npc_transform) => {
+                let midpoint = (player_position + npc_transform.translation.truncate()) / 2.;
+                // Keeps both of them in the part of the screen above the console.
+                let console_offset = Vec2::new(0., view_size.y * CONSOLE_HEIGHT / 2.);
+                (midpoint - console_offset, FOCUS_SHARPNESS)
+            }
+            None => {
+                let offset = player_position - camera.center;
+                let outside = offset.abs() - DEADZONE;
+                let target = camera.center + offset.signum() * outside.max(Vec2::ZERO);
+                (target, FOLLOW_SHARPNESS)
             }
+        };
+
+        if camera.snap || camera.center.distance(target) > SNAP_DISTANCE {
+            camera.center = target;
+            camera.snap = false;
+        } else {
+            let center = camera.center;
+            camera.center = center + (target - center) * ease(sharpness);
+        }
+
+        // Levels smaller than the view on an axis are centered on it instead.
+        let clamp_axis = |center: f32, origin: f32, level: f32, view: f32| {
+            if view >= level {
+                origin + level / 2.
+            } else {
+                center.clamp(origin + view / 2., origin + level - view / 2.)
+            }
+        };
+        let mut position = Vec2::new(
+            clamp_axis(camera.center.x, level_origin.x, level_size.x, view_size.x),
+            clamp_axis(camera.center.y, level_origin.y, level_size.y, view_size.y),
+        );
+
+        camera.trauma = (camera.trauma - TRAUMA_DECAY * delta).max(0.);
+        if camera.trauma > 0. {
+            let mut rng = rand::thread_rng();
+            let shake = Vec2::new(rng.gen_range(-1. ..1.), rng.gen_range(-1. ..1.));
+            position += shake * MAX_SHAKE_OFFSET * camera.trauma * camera.trauma;
+        }
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+
+        let unchanged = matches!(
+            projection.scaling_mode,
+            ScalingMode::Fixed { width, height } if width == view_size.x && height == view_size.y
+        );
+        if !unchanged {
+            projection.scaling_mode = ScalingMode::Fixed {
+                width: view_size.x,
+                height: view_size.y,
+            };
         }
     }
 }
@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::{App, EventWriter, Plugin, Res, ResMut, Resource},
+    reflect::Reflect,
+    time::Time,
+};
+
+pub const MINUTES_PER_DAY: f32 = 24. * 60.;
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameClock>()
+            .add_event::<NewDayEvent>()
+            .add_system(advance_clock);
+    }
+}
+
+// In-game time of day. It follows `Time`, so it stops while the game is paused.
+#[derive(Resource, Reflect, Clone, Debug)]
+pub struct GameClock {
+    // Starts from day 1.
+    pub day: u32,
+    // Minutes since midnight, in 0..MINUTES_PER_DAY.
+    pub minutes: f32,
+    // How many in-game minutes pass in one real second.
+    pub minutes_per_second: f32,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock {
+            day: 1,
+            minutes: 6. * 60.,
+            minutes_per_second: 1.,
+        }
+    }
+}
+
+impl GameClock {
+    pub fn hour(&self) -> u32 {
+        (self.minutes / 60.) as u32
+    }
+
+    pub fn minute(&self) -> u32 {
+        (self.minutes % 60.) as u32
+    }
+
+    pub fn set_time(&mut self, hour: u32, minute: u32) {
+        self.minutes = ((hour % 24) * 60 + minute % 60) as f32;
+    }
+
+    // Minutes passed since the start of day 1. Used as the timestamp of events like memories.
+    pub fn total_minutes(&self) -> f64 {
+        (self.day.saturating_sub(1)) as f64 * MINUTES_PER_DAY as f64 + self.minutes as f64
+    }
+
+    // Clock showing a timestamp from `total_minutes`.
+    pub fn at(total_minutes: f64) -> Self {
+        let minutes_per_day = MINUTES_PER_DAY as f64;
+        GameClock {
+            day: (total_minutes / minutes_per_day) as u32 + 1,
+            minutes: (total_minutes % minutes_per_day) as f32,
+            ..Default::default()
+        }
+    }
+
+    // Formatted like `Day 3 07:45`.
+    pub fn display(&self) -> String {
+        format!("Day {} {:02}:{:02}", self.day, self.hour(), self.minute())
+    }
+}
+
+// Parses `hh:mm` into hour and minute.
+pub fn parse_time_of_day(text: &str) -> Option<(u32, u32)> {
+    let (hour, minute) = text.split_once(':')?;
+    let (hour, minute) = (hour.trim().parse().ok()?, minute.trim().parse().ok()?);
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+pub struct NewDayEvent {
+    pub day: u32,
+}
+
+pub fn advance_clock(
+    time: Res<Time>,
+    mut clock: ResMut<GameClock>,
+    mut new_day_writer: EventWriter<NewDayEvent>,
+) {
+    clock.minutes += time.delta_seconds() * clock.minutes_per_second;
+    while clock.minutes >= MINUTES_PER_DAY {
+        clock.minutes -= MINUTES_PER_DAY;
+        clock.day += 1;
+        new_day_writer.send(NewDayEvent { day: clock.day });
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::{
     prelude::{
         info, Commands, Component, Deref, DerefMut, DespawnRecursiveExt, Entity, EventReader, Or,
-        Parent, Query, ReflectComponent, Res, Vec2, Visibility, With,
+        Parent, Query, ReflectComponent, Res, Vec2, Visibility, With, Without,
     },
     reflect::Reflect,
     time::{Time, Timer, TimerMode},
@@ -10,6 +10,8 @@ use bevy_rapier2d::prelude::Group;
 
 use crate::units::{Player, NPC};
 
+use super::Invulnerable;
+
 #[derive(Component, Deref, DerefMut, Clone, Reflect)]
 pub struct Delay(pub Timer);
 
@@ -20,7 +22,7 @@ impl Default for Delay {
 }
 
 /// A component representing an attack that can do damage to [`Damageable`]s with [`Health`].
-#[derive(Component, Clone, Copy, Default, Reflect, Debug)]
+#[derive(Component, Clone, Default, Reflect, Debug)]
 #[reflect(Component)]
 pub struct Attack {
     //maybe just replace all fields with AttackMeta
@@ -28,6 +30,8 @@ pub struct Attack {
     /// The direction and speed that the attack is hitting something in.
     pub pushback: Vec2,
     pub hitstun_duration: f32,
+    // Entities this attack already damaged. Each of them is hit once, however long the attack lives.
+    pub hit_entities: Vec<Entity>,
     // add this for attacks that are not immediately active, used in activate_hitbox
     // pub hitbox_meta: Option<ColliderMeta>,
 }
@@ -44,6 +48,35 @@ pub struct DamageEvent {
 #[derive(Component, Default, Clone)]
 pub struct Hurtbox;
 
+// Hit points of units. A unit is hidden when they run out.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+// A single hit point, so units still go down on the first hit.
+impl Default for Health {
+    fn default() -> Self {
+        Health { current: 1, max: 1 }
+    }
+}
+
+impl Health {
+    // From 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0 {
+            0.
+        } else {
+            self.current.clamp(0, self.max) as f32 / self.max as f32
+        }
+    }
+}
+
+// Entities with `GodMode` ignore every hit. Toggled by the `god` developer command.
+#[derive(Component, Default, Clone, Reflect)]
+pub struct GodMode;
+
 /// Empty struct simply for grouping collision layer constants.
 #[derive(Copy, Clone)]
 pub struct BodyLayers;
@@ -64,14 +97,32 @@ impl BodyLayers {
     pub const ALL: Group = Group::ALL;
 }
 
+#[allow(clippy::type_complexity)]
 pub fn collect_hit(
-    mut npc: Query<&mut Visibility, Or<(With<NPC>, With<Player>)>>,
+    mut npc: Query<
+        (&mut Visibility, Option<&mut Health>),
+        (
+            Or<(With<NPC>, With<Player>)>,
+            Without<GodMode>,
+            Without<Invulnerable>,
+        ),
+    >,
     mut damage_events: EventReader<DamageEvent>,
 ) {
     for event in damage_events.iter() {
-        if let Ok(mut visibility) = npc.get_mut(event.damaged_entity) {
+        if let Ok((mut visibility, health)) = npc.get_mut(event.damaged_entity) {
             info!("NPC {:?} hit {:?}", event.damaged_entity, visibility);
-            *visibility = Visibility::Hidden;
+            // Units without `Health` go down on the first hit.
+            let defeated = match health {
+                Some(mut health) => {
+                    health.current -= event.damage;
+                    health.current <= 0
+                }
+                None => true,
+            };
+            if defeated {
+                *visibility = Visibility::Hidden;
+            }
         }
     }
 }
@@ -79,11 +130,11 @@ pub fn collect_hit(
 pub fn deactivate_attack(
     mut commands: Commands,
     attacks: Query<(&Parent, Entity), With<Attack>>,
-    mut player: Query<&mut Delay, With<Player>>,
+    mut attackers: Query<&mut Delay>,
     time: Res<Time>,
 ) {
     for (parent, entity) in attacks.iter() {
-        let delay = player.get_mut(parent.get());
+        let delay = attackers.get_mut(parent.get());
         if !delay.is_err() {
             let delay = &mut delay.unwrap().0;
             delay.tick(time.delta());
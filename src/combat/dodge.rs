@@ -0,0 +1,106 @@
+use bevy::{
+    prelude::{Commands, Component, Entity, Query, Res, Vec2, With, Without},
+    reflect::Reflect,
+    time::{Time, Timer, TimerMode},
+};
+use bevy_rapier2d::prelude::Velocity;
+
+use crate::{
+    input::{Action, ActionInput},
+    physics::{MoveLock, Stamina},
+    sprites::{AnimationIndices, AnimationState, Facing},
+    units::Player,
+};
+
+use super::Delay;
+
+const ROLL_SECONDS: f32 = 0.35;
+const ROLL_SPEED: f32 = 220.;
+const ROLL_STAMINA_COST: f32 = 25.;
+// Shorter than the roll, so the end of a roll can still be hit.
+const INVULNERABLE_SECONDS: f32 = 0.25;
+
+// A dodge roll in progress. The unit moves in `direction` until the timer finishes.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Roll {
+    pub direction: Vec2,
+    pub timer: Timer,
+}
+
+// Hits are ignored while this timer runs, like `GodMode`.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Invulnerable(pub Timer);
+
+#[allow(clippy::type_complexity)]
+pub fn start_roll(
+    mut commands: Commands,
+    action_input: Res<ActionInput>,
+    mut query: Query<
+        (
+            Entity,
+            &Facing,
+            &mut AnimationIndices,
+            &mut MoveLock,
+            &mut Delay,
+            &mut Stamina,
+        ),
+        (With<Player>, Without<Roll>),
+    >,
+) {
+    if !action_input.just_pressed(Action::Roll) {
+        return;
+    }
+
+    for (entity, facing, mut indices, mut move_lock, mut delay, mut stamina) in query.iter_mut() {
+        if move_lock.0 || !stamina.spend(ROLL_STAMINA_COST) {
+            continue;
+        }
+
+        // Rolls toward the movement input, or forward when standing still.
+        let direction = action_input
+            .movement()
+            .try_normalize()
+            .unwrap_or_else(|| facing.direction.to_vector());
+
+        indices.restart(AnimationState::Roll);
+        *delay = Delay(Timer::from_seconds(ROLL_SECONDS, TimerMode::Once));
+        move_lock.0 = true;
+
+        commands.entity(entity).insert((
+            Roll {
+                direction,
+                timer: Timer::from_seconds(ROLL_SECONDS, TimerMode::Once),
+            },
+            Invulnerable(Timer::from_seconds(INVULNERABLE_SECONDS, TimerMode::Once)),
+        ));
+    }
+}
+
+// Moves rolling units. This runs after `movement`, which stops units under `MoveLock`.
+pub fn apply_roll(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Roll, &mut Velocity, &mut AnimationIndices)>,
+) {
+    for (entity, mut roll, mut velocity, mut indices) in query.iter_mut() {
+        if roll.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Roll>();
+            velocity.linvel = Vec2::ZERO;
+            indices.animation_state = AnimationState::Idle;
+        } else {
+            velocity.linvel = roll.direction * ROLL_SPEED;
+        }
+    }
+}
+
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
@@ -1,24 +1,26 @@
 use bevy::{
     prelude::{
-        info, BuildChildren, Commands, Entity, EventWriter, Input, KeyCode, Parent, Query, Res,
-        Vec2, With,
+        info, BuildChildren, Commands, Entity, EventReader, EventWriter, Parent, Query, Res, Vec2,
+        With,
     },
     time::{Time, Timer, TimerMode},
 };
 use bevy_rapier2d::prelude::{CollisionGroups, Velocity};
 
 use crate::{
+    ai::{same_party, PartyMember},
     combat::{Attack, BodyLayers},
+    input::{Action, ActionInput},
     maps::{Coordinate, EntityGridMap},
     physics::MoveLock,
-    sprites::{AnimationIndices, AnimationState, FaceDirection, Facing},
+    sprites::{AnimationEvent, AnimationIndices, AnimationState, FaceDirection, Facing, HIT_EVENT},
     units::Player,
 };
 
 use super::{DamageEvent, Delay, Hurtbox};
 
 pub fn melee_attack(
-    input: Res<Input<KeyCode>>,
+    action_input: Res<ActionInput>,
     mut commands: Commands,
     mut fighters: Query<
         (
@@ -35,7 +37,7 @@ pub fn melee_attack(
 ) {
     for (mut indices, mut move_lock, _facing, mut _velocity, mut delay, entity) in &mut fighters {
         delay.tick(time.delta());
-        if input.pressed(KeyCode::Space) && !move_lock.0 {
+        if action_input.pressed(Action::Attack) && !move_lock.0 {
             // const MOVE_FRONT: f32 = 100.;
             // match facing.direction {
             //     FaceDirection::Down => velocity.linvel.y = -MOVE_FRONT,
@@ -44,7 +46,7 @@ pub fn melee_attack(
             //     FaceDirection::Up => velocity.linvel.y = MOVE_FRONT,
             // }
 
-            indices.animation_state = AnimationState::Attack;
+            indices.restart(AnimationState::Attack);
 
             // Spawn the attack entity
             let attack_entity = commands
@@ -56,6 +58,7 @@ pub fn melee_attack(
                     damage: 1,
                     pushback: Vec2::ZERO,
                     hitstun_duration: 1.,
+                    hit_entities: Vec::new(),
                 })
                 .id();
             *delay = Delay(Timer::from_seconds(0.8, TimerMode::Once));
@@ -66,15 +69,27 @@ pub fn melee_attack(
     }
 }
 
+// Attacks hit on the frames of the attacker's clip that send `HIT_EVENT`.
+// They don't hit the attacker's own party.
 pub fn melee_attack_system(
     entity_map: Res<EntityGridMap>,
-    attacks: Query<(&Parent, &Attack)>,
+    mut animation_events: EventReader<AnimationEvent>,
+    mut attacks: Query<(&Parent, &mut Attack)>,
     attackers: Query<(&Facing, &Coordinate)>,
     _hurtboxes: Query<&Parent, With<Hurtbox>>,
+    party_members: Query<&PartyMember>,
     mut event_writer: EventWriter<DamageEvent>,
 ) {
-    for (attacker, attack) in attacks.iter() {
+    let hits: Vec<Entity> = animation_events
+        .iter()
+        .filter(|event| event.name == HIT_EVENT)
+        .map(|event| event.entity)
+        .collect();
+    for (attacker, mut attack) in attacks.iter_mut() {
         let attacker_entity = attacker.get();
+        if !hits.contains(&attacker_entity) {
+            continue;
+        }
 
         // TODO: under code should be separate systems. Maybe `fn find_entities_in_range`, and add entities in player range into player entitiy's children entities.
         // This always check player's range at each frame and update children entities. This system can enable to emphasize entities nearby.
@@ -106,8 +121,9 @@ pub fn melee_attack_system(
             for y in range_y.clone() {
                 if let Some(hit_range) = entity_map.get((x, y)) {
                     for hurtbox_entity in hit_range {
-                        if attacker_entity.eq(hurtbox_entity)
+                        if same_party(&party_members, attacker_entity, *hurtbox_entity)
                             | hurtbox_vec.contains(&hurtbox_entity)
+                            | attack.hit_entities.contains(hurtbox_entity)
                         {
                             continue;
                         }
@@ -120,6 +136,7 @@ pub fn melee_attack_system(
         info!("Attacker: {:?}, Attack: {:?}", attacker_entity, attack);
 
         for hurtbox_entity in hurtbox_vec {
+            attack.hit_entities.push(*hurtbox_entity);
             event_writer.send(DamageEvent {
                 damageing_entity: attacker_entity,
                 damage_velocity: attack.pushback,
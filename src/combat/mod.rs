@@ -1,9 +1,13 @@
-use bevy::prelude::{App, Plugin};
+use bevy::prelude::{App, IntoSystemConfig, Plugin};
+
+use crate::physics::movement;
 
 pub mod base;
+pub mod dodge;
 pub mod melee;
 pub mod projectile;
 pub use base::*;
+pub use dodge::*;
 pub use melee::*;
 pub use projectile::*;
 
@@ -14,6 +18,9 @@ impl Plugin for CombatPlugin {
         app.add_event::<DamageEvent>()
             .add_system(melee_attack)
             .add_system(melee_attack_system)
+            .add_system(start_roll.before(movement))
+            .add_system(apply_roll.after(movement))
+            .add_system(tick_invulnerability.after(collect_hit))
             .add_system(collect_hit)
             .add_system(deactivate_attack);
     }
@@ -1,4 +1,3 @@
-pub const ASPECT_RATIO: f32 = 16. / 9.;
 pub const UNIT_SIZE: f32 = 8.;
 pub const GRID_SIZE: f32 = 16.;
 // Shifts Coordinate upside&rightside to the center of the grid.
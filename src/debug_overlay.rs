@@ -0,0 +1,318 @@
+use bevy::{
+    prelude::{
+        default, App, AssetServer, Color, Commands, Component, DetectChanges, Entity,
+        IntoSystemConfig, Name, Plugin, Quat, Query, Res, ResMut, Resource, Sprite, SpriteBundle,
+        Text, Text2dBundle, TextStyle, Transform, Vec2, Vec3, Visibility, With, Without,
+    },
+    reflect::Reflect,
+    sprite::Anchor,
+    text::TextAlignment,
+};
+use big_brain::{prelude::ActionState, scorers::Score, thinker::Actor};
+use seldom_map_nav::prelude::Pathfind;
+
+use crate::{
+    ai::Distance,
+    constants::{GRID_OFFSET, GRID_SIZE},
+    input::{Action, ActionInput},
+    maps::{Coordinate, EntityGridMap, TileGridMap, TileType},
+    units::NPC,
+};
+
+// Overlay shapes are drawn above every sprite, including y-sorted units.
+const OVERLAY_Z: f32 = 100.;
+const LINE_WIDTH: f32 = 1.;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_system(toggle_debug_overlay)
+            .add_system(draw_grid_overlay.after(toggle_debug_overlay))
+            .add_system(draw_ai_overlay.after(toggle_debug_overlay));
+    }
+}
+
+// Flags of the debug overlay. Each layer can be switched on and off in the inspector, F3 toggles the whole overlay.
+#[derive(Resource, Reflect, Debug)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+    pub show_paths: bool,
+    pub show_ranges: bool,
+    pub show_targets: bool,
+    pub show_scores: bool,
+    pub show_tiles: bool,
+    pub show_occupancy: bool,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay {
+            enabled: false,
+            show_paths: true,
+            show_ranges: true,
+            show_targets: true,
+            show_scores: true,
+            show_tiles: true,
+            show_occupancy: true,
+        }
+    }
+}
+
+// Shapes and labels of AI state. They are pooled: reused every frame, and hidden when there are more than needed.
+#[derive(Component)]
+pub struct DebugShape;
+
+#[derive(Component)]
+pub struct DebugLabel;
+
+// Shapes of `TileGridMap`, which are only redrawn when the map or the overlay flags change.
+#[derive(Component)]
+pub struct DebugTileShape;
+
+pub fn toggle_debug_overlay(action_input: Res<ActionInput>, mut overlay: ResMut<DebugOverlay>) {
+    if action_input.just_pressed(Action::DebugOverlay) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+pub fn draw_grid_overlay(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    tile_grid_map: Res<TileGridMap>,
+    shapes: Query<Entity, With<DebugTileShape>>,
+) {
+    if !overlay.is_changed() && !tile_grid_map.is_changed() {
+        return;
+    }
+
+    for entity in shapes.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    if !overlay.enabled || !overlay.show_tiles {
+        return;
+    }
+
+    for (&(x, y), (_, tile)) in tile_grid_map.tile_map.iter() {
+        let color = match tile {
+            TileType::Wall => Color::rgba(1., 0.2, 0.2, 0.35),
+            TileType::Floor => Color::rgba(0.2, 1., 0.2, 0.1),
+            TileType::Road => Color::rgba(0.8, 0.8, 0.8, 0.2),
+            TileType::Grass => Color::rgba(0.2, 1., 0.2, 0.25),
+            TileType::Mud => Color::rgba(0.6, 0.4, 0.2, 0.35),
+            TileType::Water => Color::rgba(0.2, 0.4, 1., 0.35),
+            TileType::DeepWater => Color::rgba(0.1, 0.2, 0.8, 0.35),
+        };
+        commands.spawn((cell_bundle((x, y), color), DebugTileShape));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_ai_overlay(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    entity_map: Res<EntityGridMap>,
+    asset_server: Res<AssetServer>,
+    mut shapes: Query<
+        (&mut Sprite, &mut Transform, &mut Visibility),
+        (With<DebugShape>, Without<NPC>),
+    >,
+    mut labels: Query<
+        (&mut Text, &mut Transform, &mut Visibility),
+        (With<DebugLabel>, Without<DebugShape>, Without<NPC>),
+    >,
+    actors: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&Pathfind>,
+            Option<&Distance>,
+            Option<&Coordinate>,
+        ),
+        With<NPC>,
+    >,
+    targets: Query<&Transform, (Without<DebugShape>, Without<DebugLabel>)>,
+    scorers: Query<(&Actor, &Score)>,
+    actions: Query<(&Actor, &ActionState, Option<&Name>)>,
+) {
+    let mut shape_bundles = Vec::new();
+    let mut label_texts: Vec<(Vec3, String)> = Vec::new();
+
+    if overlay.enabled {
+        if overlay.show_occupancy {
+            for &(x, y) in entity_map.entity_map.keys() {
+                shape_bundles.push(cell_bundle((x, y), Color::rgba(1., 1., 0.2, 0.3)));
+            }
+        }
+
+        for (actor, transform, pathfind, distance, coordinate) in actors.iter() {
+            let position = transform.translation.truncate();
+
+            if overlay.show_paths {
+                if let Some(pathfind) = pathfind {
+                    let mut from = position;
+                    for &to in pathfind.path.iter() {
+                        shape_bundles.push(line_bundle(from, to, Color::CYAN));
+                        from = to;
+                    }
+                }
+            }
+
+            if let Some(distance) = distance {
+                if overlay.show_ranges {
+                    if let Some(coordinate) = coordinate {
+                        let min = Vec2::new(
+                            (coordinate.min_x - distance.range) as f32,
+                            (coordinate.min_y - distance.range) as f32,
+                        ) * GRID_SIZE;
+                        let max = Vec2::new(
+                            (coordinate.max_x + distance.range + 1) as f32,
+                            (coordinate.max_y + distance.range + 1) as f32,
+                        ) * GRID_SIZE;
+                        shape_bundles.extend(rect_outline_bundles(min, max, Color::ORANGE));
+                    }
+                }
+
+                if overlay.show_targets && distance.has_target() {
+                    if let Ok(target_transform) = targets.get(distance.target) {
+                        shape_bundles.push(line_bundle(
+                            position,
+                            target_transform.translation.truncate(),
+                            Color::RED,
+                        ));
+                    }
+                }
+            }
+
+            if overlay.show_scores {
+                let score = scorers
+                    .iter()
+                    .filter(|(Actor(scorer_actor), _)| *scorer_actor == actor)
+                    .map(|(_, score)| score.get())
+                    .fold(0., f32::max);
+                let action = actions.iter().find(|(Actor(action_actor), state, _)| {
+                    *action_actor == actor
+                        && matches!(state, ActionState::Requested | ActionState::Executing)
+                });
+
+                let label = match action {
+                    Some((_, state, name)) => format!(
+                        "{} {:?} {:.2}",
+                        name.map_or("Action", |name| name.as_str()),
+                        state,
+                        score
+                    ),
+                    None => format!("Idle {:.2}", score),
+                };
+                label_texts.push((
+                    (position + Vec2::new(0., GRID_SIZE)).extend(OVERLAY_Z),
+                    label,
+                ));
+            }
+        }
+    }
+
+    let mut pooled_shapes = shapes.iter_mut();
+    for bundle in shape_bundles {
+        match pooled_shapes.next() {
+            Some((mut sprite, mut transform, mut visibility)) => {
+                *sprite = bundle.sprite;
+                *transform = bundle.transform;
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                commands.spawn((bundle, DebugShape));
+            }
+        }
+    }
+    for (_, _, mut visibility) in pooled_shapes {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+        }
+    }
+
+    let mut pooled_labels = labels.iter_mut();
+    for (translation, label) in label_texts {
+        match pooled_labels.next() {
+            Some((mut text, mut transform, mut visibility)) => {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label;
+                }
+                transform.translation = translation;
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                commands.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            label,
+                            TextStyle {
+                                font: asset_server.load("fonts/VT323-Regular.ttf"),
+                                font_size: 12.,
+                                color: Color::WHITE,
+                            },
+                        )
+                        .with_alignment(TextAlignment::Center),
+                        text_anchor: Anchor::BottomCenter,
+                        transform: Transform::from_translation(translation),
+                        ..default()
+                    },
+                    DebugLabel,
+                ));
+            }
+        }
+    }
+    for (_, _, mut visibility) in pooled_labels {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+// A thin sprite stretched and rotated between two points.
+fn line_bundle(start: Vec2, end: Vec2, color: Color) -> SpriteBundle {
+    let delta = end - start;
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::new(delta.length(), LINE_WIDTH)),
+            ..default()
+        },
+        transform: Transform {
+            translation: ((start + end) / 2.).extend(OVERLAY_Z),
+            rotation: Quat::from_rotation_z(delta.y.atan2(delta.x)),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn rect_outline_bundles(min: Vec2, max: Vec2, color: Color) -> [SpriteBundle; 4] {
+    let top_left = Vec2::new(min.x, max.y);
+    let bottom_right = Vec2::new(max.x, min.y);
+    [
+        line_bundle(min, top_left, color),
+        line_bundle(top_left, max, color),
+        line_bundle(max, bottom_right, color),
+        line_bundle(bottom_right, min, color),
+    ]
+}
+
+// A filled square covering one grid cell. Cell (0, 0) is centered at (GRID_OFFSET, GRID_OFFSET).
+fn cell_bundle((x, y): (i32, i32), color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::splat(GRID_SIZE - 1.)),
+            ..default()
+        },
+        transform: Transform::from_xyz(
+            x as f32 * GRID_SIZE + GRID_OFFSET,
+            y as f32 * GRID_SIZE + GRID_OFFSET,
+            OVERLAY_Z - 1.,
+        ),
+        ..default()
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    clock::NewDayEvent,
+    constants::{GRID_OFFSET, GRID_SIZE},
+    input::{Action, ActionInput},
+    interaction::{ChannelCompletedEvent, ChannelKind, Interactable},
+    maps::{Coordinate, EntityGridMap, MapConfig, TileGridMap, TileType},
+    physics::{MoveLock, Stamina},
+    sprites::{Facing, YSort},
+    state::AppState,
+    ui::{HotbarSelection, Inventory, ItemDictionary, ItemType},
+    units::{Player, UnitSize},
+};
+
+// `crops-v2.png` has a crop in every 32x64 column, with growth stages in rows from the top.
+const CROP_ATLAS_COLUMNS: usize = 32;
+const CROP_ATLAS_ROWS: usize = 16;
+const CROP_STAGES: usize = 4;
+// Crops are drawn at half size, so they fit in a 16x32 tile like units.
+const CROP_SCALE: f32 = 0.5;
+// Over floor layers, and under walls and units.
+const SOIL_Z: f32 = 2.5;
+const SOIL_COLOR: Color = Color::rgb(0.47, 0.31, 0.2);
+const WATERED_SOIL_COLOR: Color = Color::rgb(0.3, 0.19, 0.12);
+const TOOL_STAMINA_COST: f32 = 2.;
+const HARVEST_SECONDS: f32 = 0.5;
+// Tools the player starts with, by name in `ItemDictionary`.
+const STARTER_ITEMS: [(&str, u32); 3] = [("Hoe", 1), ("Watering Can", 1), ("Wheat Seeds", 5)];
+
+pub struct FarmingPlugin;
+
+impl Plugin for FarmingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_crop_atlas)
+            .add_system(give_starter_items)
+            .add_system(use_selected_item.in_set(OnUpdate(AppState::MainGame)))
+            .add_system(grow_crops)
+            .add_system(harvest_crops)
+            .add_system(update_soil_sprites)
+            .add_system(update_crop_sprites);
+    }
+}
+
+pub struct CropKind {
+    pub name: &'static str,
+    pub seed: &'static str,
+    // Days of watered soil until it can be harvested.
+    pub grow_days: u32,
+    pub atlas_column: usize,
+}
+
+// Crops grown from the seeds in `ItemDictionary`. Harvesting gives the item called `name`.
+pub const CROP_KINDS: [CropKind; 5] = [
+    CropKind {
+        name: "Wheat",
+        seed: "Wheat Seeds",
+        grow_days: 4,
+        atlas_column: 0,
+    },
+    CropKind {
+        name: "Beet",
+        seed: "Beet Seeds",
+        grow_days: 5,
+        atlas_column: 9,
+    },
+    CropKind {
+        name: "Pepper",
+        seed: "Pepper Seeds",
+        grow_days: 6,
+        atlas_column: 18,
+    },
+    CropKind {
+        name: "Corn",
+        seed: "Corn Seeds",
+        grow_days: 7,
+        atlas_column: 30,
+    },
+    CropKind {
+        name: "Pumpkin",
+        seed: "Pumpkin Seeds",
+        grow_days: 8,
+        atlas_column: 27,
+    },
+];
+
+pub fn crop_kind(name: &str) -> Option<usize> {
+    CROP_KINDS.iter().position(|kind| kind.name == name)
+}
+
+#[derive(Resource)]
+pub struct CropAtlas(pub Handle<TextureAtlas>);
+
+// Tilled tile that seeds can be planted in. It dries every night.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Soil {
+    pub cell: (i32, i32),
+    pub watered: bool,
+}
+
+// Planted on the `Soil` of the same cell. It only grows on days its soil was watered.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Crop {
+    pub cell: (i32, i32),
+    // Index of `CROP_KINDS`.
+    pub kind: usize,
+    pub days_grown: u32,
+}
+
+impl Crop {
+    pub fn kind(&self) -> &'static CropKind {
+        &CROP_KINDS[self.kind]
+    }
+
+    pub fn is_ripe(&self) -> bool {
+        self.days_grown >= self.kind().grow_days
+    }
+
+    fn atlas_index(&self) -> usize {
+        let stage = (self.days_grown as usize * CROP_STAGES / self.kind().grow_days as usize)
+            .min(CROP_STAGES - 1);
+        stage * CROP_ATLAS_COLUMNS + self.kind().atlas_column
+    }
+}
+
+fn cell_center((x, y): (i32, i32)) -> Vec2 {
+    Vec2::new(
+        x as f32 * GRID_SIZE + GRID_OFFSET,
+        y as f32 * GRID_SIZE + GRID_OFFSET,
+    )
+}
+
+pub fn spawn_soil(commands: &mut Commands, cell: (i32, i32), watered: bool) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(GRID_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(cell_center(cell).extend(SOIL_Z)),
+                ..default()
+            },
+            Soil { cell, watered },
+            Name::new("Soil"),
+        ))
+        .id()
+}
+
+// Crops are y-sorted like units, since grown ones are taller than a tile.
+pub fn spawn_crop(commands: &mut Commands, crop_atlas: &CropAtlas, crop: Crop) -> Entity {
+    let bottom = cell_center(crop.cell) - Vec2::new(0., GRID_SIZE / 2.);
+    let ripe = crop.is_ripe();
+    let mut crop_commands = commands.spawn((
+        SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: crop.atlas_index(),
+                anchor: Anchor::BottomCenter,
+                ..default()
+            },
+            texture_atlas: crop_atlas.0.clone(),
+            transform: Transform::from_translation(bottom.extend(0.))
+                .with_scale(Vec3::splat(CROP_SCALE)),
+            ..default()
+        },
+        Name::new(crop.kind().name),
+        crop,
+        YSort::default(),
+        // Registers the crop in `EntityGridMap`, so it can be interacted with.
+        Coordinate::default(),
+        UnitSize {
+            width: GRID_SIZE / 2. - 1.,
+            height: GRID_SIZE / 2. - 1.,
+        },
+    ));
+    if ripe {
+        crop_commands.insert(harvest_interaction());
+    }
+    crop_commands.id()
+}
+
+fn harvest_interaction() -> Interactable {
+    Interactable {
+        kind: ChannelKind::Harvest,
+        seconds: HARVEST_SECONDS,
+    }
+}
+
+// Crops are removed from `EntityGridMap` too, since it is only updated when entities move.
+pub fn despawn_crop(
+    commands: &mut Commands,
+    entity_map: &mut EntityGridMap,
+    crop: Entity,
+    coordinate: &Coordinate,
+) {
+    for x in coordinate.min_x..=coordinate.max_x {
+        for y in coordinate.min_y..=coordinate.max_y {
+            entity_map.delete((x, y), crop);
+        }
+    }
+    commands.entity(crop).despawn_recursive();
+}
+
+pub fn load_crop_atlas(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("crops-v2.png"),
+        Vec2::new(32., 64.),
+        CROP_ATLAS_COLUMNS,
+        CROP_ATLAS_ROWS,
+        None,
+        None,
+    );
+    commands.insert_resource(CropAtlas(texture_atlases.add(texture_atlas)));
+}
+
+pub fn give_starter_items(
+    mut commands: Commands,
+    item_dictionary: Res<ItemDictionary>,
+    mut player_query: Query<&mut Inventory, Added<Player>>,
+) {
+    for mut inventory in player_query.iter_mut() {
+        for (name, count) in STARTER_ITEMS {
+            if let Some(item_info_index) = item_dictionary.find(name) {
+                inventory.stack(&mut commands, item_info_index, count);
+            }
+        }
+    }
+}
+
+// Uses the item in the selected hotbar slot on the cell in front of the player.
+// The hoe tills floor, the watering can waters soil, and seeds are planted in empty soil.
+#[allow(clippy::too_many_arguments)]
+pub fn use_selected_item(
+    mut commands: Commands,
+    action_input: Res<ActionInput>,
+    selection: Res<HotbarSelection>,
+    item_dictionary: Res<ItemDictionary>,
+    crop_atlas: Res<CropAtlas>,
+    map_config: Res<MapConfig>,
+    tile_grid_map: Res<TileGridMap>,
+    mut player_query: Query<
+        (&Transform, &Facing, &MoveLock, &mut Inventory, &mut Stamina),
+        With<Player>,
+    >,
+    mut soil_query: Query<&mut Soil>,
+    crop_query: Query<&Crop>,
+) {
+    if !action_input.just_pressed(Action::UseItem) {
+        return;
+    }
+    let Ok((transform, facing, move_lock, mut inventory, mut stamina)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+    if move_lock.0 {
+        return;
+    }
+    let Some(info) = inventory.get_item_info(selection.0, &item_dictionary) else {
+        return;
+    };
+
+    let (x, y) = TileGridMap::coordinate_of(transform.translation.truncate());
+    let direction = facing.direction.to_vector();
+    let cell = (x + direction.x as i32, y + direction.y as i32);
+    let mut soil = soil_query.iter_mut().find(|soil| soil.cell == cell);
+    let planted = crop_query.iter().any(|crop| crop.cell == cell);
+
+    match (info.item_type, info.name.as_str()) {
+        (ItemType::Tool, "Hoe") if soil.is_none() => {
+            let tillable = tile_grid_map
+                .tile_type_at(cell_center(cell))
+                .is_some_and(|tile_type| {
+                    let terrain = map_config.terrain(tile_type);
+                    tile_type != TileType::Road && !terrain.blocking && !terrain.swimmable
+                });
+            if tillable && stamina.spend(TOOL_STAMINA_COST) {
+                spawn_soil(&mut commands, cell, false);
+            }
+        }
+        (ItemType::Tool, "Watering Can") => {
+            if let Some(soil) = soil.as_mut().filter(|soil| !soil.watered) {
+                if stamina.spend(TOOL_STAMINA_COST) {
+                    soil.watered = true;
+                }
+            }
+        }
+        (ItemType::Seed, seed) if soil.is_some() && !planted => {
+            let Some(kind) = CROP_KINDS.iter().position(|kind| kind.seed == seed) else {
+                return;
+            };
+            if inventory.take_one(&mut commands, selection.0) {
+                let crop = Crop {
+                    cell,
+                    kind,
+                    days_grown: 0,
+                };
+                spawn_crop(&mut commands, &crop_atlas, crop);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Crops in watered soil grow a day every night, and all soil dries.
+pub fn grow_crops(
+    mut commands: Commands,
+    mut new_day_events: EventReader<NewDayEvent>,
+    mut soil_query: Query<&mut Soil>,
+    mut crop_query: Query<(Entity, &mut Crop)>,
+) {
+    for _ in new_day_events.iter() {
+        for (entity, mut crop) in crop_query.iter_mut() {
+            let watered = soil_query
+                .iter()
+                .any(|soil| soil.cell == crop.cell && soil.watered);
+            if watered && !crop.is_ripe() {
+                crop.days_grown += 1;
+                if crop.is_ripe() {
+                    commands.entity(entity).insert(harvest_interaction());
+                }
+            }
+        }
+        for mut soil in soil_query.iter_mut() {
+            soil.watered = false;
+        }
+    }
+}
+
+// Ripe crops are harvested into the inventory of whoever finished the `Harvest` channel on them.
+pub fn harvest_crops(
+    mut commands: Commands,
+    item_dictionary: Res<ItemDictionary>,
+    mut entity_map: ResMut<EntityGridMap>,
+    mut completed_events: EventReader<ChannelCompletedEvent>,
+    crop_query: Query<(&Crop, &Coordinate)>,
+    mut inventory_query: Query<&mut Inventory>,
+) {
+    for event in completed_events.iter() {
+        if event.kind != ChannelKind::Harvest {
+            continue;
+        }
+        let Some(target) = event.target else {
+            continue;
+        };
+        let (Ok((crop, coordinate)), Ok(mut inventory)) = (
+            crop_query.get(target),
+            inventory_query.get_mut(event.entity),
+        ) else {
+            continue;
+        };
+        if !crop.is_ripe() {
+            continue;
+        }
+        let Some(item_info_index) = item_dictionary.find(crop.kind().name) else {
+            continue;
+        };
+
+        // The crop stays in the field if the inventory is full.
+        if inventory.stack(&mut commands, item_info_index, 1) {
+            despawn_crop(&mut commands, &mut entity_map, target, coordinate);
+        }
+    }
+}
+
+pub fn update_soil_sprites(mut soil_query: Query<(&Soil, &mut Sprite), Changed<Soil>>) {
+    for (soil, mut sprite) in soil_query.iter_mut() {
+        sprite.color = if soil.watered {
+            WATERED_SOIL_COLOR
+        } else {
+            SOIL_COLOR
+        };
+    }
+}
+
+pub fn update_crop_sprites(mut crop_query: Query<(&Crop, &mut TextureAtlasSprite), Changed<Crop>>) {
+    for (crop, mut sprite) in crop_query.iter_mut() {
+        sprite.index = crop.atlas_index();
+    }
+}
@@ -0,0 +1,479 @@
+use bevy::{
+    input::InputSystem,
+    prelude::{
+        warn, App, Axis, Camera, Camera2d, CoreSet, Deref, DerefMut, GamepadAxis, GamepadAxisType,
+        GamepadButton, GamepadButtonType, Gamepads, GlobalTransform, Input, IntoSystemConfig,
+        KeyCode, MouseButton, Plugin, Query, Res, ResMut, Resource, Vec2, With,
+    },
+    reflect::Reflect,
+    utils::HashSet,
+    window::Window,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io::ErrorKind};
+
+// Bindings shipped with the game, read at startup.
+pub const INPUT_CONFIG_PATH: &str = "config/input.ron";
+// Bindings changed in the controls menu are written here, and override `INPUT_CONFIG_PATH`.
+pub const USER_INPUT_CONFIG_PATH: &str = "config/input.user.ron";
+// Left stick tilts below this are ignored, so worn sticks don't drift.
+pub const DEFAULT_STICK_DEADZONE: f32 = 0.2;
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputMap::load())
+            .init_resource::<ActionInput>()
+            .init_resource::<CursorPos>()
+            .add_system(
+                update_action_input
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_system(update_cursor_pos);
+    }
+}
+
+// Things the player can do, independent of the keys they are bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Attack,
+    Sprint,
+    Roll,
+    Talk,
+    Interact,
+    // Uses the item selected in the hotbar, like a hoe or seeds.
+    UseItem,
+    OpenInventory,
+    WorldMap,
+    MoveToCursor,
+    Pause,
+    // Menus are also navigated with the move actions.
+    MenuConfirm,
+    MenuBack,
+    DevConsole,
+    QuickSave,
+    QuickLoad,
+    DebugOverlay,
+}
+
+impl Action {
+    pub const ALL: [Action; 20] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Attack,
+        Action::Sprint,
+        Action::Roll,
+        Action::Talk,
+        Action::Interact,
+        Action::UseItem,
+        Action::OpenInventory,
+        Action::WorldMap,
+        Action::MoveToCursor,
+        Action::Pause,
+        Action::MenuConfirm,
+        Action::MenuBack,
+        Action::DevConsole,
+        Action::QuickSave,
+        Action::QuickLoad,
+        Action::DebugOverlay,
+    ];
+
+    // Actions only used in menus. Menus are navigated with the move actions too, and `Pause` works everywhere.
+    fn is_menu(&self) -> bool {
+        matches!(self, Action::MenuConfirm | Action::MenuBack)
+    }
+
+    fn in_menus(&self) -> bool {
+        self.is_menu()
+            || matches!(
+                self,
+                Action::MoveUp
+                    | Action::MoveDown
+                    | Action::MoveLeft
+                    | Action::MoveRight
+                    | Action::Pause
+            )
+    }
+
+    // Whether both actions can be used on the same screen, so they can't share a binding.
+    pub fn overlaps(&self, other: Action) -> bool {
+        (!self.is_menu() && !other.is_menu()) || (self.in_menus() && other.in_menus())
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Attack => "Attack",
+            Action::Sprint => "Sprint",
+            Action::Roll => "Dodge roll",
+            Action::Talk => "Talk to NPC",
+            Action::Interact => "Interact",
+            Action::UseItem => "Use item",
+            Action::OpenInventory => "Inventory",
+            Action::WorldMap => "World map",
+            Action::MoveToCursor => "Move to cursor",
+            Action::Pause => "Pause",
+            Action::MenuConfirm => "Menu confirm",
+            Action::MenuBack => "Menu back",
+            Action::DevConsole => "Developer console",
+            Action::QuickSave => "Quick save",
+            Action::QuickLoad => "Quick load",
+            Action::DebugOverlay => "Debug overlay",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // Pressed on any connected gamepad.
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(self, Binding::Gamepad(_))
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+// Config files may leave out actions and settings, which keep the value they had.
+#[derive(Default, Deserialize)]
+struct PartialInputMap {
+    #[serde(default)]
+    bindings: BTreeMap<Action, Vec<Binding>>,
+    #[serde(default)]
+    stick_deadzone: Option<f32>,
+}
+
+// `None` if the file doesn't exist.
+fn read_input_map(path: &str) -> Result<Option<PartialInputMap>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    ron::from_str(&text)
+        .map(Some)
+        .map_err(|err| err.to_string())
+}
+
+// Keys, mouse buttons and gamepad buttons bound to each action. An action can have several bindings.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    #[serde(default = "default_stick_deadzone")]
+    pub stick_deadzone: f32,
+}
+
+fn default_stick_deadzone() -> f32 {
+    DEFAULT_STICK_DEADZONE
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key, Mouse};
+
+        let bindings = [
+            (
+                Action::MoveUp,
+                vec![Key(KeyCode::W), Gamepad(GamepadButtonType::DPadUp)],
+            ),
+            (
+                Action::MoveDown,
+                vec![Key(KeyCode::S), Gamepad(GamepadButtonType::DPadDown)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyCode::A), Gamepad(GamepadButtonType::DPadLeft)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Key(KeyCode::D), Gamepad(GamepadButtonType::DPadRight)],
+            ),
+            (
+                Action::Attack,
+                vec![Key(KeyCode::Space), Gamepad(GamepadButtonType::West)],
+            ),
+            (
+                Action::Sprint,
+                vec![
+                    Key(KeyCode::LShift),
+                    Gamepad(GamepadButtonType::LeftTrigger),
+                ],
+            ),
+            (
+                Action::Roll,
+                vec![
+                    Key(KeyCode::LControl),
+                    Gamepad(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Action::Talk,
+                vec![Key(KeyCode::E), Gamepad(GamepadButtonType::North)],
+            ),
+            (
+                Action::Interact,
+                vec![Key(KeyCode::F), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Action::UseItem,
+                vec![Key(KeyCode::Q), Gamepad(GamepadButtonType::East)],
+            ),
+            (
+                Action::OpenInventory,
+                vec![Key(KeyCode::I), Gamepad(GamepadButtonType::Select)],
+            ),
+            (
+                Action::WorldMap,
+                vec![Key(KeyCode::M), Gamepad(GamepadButtonType::RightThumb)],
+            ),
+            (Action::MoveToCursor, vec![Mouse(MouseButton::Right)]),
+            (
+                Action::Pause,
+                vec![Key(KeyCode::Escape), Gamepad(GamepadButtonType::Start)],
+            ),
+            (
+                Action::MenuConfirm,
+                vec![Key(KeyCode::Return), Gamepad(GamepadButtonType::South)],
+            ),
+            (Action::MenuBack, vec![Gamepad(GamepadButtonType::East)]),
+            (Action::DevConsole, vec![Key(KeyCode::Grave)]),
+            (Action::QuickSave, vec![Key(KeyCode::F5)]),
+            (Action::QuickLoad, vec![Key(KeyCode::F9)]),
+            (Action::DebugOverlay, vec![Key(KeyCode::F3)]),
+        ];
+
+        InputMap {
+            bindings: bindings.into_iter().collect(),
+            stick_deadzone: DEFAULT_STICK_DEADZONE,
+        }
+    }
+}
+
+impl InputMap {
+    // Reads `INPUT_CONFIG_PATH`, then `USER_INPUT_CONFIG_PATH`. Actions missing in the files keep their default bindings.
+    pub fn load() -> Self {
+        let mut input_map = InputMap::default();
+        match read_input_map(INPUT_CONFIG_PATH) {
+            Ok(Some(loaded)) => input_map.extend(loaded),
+            Ok(None) => warn!(
+                "Using default bindings, {} doesn't exist",
+                INPUT_CONFIG_PATH
+            ),
+            Err(err) => warn!(
+                "Using default bindings, failed to load {}: {}",
+                INPUT_CONFIG_PATH, err
+            ),
+        }
+        // The user file only exists once bindings were changed.
+        match read_input_map(USER_INPUT_CONFIG_PATH) {
+            Ok(loaded) => input_map.extend(loaded.unwrap_or_default()),
+            Err(err) => warn!(
+                "Ignoring changed bindings, failed to load {}: {}",
+                USER_INPUT_CONFIG_PATH, err
+            ),
+        }
+        input_map
+    }
+
+    fn extend(&mut self, loaded: PartialInputMap) {
+        self.bindings.extend(loaded.bindings);
+        if let Some(stick_deadzone) = loaded.stick_deadzone {
+            self.stick_deadzone = stick_deadzone;
+        }
+    }
+
+    // Writes the bindings to `USER_INPUT_CONFIG_PATH`, so the shipped config stays as it is.
+    pub fn save(&self) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        fs::write(USER_INPUT_CONFIG_PATH, text).map_err(|err| err.to_string())
+    }
+
+    // Forgets bindings changed in the controls menu, and goes back to `INPUT_CONFIG_PATH`.
+    pub fn reset() -> Result<Self, String> {
+        match fs::remove_file(USER_INPUT_CONFIG_PATH) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(InputMap::load()),
+        }
+    }
+
+    // Another action that `binding` is bound to and is used at the same time as `action`.
+    pub fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|(&other, _)| other != action && action.overlaps(other))
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(&other, _)| other)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| bindings)
+    }
+
+    // Replaces the binding of the same device, so rebinding a key keeps the gamepad button and vice versa.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|bound| bound.is_gamepad() != binding.is_gamepad());
+        bindings.insert(0, binding);
+    }
+
+    // Bindings joined for display, like `W / Pad DPadUp`.
+    pub fn display(&self, action: Action) -> String {
+        self.bindings(action)
+            .iter()
+            .map(|binding| binding.display())
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+// State of every action in this frame. Read this instead of `Input<KeyCode>` for game controls.
+#[derive(Resource, Default, Debug)]
+pub struct ActionInput {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // Actions just pressed by a gamepad binding, for UI that already handles the keyboard itself.
+    gamepad_just_pressed: HashSet<Action>,
+    // Left stick tilt past the deadzone, or the move actions when the stick is not used. Its length is at most 1.
+    movement: Vec2,
+}
+
+impl ActionInput {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn gamepad_just_pressed(&self, action: Action) -> bool {
+        self.gamepad_just_pressed.contains(&action)
+    }
+
+    // -1, 0 or 1 from a pair of opposite actions, like `MoveLeft` and `MoveRight`.
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        f32::from(u8::from(self.pressed(positive))) - f32::from(u8::from(self.pressed(negative)))
+    }
+
+    pub fn movement(&self) -> Vec2 {
+        self.movement
+    }
+}
+
+// Rescales the stick so the tilt starts from 0 at the edge of the deadzone. Returns `None` inside the deadzone.
+fn apply_deadzone(stick: Vec2, deadzone: f32) -> Option<Vec2> {
+    let tilt = stick.length();
+    if tilt <= deadzone {
+        return None;
+    }
+    let scaled = ((tilt - deadzone) / (1. - deadzone)).min(1.);
+    Some(stick / tilt * scaled)
+}
+
+pub fn update_action_input(
+    input_map: Res<InputMap>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut action_input: ResMut<ActionInput>,
+) {
+    let was_pressed = std::mem::take(&mut action_input.pressed);
+    action_input.just_pressed.clear();
+    action_input.gamepad_just_pressed.clear();
+
+    let is_pressed = |binding: &Binding| match *binding {
+        Binding::Key(key) => keys.pressed(key),
+        Binding::Mouse(button) => mouse.pressed(button),
+        Binding::Gamepad(button_type) => gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type))),
+    };
+
+    for (&action, bindings) in input_map.bindings.iter() {
+        if bindings.iter().any(is_pressed) {
+            action_input.pressed.insert(action);
+            if !was_pressed.contains(&action) {
+                action_input.just_pressed.insert(action);
+            }
+        }
+
+        let gamepad_just_pressed = bindings.iter().any(|binding| match *binding {
+            Binding::Gamepad(button_type) => gamepads.iter().any(|gamepad| {
+                gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type))
+            }),
+            _ => false,
+        });
+        if gamepad_just_pressed {
+            action_input.gamepad_just_pressed.insert(action);
+        }
+    }
+
+    // The most tilted left stick among connected gamepads.
+    let stick = gamepads
+        .iter()
+        .map(|gamepad| {
+            let axis = |axis_type| {
+                gamepad_axes
+                    .get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or(0.)
+            };
+            Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )
+        })
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap_or(Vec2::ZERO);
+
+    // Diagonals of keys and d-pads are as fast as straight moves, like a fully tilted stick.
+    action_input.movement = apply_deadzone(stick, input_map.stick_deadzone).unwrap_or_else(|| {
+        Vec2::new(
+            action_input.axis(Action::MoveLeft, Action::MoveRight),
+            action_input.axis(Action::MoveDown, Action::MoveUp),
+        )
+        .normalize_or_zero()
+    });
+}
+
+// World position of the mouse cursor.
+#[derive(Default, Deref, DerefMut, Resource, Reflect)]
+pub struct CursorPos(Option<Vec2>);
+
+pub fn update_cursor_pos(
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    windows: Query<&Window>,
+    mut pos: ResMut<CursorPos>,
+) {
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor_position) = windows.single().cursor_position() else {
+        return;
+    };
+    let cursor_world_position = camera.viewport_to_world_2d(camera_transform, cursor_position);
+
+    **pos = cursor_world_position;
+}
@@ -1,7 +1,10 @@
 use bevy::prelude::{App, Plugin};
 use bevy_inspector_egui::quick::{ResourceInspectorPlugin, WorldInspectorPlugin};
 
-use crate::{ai, combat, maps, physics, sprites, ui};
+use crate::{
+    ai, camera, clock, combat, debug_overlay, farming, input, interaction, maps, physics, sprites,
+    ui,
+};
 
 pub struct InspectorPlugin;
 
@@ -9,18 +12,43 @@ impl Plugin for InspectorPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(WorldInspectorPlugin::new())
             .add_plugin(ResourceInspectorPlugin::<maps::EntityGridMap>::new())
-            .add_plugin(ResourceInspectorPlugin::<ai::CursorPos>::new())
+            .add_plugin(ResourceInspectorPlugin::<input::CursorPos>::new())
+            .add_plugin(ResourceInspectorPlugin::<debug_overlay::DebugOverlay>::new())
+            .add_plugin(ResourceInspectorPlugin::<clock::GameClock>::new())
             // Type should be registered to view in WorldInspector. Components should be derived from `Reflect` and `Clone`.
+            .register_type::<camera::GameCamera>()
             .register_type::<sprites::Facing>()
             .register_type::<physics::MoveLock>()
             .register_type::<maps::Coordinate>()
+            .register_type::<maps::LevelExit>()
+            .register_type::<maps::Entrance>()
+            .register_type::<maps::Offscreen>()
+            .register_type::<maps::TileAnimation>()
             .register_type::<combat::Delay>()
+            .register_type::<combat::Health>()
+            .register_type::<combat::GodMode>()
+            .register_type::<combat::Roll>()
+            .register_type::<combat::Invulnerable>()
+            .register_type::<physics::Stamina>()
+            .register_type::<interaction::Channel>()
+            .register_type::<interaction::Interactable>()
+            .register_type::<interaction::Chest>()
+            .register_type::<farming::Soil>()
+            .register_type::<farming::Crop>()
             .register_type::<ui::ConsoleData>()
             .register_type::<sprites::AnimationIndices>()
+            .register_type::<sprites::Appearance>()
             .register_type::<seldom_map_nav::prelude::Pathfind>()
             .register_type::<seldom_map_nav::prelude::Nav>()
             .register_type::<ai::Distance>()
             .register_type::<ai::Approach>()
+            .register_type::<ai::PartyMember>()
+            .register_type::<ai::Follow>()
+            .register_type::<ai::AssistLeader>()
+            .register_type::<ai::Persona>()
+            .register_type::<ai::MemoryStream>()
+            .register_type::<ai::Relationships>()
+            .register_type::<ai::DailyPlan>()
             .register_type::<big_brain::thinker::Actor>()
             .register_type::<big_brain::scorers::Score>()
             .register_type::<big_brain::actions::ActionState>();
@@ -0,0 +1,437 @@
+use bevy::{
+    prelude::{
+        default, Added, App, BuildChildren, Bundle, Children, Color, Commands, Component,
+        DespawnRecursiveExt, Entity, EventReader, EventWriter, GlobalTransform, Handle, Image,
+        IntoSystemConfig, Parent, Plugin, Query, RemovedComponents, Res, ResMut, Sprite,
+        SpriteBundle, Transform, Vec2, Vec3, With, Without,
+    },
+    reflect::{FromReflect, Reflect},
+    sprite::Anchor,
+    time::{Time, Timer, TimerMode},
+};
+use bevy_ecs_ldtk::{
+    prelude::{LdtkEntity, LdtkEntityAppExt, LdtkFields},
+    EntityInstance, LdtkLevel,
+};
+
+use crate::{
+    combat::DamageEvent,
+    constants::GRID_SIZE,
+    input::{Action, ActionInput},
+    maps::{Coordinate, EntityGridMap, TileGridMap},
+    physics::{movement, MoveLock},
+    sprites::{FaceDirection, Facing},
+    state::move_unlock_system,
+    ui::{BarkEvent, Inventory, ItemDictionary},
+    units::Player,
+};
+
+// Progress bars are drawn above the head of 16x32 unit sprites.
+const BAR_OFFSET_Y: f32 = 20.;
+const BAR_WIDTH: f32 = 16.;
+const BAR_HEIGHT: f32 = 2.;
+
+const CHEST_SECONDS: f32 = 1.;
+const CHEST_COLOR: Color = Color::rgb(0.75, 0.45, 0.2);
+const OPENED_CHEST_COLOR: Color = Color::rgb(0.35, 0.22, 0.12);
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChannelCompletedEvent>()
+            .add_event::<ChannelCancelledEvent>()
+            .add_system(start_interaction.before(tick_channels))
+            // Channels lock movement again after `move_unlock_system` released it, and before the player moves.
+            .add_system(tick_channels.after(move_unlock_system).before(movement))
+            .add_system(update_channel_bars.after(tick_channels))
+            .add_system(bark_interrupted_channels.after(tick_channels))
+            .register_ldtk_entity::<ChestBundle>("Chest")
+            .add_system(place_chests)
+            .add_system(forget_removed_chests)
+            .add_system(open_chests.after(tick_channels));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, FromReflect)]
+pub enum ChannelKind {
+    OpenChest,
+    Harvest,
+}
+
+// An action that takes time. While the component exists, the unit can't move and a progress bar is shown over it.
+// It is cancelled by movement input or damage. NPC actions can insert it too, and wait until it is removed.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Channel {
+    pub kind: ChannelKind,
+    // Entity being interacted with, e.g. the chest to open.
+    pub target: Option<Entity>,
+    pub timer: Timer,
+}
+
+impl Channel {
+    pub fn new(kind: ChannelKind, target: Option<Entity>, seconds: f32) -> Self {
+        Channel {
+            kind,
+            target,
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+
+    // From 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.timer.percent()
+    }
+}
+
+// Entities that the player can interact with by facing them and pressing `Action::Interact`.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Interactable {
+    pub kind: ChannelKind,
+    pub seconds: f32,
+}
+
+// Chest placed in LDtk levels. Opening it puts `count` of `item` into the inventory of the opener.
+// It opens again when its level respawns.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct Chest {
+    pub item: Option<String>,
+    pub count: u32,
+}
+
+impl From<&EntityInstance> for Chest {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Chest {
+            item: entity_instance
+                .get_maybe_string_field("item")
+                .ok()
+                .cloned()
+                .flatten(),
+            count: entity_instance
+                .get_int_field("count")
+                .map_or(1, |count| (*count).max(0) as u32),
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct ChestBundle {
+    #[from_entity_instance]
+    pub chest: Chest,
+}
+
+#[derive(Debug)]
+pub struct ChannelCompletedEvent {
+    pub entity: Entity,
+    pub kind: ChannelKind,
+    pub target: Option<Entity>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    Moved,
+    Damaged,
+}
+
+#[derive(Debug)]
+pub struct ChannelCancelledEvent {
+    pub entity: Entity,
+    pub kind: ChannelKind,
+    pub reason: CancelReason,
+}
+
+// Background of the progress bar, spawned as a child of the channelling unit.
+#[derive(Component)]
+pub struct ChannelBar;
+
+#[derive(Component)]
+pub struct ChannelBarFill;
+
+// Starts the interaction of the `Interactable` in front of the player.
+#[allow(clippy::type_complexity)]
+pub fn start_interaction(
+    mut commands: Commands,
+    action_input: Res<ActionInput>,
+    entity_map: Res<EntityGridMap>,
+    player_query: Query<
+        (Entity, &Facing, &Coordinate, &MoveLock),
+        (With<Player>, Without<Channel>),
+    >,
+    interactable_query: Query<&Interactable>,
+) {
+    if !action_input.just_pressed(Action::Interact) {
+        return;
+    }
+
+    for (player, facing, coordinate, move_lock) in player_query.iter() {
+        if move_lock.0 {
+            continue;
+        }
+
+        let (range_x, range_y) = match facing.direction {
+            FaceDirection::Down => (
+                (coordinate.min_x..=coordinate.max_x),
+                (coordinate.min_y - 1..=coordinate.min_y - 1),
+            ),
+            FaceDirection::Left => (
+                (coordinate.min_x - 1..=coordinate.min_x - 1),
+                (coordinate.min_y..=coordinate.max_y),
+            ),
+            FaceDirection::Right => (
+                (coordinate.max_x + 1..=coordinate.max_x + 1),
+                (coordinate.min_y..=coordinate.max_y),
+            ),
+            FaceDirection::Up => (
+                (coordinate.min_x..=coordinate.max_x),
+                (coordinate.max_y + 1..=coordinate.max_y + 1),
+            ),
+        };
+
+        let target = range_x
+            .flat_map(|x| range_y.clone().map(move |y| (x, y)))
+            .filter_map(|cell| entity_map.get(cell))
+            .flatten()
+            .find_map(|&entity| {
+                interactable_query
+                    .get(entity)
+                    .ok()
+                    .map(|interactable| (entity, interactable))
+            });
+
+        if let Some((target, interactable)) = target {
+            commands.entity(player).insert(Channel::new(
+                interactable.kind,
+                Some(target),
+                interactable.seconds,
+            ));
+        }
+    }
+}
+
+// Ticks channels and holds `MoveLock` while they run.
+// Movement input cancels the player's channel, and any damage cancels the channel of the damaged unit.
+pub fn tick_channels(
+    mut commands: Commands,
+    time: Res<Time>,
+    action_input: Res<ActionInput>,
+    mut channel_query: Query<(Entity, &mut Channel, Option<&mut MoveLock>, Option<&Player>)>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut completed_writer: EventWriter<ChannelCompletedEvent>,
+    mut cancelled_writer: EventWriter<ChannelCancelledEvent>,
+) {
+    let damaged: Vec<Entity> = damage_events
+        .iter()
+        .map(|event| event.damaged_entity)
+        .collect();
+    let moved = action_input.movement() != Vec2::ZERO;
+
+    for (entity, mut channel, move_lock, player) in channel_query.iter_mut() {
+        let reason = if damaged.contains(&entity) {
+            Some(CancelReason::Damaged)
+        } else if player.is_some() && moved {
+            Some(CancelReason::Moved)
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            commands.entity(entity).remove::<Channel>();
+            cancelled_writer.send(ChannelCancelledEvent {
+                entity,
+                kind: channel.kind,
+                reason,
+            });
+            continue;
+        }
+
+        if let Some(mut move_lock) = move_lock {
+            move_lock.0 = true;
+        }
+
+        if channel.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Channel>();
+            completed_writer.send(ChannelCompletedEvent {
+                entity,
+                kind: channel.kind,
+                target: channel.target,
+            });
+        }
+    }
+}
+
+// Units hit while channelling say so, since the progress bar just disappears like when they walk away.
+pub fn bark_interrupted_channels(
+    mut cancelled_events: EventReader<ChannelCancelledEvent>,
+    mut bark_writer: EventWriter<BarkEvent>,
+) {
+    for event in cancelled_events.iter() {
+        if event.reason != CancelReason::Damaged {
+            continue;
+        }
+        let message = match event.kind {
+            ChannelKind::OpenChest => "Ouch! The chest will have to wait.",
+            ChannelKind::Harvest => "Ouch! The harvest will have to wait.",
+        };
+        bark_writer.send(BarkEvent {
+            speaker: event.entity,
+            message: message.to_string(),
+        });
+    }
+}
+
+// Spawns progress bars for new channels, fills them, and despawns them when the channel is gone.
+pub fn update_channel_bars(
+    mut commands: Commands,
+    channel_query: Query<(Entity, &Channel, Option<&Children>)>,
+    bar_query: Query<(Entity, &Parent), With<ChannelBar>>,
+    mut fill_query: Query<(&Parent, &mut Sprite), With<ChannelBarFill>>,
+) {
+    for (bar, parent) in bar_query.iter() {
+        if !channel_query.contains(parent.get()) {
+            commands.entity(bar).despawn_recursive();
+        }
+    }
+
+    for (entity, channel, children) in channel_query.iter() {
+        let bar = children.and_then(|children| {
+            children
+                .iter()
+                .find(|&&child| bar_query.contains(child))
+                .copied()
+        });
+
+        let Some(bar) = bar else {
+            let bar = commands
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgba(0., 0., 0., 0.7),
+                            custom_size: Some(Vec2::new(BAR_WIDTH, BAR_HEIGHT)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(Vec3::new(0., BAR_OFFSET_Y, 1.)),
+                        ..default()
+                    },
+                    ChannelBar,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::rgb_u8(102, 255, 102),
+                                custom_size: Some(Vec2::new(0., BAR_HEIGHT)),
+                                anchor: Anchor::CenterLeft,
+                                ..default()
+                            },
+                            transform: Transform::from_translation(Vec3::new(
+                                -BAR_WIDTH / 2.,
+                                0.,
+                                0.1,
+                            )),
+                            ..default()
+                        },
+                        ChannelBarFill,
+                    ));
+                })
+                .id();
+            commands.entity(entity).push_children(&[bar]);
+            continue;
+        };
+
+        for (parent, mut sprite) in fill_query.iter_mut() {
+            if parent.get() == bar {
+                sprite.custom_size = Some(Vec2::new(BAR_WIDTH * channel.progress(), BAR_HEIGHT));
+            }
+        }
+    }
+}
+
+// Shows new chests as boxes and puts them into `EntityGridMap`, with their position computed like `insert_terrain`.
+pub fn place_chests(
+    mut commands: Commands,
+    mut entity_map: ResMut<EntityGridMap>,
+    chest_query: Query<(Entity, &Parent, &Transform), Added<Chest>>,
+    layer_query: Query<(&Parent, &Transform)>,
+    level_query: Query<&GlobalTransform, With<Handle<LdtkLevel>>>,
+) {
+    for (entity, layer, transform) in chest_query.iter() {
+        let Ok((level, layer_transform)) = layer_query.get(layer.get()) else {
+            continue;
+        };
+        let Ok(level_transform) = level_query.get(level.get()) else {
+            continue;
+        };
+        let translation =
+            level_transform.translation() + layer_transform.translation + transform.translation;
+        let (x, y) = TileGridMap::coordinate_of(translation.truncate());
+
+        entity_map.insert((x, y), entity);
+        commands.entity(entity).insert((
+            Sprite {
+                color: CHEST_COLOR,
+                custom_size: Some(Vec2::splat(GRID_SIZE * 0.75)),
+                ..default()
+            },
+            Handle::<Image>::default(),
+            Coordinate {
+                min_x: x,
+                min_y: y,
+                max_x: x,
+                max_y: y,
+            },
+            Interactable {
+                kind: ChannelKind::OpenChest,
+                seconds: CHEST_SECONDS,
+            },
+        ));
+    }
+}
+
+// Chests despawn with their level, so they are taken out of `EntityGridMap` too.
+pub fn forget_removed_chests(
+    mut removed_chests: RemovedComponents<Chest>,
+    mut entity_map: ResMut<EntityGridMap>,
+) {
+    for chest in removed_chests.iter() {
+        entity_map.entity_map.retain(|_, entities| {
+            entities.retain(|&entity| entity != chest);
+            !entities.is_empty()
+        });
+    }
+}
+
+pub fn open_chests(
+    mut commands: Commands,
+    item_dictionary: Res<ItemDictionary>,
+    mut completed_events: EventReader<ChannelCompletedEvent>,
+    mut chest_query: Query<(&Chest, &mut Sprite), With<Interactable>>,
+    mut inventory_query: Query<&mut Inventory>,
+) {
+    for event in completed_events.iter() {
+        if event.kind != ChannelKind::OpenChest {
+            continue;
+        }
+        let Some(target) = event.target else {
+            continue;
+        };
+        let Ok((chest, mut sprite)) = chest_query.get_mut(target) else {
+            continue;
+        };
+
+        if let (Some(item_info_index), Ok(mut inventory)) = (
+            chest
+                .item
+                .as_deref()
+                .and_then(|item| item_dictionary.find(item)),
+            inventory_query.get_mut(event.entity),
+        ) {
+            // The chest stays closed if the inventory is full.
+            if !inventory.stack(&mut commands, item_info_index, chest.count) {
+                continue;
+            }
+        }
+
+        sprite.color = OPENED_CHEST_COLOR;
+        commands.entity(target).remove::<Interactable>();
+    }
+}
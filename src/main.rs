@@ -10,11 +10,17 @@ use bevy_rapier2d::prelude::{NoUserData, RapierPhysicsPlugin};
 
 mod ai;
 mod camera;
+mod clock;
 mod combat;
 mod constants;
+mod debug_overlay;
+mod farming;
+mod input;
 mod inspector;
+mod interaction;
 mod maps;
 mod physics;
+mod save;
 mod sprites;
 mod state;
 mod ui;
@@ -25,16 +31,27 @@ fn main() {
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
         .add_plugin(LdtkPlugin)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+        .add_plugin(input::InputMapPlugin)
         .add_plugin(maps::MapPlugin)
         .add_plugin(physics::PhysicsPlugin)
         .add_plugin(sprites::SpritesPlugin)
         .add_plugin(camera::CameraPlugin)
         .add_plugin(units::UnitsPlugin)
         .add_plugin(combat::CombatPlugin)
+        .add_plugin(clock::ClockPlugin)
+        .add_plugin(interaction::InteractionPlugin)
+        .add_plugin(farming::FarmingPlugin)
         // StatePlugin should be front of ConsolePlugin due to `add_state`.
         .add_plugin(state::StatePlugin)
         .add_plugin(ui::ConsolePlugin)
+        .add_plugin(ui::DevConsolePlugin)
+        .add_plugin(ui::HudPlugin)
+        .add_plugin(ui::WorldMapPlugin)
+        .add_plugin(ui::SpeechBubblePlugin)
+        .add_plugin(ui::MenuPlugin)
+        .add_plugin(save::SavePlugin)
         .add_plugin(ai::AIPlugin)
+        .add_plugin(debug_overlay::DebugOverlayPlugin)
         .add_plugin(inspector::InspectorPlugin)
         .run();
 }
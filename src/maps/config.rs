@@ -0,0 +1,100 @@
+use bevy::prelude::{warn, Resource};
+use bevy_ecs_ldtk::LevelSelection;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fs};
+
+use super::{Terrain, TileType, DEFAULT_TERRAIN};
+
+// The map is read from here at startup. `--map <path>` and `--level <identifier>` override it.
+pub const MAP_CONFIG_PATH: &str = "config/map.ron";
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapConfig {
+    // LDtk file in the assets directory, like `Typical_TopDown_example.ldtk`.
+    pub path: String,
+    // Identifier of the level the game starts in. The first level of the file is used if it is `None`.
+    pub start_level: Option<String>,
+    // Terrain of int-grid values, tile enum tags and tile custom data, by their identifier in LDtk.
+    // Values are matched by identifier, not by number, so maps can number them differently.
+    pub terrain_identifiers: BTreeMap<String, TileType>,
+    pub terrains: BTreeMap<TileType, Terrain>,
+    // Tiles on these layers are drawn over others, and aren't floor by themselves.
+    pub decoration_layers: Vec<String>,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        MapConfig {
+            path: "Typical_TopDown_example.ldtk".to_string(),
+            start_level: None,
+            terrain_identifiers: [
+                ("walls", TileType::Wall),
+                ("water", TileType::Water),
+                ("deep_water", TileType::DeepWater),
+                ("grass", TileType::Grass),
+                ("road", TileType::Road),
+                ("mud", TileType::Mud),
+            ]
+            .into_iter()
+            .map(|(identifier, tile_type)| (identifier.to_string(), tile_type))
+            .collect(),
+            terrains: Terrain::defaults(),
+            decoration_layers: vec!["Wall_tops".to_string()],
+        }
+    }
+}
+
+impl MapConfig {
+    // Reads `MAP_CONFIG_PATH`, and applies the command line arguments over it.
+    pub fn load() -> Self {
+        let loaded = fs::read_to_string(MAP_CONFIG_PATH)
+            .map_err(|err| err.to_string())
+            .and_then(|text| ron::from_str::<MapConfig>(&text).map_err(|err| err.to_string()));
+
+        let mut map_config = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                warn!(
+                    "Using the default map, failed to load {}: {}",
+                    MAP_CONFIG_PATH, err
+                );
+                MapConfig::default()
+            }
+        };
+        // Tile types missing in the file keep their default terrain.
+        for (tile_type, terrain) in Terrain::defaults() {
+            map_config.terrains.entry(tile_type).or_insert(terrain);
+        }
+        map_config.apply_args(env::args().skip(1));
+        map_config
+    }
+
+    fn apply_args(&mut self, mut args: impl Iterator<Item = String>) {
+        while let Some(arg) = args.next() {
+            if arg != "--map" && arg != "--level" {
+                continue;
+            }
+            let Some(value) = args.next() else {
+                warn!("Missing value after {}", arg);
+                break;
+            };
+            if arg == "--map" {
+                self.path = value;
+            } else {
+                self.start_level = Some(value);
+            }
+        }
+    }
+
+    pub fn terrain(&self, tile_type: TileType) -> &Terrain {
+        self.terrains.get(&tile_type).unwrap_or(&DEFAULT_TERRAIN)
+    }
+
+    pub fn start_level_selection(&self) -> LevelSelection {
+        match &self.start_level {
+            Some(identifier) => LevelSelection::Identifier(identifier.clone()),
+            None => LevelSelection::Index(0),
+        }
+    }
+}
@@ -1,33 +1,94 @@
-use bevy::prelude::{
-    AssetServer, Assets, Bundle, Commands, Component, Handle, Query, Rect, Res, ResMut, Transform,
-    Vec2, With, Without,
+use bevy::{
+    prelude::{
+        Added, AssetServer, Assets, Bundle, Commands, Component, Entity, Handle, Parent, Query,
+        Rect, Res, ResMut, Transform, Vec2, With, Without,
+    },
+    utils::HashMap,
+};
+use bevy_ecs_ldtk::{
+    prelude::{LayerMetadata, LdtkIntCell},
+    IntGridCell, LdtkAsset, LdtkLevel, LdtkWorldBundle, LevelSelection,
 };
-use bevy_ecs_ldtk::{prelude::LdtkIntCell, LdtkLevel, LdtkWorldBundle, LevelSelection};
 
 use crate::units::Player;
 
+use super::{insert_terrain_tile, LevelTransition, MapConfig, TileType};
+
+// Registered for every int-grid value. Their terrain is inserted by `insert_int_grid_terrain`.
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
-pub struct WallBundle {
-    wall: Wall,
+pub struct IntGridCellBundle {
+    #[from_int_grid_cell]
+    int_grid_cell: IntGridCell,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Wall;
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let ldtk_handle = asset_server.load("Typical_TopDown_example.ldtk");
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, map_config: Res<MapConfig>) {
+    let ldtk_handle = asset_server.load(map_config.path.as_str());
     commands.spawn(LdtkWorldBundle {
         ldtk_handle: ldtk_handle,
         ..Default::default()
     });
 }
 
+// Inserts `TerrainTile` to int-grid cells, by the identifier of their value in `MapConfig::terrain_identifiers`.
+pub fn insert_int_grid_terrain(
+    mut commands: Commands,
+    map_config: Res<MapConfig>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    world_query: Query<&Handle<LdtkAsset>>,
+    layer_query: Query<&LayerMetadata>,
+    cell_query: Query<(Entity, &IntGridCell, &Parent), Added<IntGridCell>>,
+) {
+    if cell_query.is_empty() {
+        return;
+    }
+    let Some(ldtk_asset) = world_query
+        .iter()
+        .find_map(|ldtk_handle| ldtk_assets.get(ldtk_handle))
+    else {
+        return;
+    };
+
+    // Tile types by layer definition uid and value.
+    let tile_types: HashMap<(i32, i32), TileType> = ldtk_asset
+        .project
+        .defs
+        .layers
+        .iter()
+        .flat_map(|layer| {
+            layer.int_grid_values.iter().filter_map(|value| {
+                let tile_type = map_config
+                    .terrain_identifiers
+                    .get(value.identifier.as_ref()?)?;
+                Some(((layer.uid, value.value), *tile_type))
+            })
+        })
+        .collect();
+
+    for (entity, cell, parent) in cell_query.iter() {
+        let Ok(layer) = layer_query.get(parent.get()) else {
+            continue;
+        };
+        if let Some(&tile_type) = tile_types.get(&(layer.layer_def_uid, cell.value)) {
+            insert_terrain_tile(&mut commands, entity, tile_type, &map_config);
+        }
+    }
+}
+
+// Selects the level the player walks into. `LevelTransition` selects levels by itself.
 pub fn update_level_selection(
     level_query: Query<(&Handle<LdtkLevel>, &Transform), Without<Player>>,
     player_query: Query<&Transform, With<Player>>,
+    transition: Option<Res<LevelTransition>>,
     mut level_selection: ResMut<LevelSelection>,
     ldtk_levels: Res<Assets<LdtkLevel>>,
 ) {
+    if transition.is_some() {
+        return;
+    }
+
     for (level_handle, level_transform) in &level_query {
         if let Some(ldtk_level) = ldtk_levels.get(level_handle) {
             let level_bounds = Rect {
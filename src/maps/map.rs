@@ -1,9 +1,10 @@
 use bevy::prelude::{
-    Added, Changed, Component, Entity, GlobalTransform, Handle, Parent, Query, Reflect, ResMut,
-    Resource, Transform, With, Without,
+    Added, Changed, Component, Entity, GlobalTransform, Handle, Parent, Query, Reflect, Res,
+    ResMut, Resource, Transform, Vec2, With, Without,
 };
-use bevy_ecs_ldtk::LdtkLevel;
+use bevy_ecs_ldtk::{prelude::LayerMetadata, LdtkLevel};
 use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 
@@ -12,9 +13,9 @@ use crate::{
     units::UnitSize,
 };
 
-use super::Wall;
+use super::{MapConfig, TerrainTile};
 
-#[derive(Clone, Default, Debug, Component, Reflect)]
+#[derive(Clone, Default, Debug, Component, Reflect, Serialize, Deserialize)]
 pub struct Coordinate {
     pub min_x: i32,
     pub min_y: i32,
@@ -181,10 +182,17 @@ pub struct TileGridMap {
     pub max_y: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// Terrain of a grid cell. What each type does is set by `MapConfig::terrains`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
+    Road,
+    Grass,
+    Mud,
+    // Shallow water can be swum through, deep water can't be entered.
+    Water,
+    DeepWater,
 }
 
 impl TileGridMap {
@@ -203,16 +211,37 @@ impl TileGridMap {
         if self.max_y < coordinate.1 {
             self.max_y = coordinate.1;
         }
+        // Floor only fills cells without other terrain, and walls are never replaced, whichever layer spawns first.
+        if let Some((_, old_type)) = self.tile_map.get(&coordinate) {
+            if *old_type == TileType::Wall
+                || (tile_type == TileType::Floor && *old_type != TileType::Floor)
+            {
+                return;
+            }
+        }
         self.tile_map.insert(coordinate, (entity, tile_type));
     }
 
+    // Coordinate of the cell `position` is in.
+    pub fn coordinate_of(position: Vec2) -> (i32, i32) {
+        (
+            ((position.x - GRID_OFFSET) / GRID_SIZE).round() as i32,
+            ((position.y - GRID_OFFSET) / GRID_SIZE).round() as i32,
+        )
+    }
+
+    pub fn tile_type_at(&self, position: Vec2) -> Option<TileType> {
+        self.tile_map
+            .get(&Self::coordinate_of(position))
+            .map(|(_, tile_type)| *tile_type)
+    }
+
     #[allow(dead_code)]
     pub fn delete(&mut self, coordinate: (i32, i32)) {
         self.tile_map.remove(&coordinate);
         // Maybe refreshing values of max_x and max_y is not needed.
     }
 
-    #[allow(dead_code)]
     pub fn get(&self, coordinate: (i32, i32)) -> Option<&(Entity, TileType)> {
         self.tile_map.get(&coordinate)
     }
@@ -224,40 +253,44 @@ impl TileGridMap {
 }
 
 // TODO: need to delete wall entity from entity map
-pub fn insert_wall(
+pub fn insert_terrain(
     mut tile_map: ResMut<TileGridMap>,
     gparent_query: Query<&GlobalTransform, With<Handle<LdtkLevel>>>,
-    parent_query: Query<(&Parent, &Transform), Without<Wall>>,
-    wall_query: Query<(Entity, &Parent, &Transform), Added<Wall>>,
+    parent_query: Query<(&Parent, &Transform), Without<TerrainTile>>,
+    terrain_query: Query<(Entity, &TerrainTile, &Parent, &Transform), Added<TerrainTile>>,
 ) {
-    // Calculate Transform of wall entity by referring grand parent entity(World_Level)'s GlobalTransform and parent(Collisions)'s Transform and insert it into tile map.
-    for (entity, parent, transform) in wall_query.iter() {
+    // Calculate Transform of tile entity by referring grand parent entity(World_Level)'s GlobalTransform and parent(layer)'s Transform and insert it into tile map.
+    for (entity, terrain, parent, transform) in terrain_query.iter() {
         if let Ok((gparent, p_transform)) = parent_query.get(parent.get()) {
             if let Ok(g_transform) = gparent_query.get(gparent.get()) {
                 let translation =
                     g_transform.translation() + p_transform.translation + transform.translation;
-                let x = ((translation.x - GRID_OFFSET) / GRID_SIZE) as i32;
-                let y = ((translation.y - GRID_OFFSET) / GRID_SIZE) as i32;
-                tile_map.insert((x, y), entity, TileType::Wall);
+                let coordinate = TileGridMap::coordinate_of(translation.truncate());
+                tile_map.insert(coordinate, entity, terrain.0);
             }
         }
     }
 }
 
+// Tiles without `TerrainTile` are floor, except ones on `MapConfig::decoration_layers`.
+#[allow(clippy::type_complexity)]
 pub fn insert_floor(
+    map_config: Res<MapConfig>,
     mut tile_map: ResMut<TileGridMap>,
     gparent_query: Query<&GlobalTransform, With<Handle<LdtkLevel>>>,
-    parent_query: Query<(&Parent, &Transform), Without<Wall>>,
-    floor_query: Query<(Entity, &Parent, &Transform), (Without<Wall>, Added<TilePos>)>,
+    parent_query: Query<(&Parent, &Transform, &LayerMetadata), Without<TerrainTile>>,
+    floor_query: Query<(Entity, &Parent, &Transform), (Without<TerrainTile>, Added<TilePos>)>,
 ) {
     for (entity, parent, transform) in floor_query.iter() {
-        if let Ok((gparent, p_transform)) = parent_query.get(parent.get()) {
+        if let Ok((gparent, p_transform, layer)) = parent_query.get(parent.get()) {
+            if map_config.decoration_layers.contains(&layer.identifier) {
+                continue;
+            }
             if let Ok(g_transform) = gparent_query.get(gparent.get()) {
                 let translation =
                     g_transform.translation() + p_transform.translation + transform.translation;
-                let x = ((translation.x - GRID_OFFSET) / GRID_SIZE) as i32;
-                let y = ((translation.y - GRID_OFFSET) / GRID_SIZE) as i32;
-                tile_map.insert((x, y), entity, TileType::Floor);
+                let coordinate = TileGridMap::coordinate_of(translation.truncate());
+                tile_map.insert(coordinate, entity, TileType::Floor);
             }
         }
     }
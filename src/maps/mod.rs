@@ -1,23 +1,41 @@
-use bevy::prelude::{App, IntoSystemConfig, IntoSystemSetConfig, Plugin};
+use bevy::prelude::{App, IntoSystemConfig, IntoSystemSetConfig, OnUpdate, Plugin};
 use bevy_ecs_ldtk::{
-    prelude::LdtkIntCellAppExt, LdtkSettings, LdtkSystemSet, LevelSelection, LevelSpawnBehavior,
-    SetClearColor,
+    prelude::{LdtkEntityAppExt, LdtkIntCellAppExt},
+    LdtkSettings, LdtkSystemSet, LevelSpawnBehavior, SetClearColor,
 };
 use bevy_rapier2d::prelude::PhysicsSet;
 
+use crate::{
+    physics::movement,
+    state::{move_unlock_system, AppState},
+};
+
+pub mod config;
 pub mod ldtk;
 pub mod map;
+pub mod offscreen;
+pub mod terrain;
+pub mod tile_animation;
+pub mod transition;
+pub use config::*;
 pub use ldtk::*;
 pub use map::*;
+pub use offscreen::*;
+pub use terrain::*;
+pub use tile_animation::*;
+pub use transition::*;
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
+        let map_config = MapConfig::load();
+
         app
             // Required to prevent race conditions between bevy_ecs_ldtk's and bevy_rapier's systems
             .configure_set(LdtkSystemSet::ProcessApi.before(PhysicsSet::SyncBackend))
-            .insert_resource(LevelSelection::Uid(109))
+            .insert_resource(map_config.start_level_selection())
+            .insert_resource(map_config)
             .insert_resource(LdtkSettings {
                 level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
                     load_level_neighbors: true,
@@ -28,12 +46,27 @@ impl Plugin for MapPlugin {
             // TODO: Maybe we can use init_resource when we `impl Default` with using `new` as `default` function for `EntityGridMap` and `TileGridMap`.
             .insert_resource(map::EntityGridMap::new())
             .insert_resource(map::TileGridMap::new())
-            .register_ldtk_int_cell::<WallBundle>(1)
+            .register_default_ldtk_int_cell::<IntGridCellBundle>()
+            .register_ldtk_entity::<LevelExitBundle>("LevelExit")
+            .register_ldtk_entity::<EntranceBundle>("Entrance")
             .add_startup_system(setup)
-            // wall should be inserted after floor in constructiong tile grid map.
-            .add_system(insert_wall.after(insert_floor))
+            .add_startup_system(spawn_fade_overlay)
+            .add_system(insert_int_grid_terrain)
+            .add_system(insert_tile_terrain)
+            .add_system(insert_tile_animations)
+            .add_system(animate_tiles)
+            .add_system(insert_terrain.after(insert_floor))
             .add_system(insert_floor)
             .add_system(change_coordinate_of_moved_entity)
-            .add_system(update_level_selection);
+            .add_system(update_level_selection)
+            .add_system(enter_level_exits.in_set(OnUpdate(AppState::MainGame)))
+            // Locks the player again after `move_unlock_system` released it, like channels.
+            .add_system(
+                progress_level_transition
+                    .after(enter_level_exits)
+                    .after(move_unlock_system)
+                    .before(movement),
+            )
+            .add_system(update_offscreen_npcs);
     }
 }
@@ -0,0 +1,58 @@
+use bevy::{
+    prelude::{
+        Assets, Commands, Component, Entity, GlobalTransform, Handle, Query, Rect, Res, Transform,
+        Vec2, With,
+    },
+    reflect::Reflect,
+};
+use bevy_ecs_ldtk::LdtkLevel;
+use bevy_rapier2d::prelude::{RigidBodyDisabled, Velocity};
+
+use crate::units::NPC;
+
+// NPCs outside of every loaded level. Walls and floors of their level are gone, so their bodies are disabled.
+// They keep their `Transform` and still follow `Nav` paths, talk and remember things, since the navmesh and
+// `EntityGridMap` are kept for unloaded levels. They are put back at the same position when their level loads again.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct Offscreen;
+
+pub fn update_offscreen_npcs(
+    mut commands: Commands,
+    level_query: Query<(&Handle<LdtkLevel>, &GlobalTransform)>,
+    ldtk_levels: Res<Assets<LdtkLevel>>,
+    mut npc_query: Query<(Entity, &Transform, &mut Velocity, Option<&Offscreen>), With<NPC>>,
+) {
+    let loaded_levels: Vec<Rect> = level_query
+        .iter()
+        .filter_map(|(level_handle, level_transform)| {
+            let level = &ldtk_levels.get(level_handle)?.level;
+            let min = level_transform.translation().truncate();
+            Some(Rect::from_corners(
+                min,
+                min + Vec2::new(level.px_wid as f32, level.px_hei as f32),
+            ))
+        })
+        .collect();
+    // Levels are respawning, e.g. by the `reload` command.
+    if loaded_levels.is_empty() {
+        return;
+    }
+
+    for (npc, transform, mut velocity, offscreen) in npc_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let loaded = loaded_levels.iter().any(|rect| rect.contains(position));
+
+        match (loaded, offscreen.is_some()) {
+            (false, false) => {
+                velocity.linvel = Vec2::ZERO;
+                commands.entity(npc).insert((Offscreen, RigidBodyDisabled));
+            }
+            (true, true) => {
+                commands
+                    .entity(npc)
+                    .remove::<(Offscreen, RigidBodyDisabled)>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::{Added, Commands, Component, Entity, Or, Query, Res, Vec2};
+use bevy_ecs_ldtk::prelude::{TileEnumTags, TileMetadata};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{MapConfig, TileGridMap, TileType, Wall};
+
+// Used for tile types missing in `MapConfig::terrains`.
+pub static DEFAULT_TERRAIN: Terrain = Terrain {
+    speed_multiplier: 1.,
+    path_cost: 1.,
+    footsteps: Vec::new(),
+    swimmable: false,
+    blocking: false,
+};
+
+// How a tile type affects units on it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Terrain {
+    pub speed_multiplier: f32,
+    // Cost of walking through a tile, relative to floor. Paths go around costly tiles when it's cheaper.
+    pub path_cost: f32,
+    // Sounds in the assets directory, one of which is played for every step of the player.
+    pub footsteps: Vec<String>,
+    // Units swim through it, so they can't sprint.
+    pub swimmable: bool,
+    // Has colliders like walls, and is solid in navmeshes.
+    pub blocking: bool,
+}
+
+impl Terrain {
+    // Footsteps are `sounds/footsteps/<name>_1.ogg` and `<name>_2.ogg`.
+    fn new(speed_multiplier: f32, path_cost: f32, footsteps: &str) -> Self {
+        Terrain {
+            speed_multiplier,
+            path_cost,
+            footsteps: (1..=2)
+                .map(|variant| format!("sounds/footsteps/{}_{}.ogg", footsteps, variant))
+                .collect(),
+            ..DEFAULT_TERRAIN.clone()
+        }
+    }
+
+    pub fn defaults() -> BTreeMap<TileType, Terrain> {
+        [
+            (TileType::Floor, Terrain::new(1., 1., "floor")),
+            (TileType::Road, Terrain::new(1.15, 0.8, "road")),
+            (TileType::Grass, Terrain::new(0.95, 1.2, "grass")),
+            (TileType::Mud, Terrain::new(0.6, 3., "mud")),
+            (
+                TileType::Water,
+                Terrain {
+                    swimmable: true,
+                    ..Terrain::new(0.4, 6., "water")
+                },
+            ),
+            (
+                TileType::DeepWater,
+                Terrain {
+                    speed_multiplier: 0.,
+                    blocking: true,
+                    ..DEFAULT_TERRAIN.clone()
+                },
+            ),
+            (
+                TileType::Wall,
+                Terrain {
+                    speed_multiplier: 0.,
+                    blocking: true,
+                    ..DEFAULT_TERRAIN.clone()
+                },
+            ),
+        ]
+        .into()
+    }
+}
+
+// Terrain under `position`, floor if it's outside of the map.
+// Blocking terrain is ignored, since units only touch its edges.
+pub fn walkable_terrain_at<'a>(
+    map_config: &'a MapConfig,
+    tile_grid_map: &TileGridMap,
+    position: Vec2,
+) -> &'a Terrain {
+    tile_grid_map
+        .tile_type_at(position)
+        .map(|tile_type| map_config.terrain(tile_type))
+        .filter(|terrain| !terrain.blocking)
+        .unwrap_or_else(|| map_config.terrain(TileType::Floor))
+}
+
+// Terrain of a tile, read from LDtk. Tiles without it are floor, unless they are on decoration layers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
+pub struct TerrainTile(pub TileType);
+
+// Inserts `TerrainTile`, and `Wall` to blocking tiles.
+pub fn insert_terrain_tile(
+    commands: &mut Commands,
+    entity: Entity,
+    tile_type: TileType,
+    map_config: &MapConfig,
+) {
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.insert(TerrainTile(tile_type));
+    if map_config.terrain(tile_type).blocking {
+        entity_commands.insert(Wall);
+    }
+}
+
+// Reads terrain from enum tags and custom data of tiles, set in the tileset of LDtk.
+#[allow(clippy::type_complexity)]
+pub fn insert_tile_terrain(
+    mut commands: Commands,
+    map_config: Res<MapConfig>,
+    tile_query: Query<
+        (Entity, Option<&TileEnumTags>, Option<&TileMetadata>),
+        Or<(Added<TileEnumTags>, Added<TileMetadata>)>,
+    >,
+) {
+    for (entity, enum_tags, metadata) in tile_query.iter() {
+        let tile_type = enum_tags
+            .into_iter()
+            .flat_map(|enum_tags| enum_tags.tags.iter())
+            .map(String::as_str)
+            // Custom data can hold other lines too, like the frames of `TileAnimation`.
+            .chain(
+                metadata
+                    .into_iter()
+                    .flat_map(|metadata| metadata.data.lines()),
+            )
+            .find_map(|identifier| map_config.terrain_identifiers.get(identifier.trim()));
+
+        if let Some(&tile_type) = tile_type {
+            insert_terrain_tile(&mut commands, entity, tile_type, &map_config);
+        }
+    }
+}
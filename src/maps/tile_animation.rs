@@ -0,0 +1,118 @@
+use bevy::{
+    prelude::{Added, Commands, Component, Entity, Query, Res},
+    reflect::Reflect,
+    time::Time,
+};
+use bevy_ecs_ldtk::prelude::TileMetadata;
+use bevy_ecs_tilemap::tiles::TileTextureIndex;
+
+const DEFAULT_FPS: f32 = 4.;
+
+// Cycles the texture of a tile through `frames`, which are tile ids in the tileset of its layer.
+// Read from lines of the tile's custom data in LDtk, next to its terrain identifier:
+//
+//     water
+//     frames: 12 18 24 30
+//     fps: 4
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct TileAnimation {
+    pub frames: Vec<u32>,
+    pub fps: f32,
+}
+
+impl TileAnimation {
+    // `None` if the custom data has no valid `frames` line.
+    pub fn from_custom_data(data: &str) -> Option<Self> {
+        let mut frames = Vec::new();
+        let mut fps = DEFAULT_FPS;
+        for line in data.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key.trim() {
+                "frames" => {
+                    frames = value
+                        .split_whitespace()
+                        .filter_map(|frame| frame.parse().ok())
+                        .collect();
+                }
+                "fps" => fps = value.trim().parse().unwrap_or(DEFAULT_FPS),
+                _ => {}
+            }
+        }
+        (!frames.is_empty() && fps > 0.).then_some(TileAnimation { frames, fps })
+    }
+
+    // Frames only depend on the time since startup, so tiles with the same frames stay in sync.
+    fn frame_at(&self, seconds: f64) -> u32 {
+        let index = (seconds * self.fps as f64) as usize % self.frames.len();
+        self.frames[index]
+    }
+}
+
+pub fn insert_tile_animations(
+    mut commands: Commands,
+    tile_query: Query<(Entity, &TileMetadata), Added<TileMetadata>>,
+) {
+    for (entity, metadata) in tile_query.iter() {
+        if let Some(animation) = TileAnimation::from_custom_data(&metadata.data) {
+            commands.entity(entity).insert(animation);
+        }
+    }
+}
+
+pub fn animate_tiles(
+    time: Res<Time>,
+    mut tile_query: Query<(&TileAnimation, &mut TileTextureIndex)>,
+) {
+    let seconds = time.elapsed_seconds_f64();
+    for (animation, mut texture_index) in tile_query.iter_mut() {
+        let frame = animation.frame_at(seconds);
+        if texture_index.0 != frame {
+            texture_index.0 = frame;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_frames_and_fps_next_to_other_lines() {
+        let animation =
+            TileAnimation::from_custom_data("water\nframes: 492 494 496\nfps: 6").unwrap();
+        assert_eq!(animation.frames, vec![492, 494, 496]);
+        assert_eq!(animation.fps, 6.);
+    }
+
+    #[test]
+    fn uses_default_fps_when_missing_or_invalid() {
+        let animation = TileAnimation::from_custom_data("frames: 1 2").unwrap();
+        assert_eq!(animation.fps, DEFAULT_FPS);
+        let animation = TileAnimation::from_custom_data("frames: 1 2\nfps: fast").unwrap();
+        assert_eq!(animation.fps, DEFAULT_FPS);
+    }
+
+    #[test]
+    fn skips_invalid_frames() {
+        let animation = TileAnimation::from_custom_data("frames: 1 x 3").unwrap();
+        assert_eq!(animation.frames, vec![1, 3]);
+    }
+
+    #[test]
+    fn needs_frames_and_positive_fps() {
+        assert!(TileAnimation::from_custom_data("water").is_none());
+        assert!(TileAnimation::from_custom_data("frames:").is_none());
+        assert!(TileAnimation::from_custom_data("frames: 1 2\nfps: 0").is_none());
+    }
+
+    #[test]
+    fn frames_follow_time_and_loop() {
+        let animation = TileAnimation::from_custom_data("frames: 10 20 30\nfps: 2").unwrap();
+        assert_eq!(animation.frame_at(0.), 10);
+        assert_eq!(animation.frame_at(0.6), 20);
+        assert_eq!(animation.frame_at(1.2), 30);
+        assert_eq!(animation.frame_at(1.6), 10);
+    }
+}
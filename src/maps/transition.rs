@@ -0,0 +1,204 @@
+use bevy::{
+    ecs::change_detection::DetectChanges,
+    prelude::{
+        default, error, BackgroundColor, Bundle, Color, Commands, Component, Entity,
+        GlobalTransform, NodeBundle, PositionType, Query, Ref, Res, ResMut, Resource, Size, Style,
+        Transform, Val, Vec2, With, ZIndex,
+    },
+    reflect::Reflect,
+    time::{Time, Timer, TimerMode},
+};
+use bevy_ecs_ldtk::{
+    prelude::{LdtkEntity, LdtkFields},
+    EntityInstance, LevelSelection,
+};
+use seldom_map_nav::prelude::{Nav, Pathfind};
+
+use crate::{physics::MoveLock, units::Player};
+
+const FADE_SECONDS: f32 = 0.4;
+// The transition gives up on finding the entrance after this, e.g. when the destination level doesn't exist.
+const LOAD_TIMEOUT_SECONDS: f32 = 5.;
+
+// Area that moves the player to the `Entrance` called `entrance`, in the level with the identifier `destination`.
+// Place entrances next to exits, not on them, so the player isn't sent back right away.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct LevelExit {
+    pub destination: String,
+    pub entrance: String,
+    pub size: Vec2,
+}
+
+impl From<&EntityInstance> for LevelExit {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let field = |identifier: &str| {
+            entity_instance
+                .get_string_field(identifier)
+                .cloned()
+                .unwrap_or_default()
+        };
+        LevelExit {
+            destination: field("destination"),
+            entrance: field("entrance"),
+            size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct LevelExitBundle {
+    #[from_entity_instance]
+    pub level_exit: LevelExit,
+}
+
+// Named point where the player arrives from a `LevelExit`. Names should be unique in the whole world.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+pub struct Entrance {
+    pub name: String,
+}
+
+impl From<&EntityInstance> for Entrance {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Entrance {
+            name: entity_instance
+                .get_string_field("name")
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct EntranceBundle {
+    #[from_entity_instance]
+    pub entrance: Entrance,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionPhase {
+    FadeOut,
+    // The screen is black until the entrance of the destination level spawns.
+    Loading,
+    FadeIn,
+}
+
+// Exists while the player goes through a `LevelExit`. The player can't move until it is removed.
+#[derive(Resource, Debug)]
+pub struct LevelTransition {
+    pub destination: String,
+    pub entrance: String,
+    pub phase: TransitionPhase,
+    pub timer: Timer,
+}
+
+// Full screen black node over the HUD, faded in and out by transitions.
+#[derive(Component)]
+pub struct FadeOverlay;
+
+pub fn spawn_fade_overlay(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            // Over the HUD and consoles, under the menus.
+            z_index: ZIndex::Global(5),
+            ..default()
+        },
+        FadeOverlay,
+    ));
+}
+
+pub fn enter_level_exits(
+    mut commands: Commands,
+    transition: Option<Res<LevelTransition>>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    exit_query: Query<(&LevelExit, &GlobalTransform)>,
+) {
+    if transition.is_some() {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation().truncate();
+
+    for (level_exit, exit_transform) in exit_query.iter() {
+        let offset = (player_position - exit_transform.translation().truncate()).abs();
+        if offset.x < level_exit.size.x / 2. && offset.y < level_exit.size.y / 2. {
+            commands.insert_resource(LevelTransition {
+                destination: level_exit.destination.clone(),
+                entrance: level_exit.entrance.clone(),
+                phase: TransitionPhase::FadeOut,
+                timer: Timer::from_seconds(FADE_SECONDS, TimerMode::Once),
+            });
+            return;
+        }
+    }
+}
+
+// Fades out, switches `LevelSelection`, moves the player to the entrance once it spawns, and fades in.
+pub fn progress_level_transition(
+    mut commands: Commands,
+    transition: Option<ResMut<LevelTransition>>,
+    time: Res<Time>,
+    mut level_selection: ResMut<LevelSelection>,
+    entrance_query: Query<(Ref<Entrance>, &GlobalTransform)>,
+    mut player_query: Query<(Entity, &mut Transform, &mut MoveLock), With<Player>>,
+    mut overlay_query: Query<&mut BackgroundColor, With<FadeOverlay>>,
+) {
+    let Some(mut transition) = transition else {
+        return;
+    };
+    let Ok((player, mut player_transform, mut move_lock)) = player_query.get_single_mut() else {
+        return;
+    };
+    // Runs after `move_unlock_system`, so the player stays locked during the whole transition.
+    move_lock.0 = true;
+
+    let finished = transition.timer.tick(time.delta()).finished();
+    let alpha = match transition.phase {
+        TransitionPhase::FadeOut => transition.timer.percent(),
+        TransitionPhase::Loading => 1.,
+        TransitionPhase::FadeIn => transition.timer.percent_left(),
+    };
+    for mut background_color in overlay_query.iter_mut() {
+        background_color.0 = Color::rgba(0., 0., 0., alpha);
+    }
+
+    match transition.phase {
+        TransitionPhase::FadeOut if finished => {
+            *level_selection = LevelSelection::Identifier(transition.destination.clone());
+            transition.phase = TransitionPhase::Loading;
+            transition.timer = Timer::from_seconds(LOAD_TIMEOUT_SECONDS, TimerMode::Once);
+        }
+        TransitionPhase::Loading => {
+            // `GlobalTransform` of entrances is only correct from the frame after they spawn.
+            let entrance = entrance_query
+                .iter()
+                .find(|(entrance, _)| !entrance.is_added() && entrance.name == transition.entrance);
+            if let Some((_, entrance_transform)) = entrance {
+                let translation = entrance_transform.translation();
+                player_transform.translation.x = translation.x;
+                player_transform.translation.y = translation.y;
+                commands.entity(player).remove::<(Nav, Pathfind)>();
+            } else if finished {
+                error!(
+                    "Couldn't find entrance \"{}\" in level \"{}\"",
+                    transition.entrance, transition.destination
+                );
+            } else {
+                return;
+            }
+            transition.phase = TransitionPhase::FadeIn;
+            transition.timer = Timer::from_seconds(FADE_SECONDS, TimerMode::Once);
+        }
+        TransitionPhase::FadeIn if finished => {
+            commands.remove_resource::<LevelTransition>();
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,32 @@
+use bevy::prelude::{AssetServer, Audio, EventReader, Query, Res, Transform, With};
+use rand::seq::SliceRandom;
+
+use crate::{
+    maps::{walkable_terrain_at, MapConfig, TileGridMap},
+    sprites::{AnimationEvent, FOOTSTEP_EVENT},
+    units::Player,
+};
+
+// Plays footsteps of the terrain under the player on the frames of their clips that send `FOOTSTEP_EVENT`.
+pub fn play_footsteps(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    map_config: Res<MapConfig>,
+    tile_grid_map: Res<TileGridMap>,
+    mut animation_events: EventReader<AnimationEvent>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for event in animation_events.iter() {
+        if event.name != FOOTSTEP_EVENT {
+            continue;
+        }
+        let Ok(transform) = player_query.get(event.entity) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        let terrain = walkable_terrain_at(&map_config, &tile_grid_map, position);
+        if let Some(footstep) = terrain.footsteps.choose(&mut rand::thread_rng()) {
+            audio.play(asset_server.load(footstep.as_str()));
+        }
+    }
+}
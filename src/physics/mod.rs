@@ -1,10 +1,14 @@
-use bevy::prelude::{default, App, Plugin, Vec2};
+use bevy::prelude::{default, App, IntoSystemConfig, Plugin, Vec2};
 use bevy_rapier2d::prelude::RapierConfiguration;
 
 pub mod collision;
+pub mod footsteps;
 pub mod movement;
+pub mod stamina;
 pub use collision::*;
+pub use footsteps::*;
 pub use movement::*;
+pub use stamina::*;
 
 pub struct PhysicsPlugin;
 
@@ -15,6 +19,8 @@ impl Plugin for PhysicsPlugin {
             ..default()
         })
         .add_system(spawn_wall_collision)
-        .add_system(movement);
+        .add_system(movement)
+        .add_system(regenerate_stamina.after(movement))
+        .add_system(play_footsteps);
     }
 }
@@ -1,10 +1,23 @@
 use bevy::{
-    prelude::{Component, Entity, Input, KeyCode, Query, Res, With},
+    prelude::{Component, Entity, Query, Res, Transform, Vec2, With},
     reflect::Reflect,
+    time::Time,
 };
 use bevy_rapier2d::prelude::Velocity;
 
-use crate::units::Player;
+use crate::{
+    input::{Action, ActionInput},
+    maps::{walkable_terrain_at, MapConfig, TileGridMap},
+    units::Player,
+};
+
+use super::Stamina;
+
+// Speed of the player at full tilt, in pixels per second.
+pub const MOVE_SPEED: f32 = 100.;
+pub const SPRINT_MULTIPLIER: f32 = 1.6;
+// Stamina used per second while sprinting.
+pub const SPRINT_COST_PER_SECOND: f32 = 20.;
 
 #[derive(Component, Clone, Reflect, Debug)]
 
@@ -22,20 +35,32 @@ pub struct MovementEvent {
 }
 
 pub fn movement(
-    input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut Velocity, &MoveLock), With<Player>>,
+    time: Res<Time>,
+    action_input: Res<ActionInput>,
+    map_config: Res<MapConfig>,
+    tile_grid_map: Res<TileGridMap>,
+    mut query: Query<(&Transform, &mut Velocity, &MoveLock, Option<&mut Stamina>), With<Player>>,
 ) {
-    for (mut velocity, move_lock) in &mut query {
+    for (transform, mut velocity, move_lock, stamina) in &mut query {
         velocity.linvel.x = 0.;
         velocity.linvel.y = 0.;
         if !move_lock.0 {
-            let right = if input.pressed(KeyCode::D) { 1. } else { 0. };
-            let left = if input.pressed(KeyCode::A) { 1. } else { 0. };
-            let up = if input.pressed(KeyCode::W) { 1. } else { 0. };
-            let down = if input.pressed(KeyCode::S) { 1. } else { 0. };
+            let direction = action_input.movement();
+            let position = transform.translation.truncate();
+            let terrain = walkable_terrain_at(&map_config, &tile_grid_map, position);
+            let mut speed = MOVE_SPEED * terrain.speed_multiplier;
+
+            // Sprinting drains stamina only while moving, and stops when it runs out. The player can't sprint while swimming.
+            if action_input.pressed(Action::Sprint) && direction != Vec2::ZERO && !terrain.swimmable
+            {
+                if let Some(mut stamina) = stamina.filter(|stamina| stamina.current > 0.) {
+                    stamina.drain(SPRINT_COST_PER_SECOND * time.delta_seconds());
+                    speed *= SPRINT_MULTIPLIER;
+                }
+            }
 
-            velocity.linvel.x = (right - left) * 100.;
-            velocity.linvel.y = (up - down) * 100.;
+            // Analog sticks move slower when tilted less.
+            velocity.linvel = direction * speed;
         }
     }
 }
@@ -0,0 +1,61 @@
+use bevy::{
+    prelude::{Component, Query, Res},
+    reflect::Reflect,
+    time::{Time, Timer, TimerMode},
+};
+
+// Stamina spent by sprinting and dodge rolls. It regenerates after a short pause since it was last used.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    pub regen_per_second: f32,
+    pub regen_delay: Timer,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina {
+            current: 100.,
+            max: 100.,
+            regen_per_second: 25.,
+            regen_delay: Timer::from_seconds(1., TimerMode::Once),
+        }
+    }
+}
+
+impl Stamina {
+    // Spends `amount` only if there is enough of it.
+    pub fn spend(&mut self, amount: f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        self.regen_delay.reset();
+        true
+    }
+
+    // Spends up to `amount`, for costs over time like sprinting.
+    pub fn drain(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.);
+        self.regen_delay.reset();
+    }
+
+    // From 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0. {
+            0.
+        } else {
+            self.current / self.max
+        }
+    }
+}
+
+pub fn regenerate_stamina(time: Res<Time>, mut query: Query<&mut Stamina>) {
+    for mut stamina in query.iter_mut() {
+        if stamina.regen_delay.tick(time.delta()).finished() && stamina.current < stamina.max {
+            stamina.current = (stamina.current + stamina.regen_per_second * time.delta_seconds())
+                .min(stamina.max);
+        }
+    }
+}
@@ -0,0 +1,652 @@
+use bevy::{
+    prelude::{
+        error, info, App, Children, Commands, DespawnRecursiveExt, Entity, EventReader,
+        EventWriter, In, IntoSystemConfig, Name, Or, Plugin, Query, Res, ResMut, Resource, State,
+        Transform, Vec3, With,
+    },
+    time::{Time, Timer, TimerMode},
+};
+use bevy_ecs_ldtk::{EntityInstance, LevelSelection};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::{
+    ai::{DailyPlan, Memory, MemoryStream, Relationship, Relationships},
+    clock::GameClock,
+    farming::{crop_kind, despawn_crop, spawn_crop, spawn_soil, Crop, CropAtlas, Soil},
+    input::{Action, ActionInput},
+    maps::{Coordinate, EntityGridMap},
+    sprites::{Appearance, FaceDirection, Facing},
+    state::AppState,
+    ui::{
+        ArgKind, ArgSpec, ConsoleCommandAppExt, ConsoleCommandInput, ConsoleCommandSpec,
+        ConsoleData, ConsoleScope, DevConsole, Inventory, Item, ItemDictionary, Location,
+        PrintConsoleEvent,
+    },
+    units::{Player, NPC},
+};
+
+// Bump this when `SaveData` changes, and convert older files in `read_save`.
+pub const SAVE_VERSION: u32 = 1;
+pub const SAVE_SLOTS: u32 = 10;
+const SAVE_DIRECTORY: &str = "saves";
+// Saved entities that don't spawn within this time, e.g. killed NPCs, are skipped.
+const LOAD_TIMEOUT_SECONDS: f32 = 5.;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_system(quick_save_and_load)
+            .add_system(save_game.after(quick_save_and_load))
+            .add_system(load_game.after(quick_save_and_load))
+            .add_system(apply_pending_load.after(load_game))
+            .add_console_command(
+                ConsoleCommandSpec::new("save", "saves the game into slot, 0 by default")
+                    .arg(ArgSpec::optional("slot", ArgKind::Int))
+                    .scope(ConsoleScope::Developer),
+                save_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("load", "loads the game from slot, 0 by default")
+                    .arg(ArgSpec::optional("slot", ArgKind::Int))
+                    .scope(ConsoleScope::Developer),
+                load_command,
+            );
+    }
+}
+
+pub struct SaveGameEvent {
+    pub slot: u32,
+}
+
+pub struct LoadGameEvent {
+    pub slot: u32,
+}
+
+// Everything written to a save file. Entities are stored by their LDtk IID, since `Entity` ids change every run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SaveData {
+    pub version: u32,
+    pub level: SavedLevel,
+    pub clock: SavedClock,
+    pub units: Vec<SavedUnit>,
+    pub dev_console: Option<SavedConsole>,
+    pub farm: Vec<SavedSoil>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SavedLevel {
+    Identifier(String),
+    Index(usize),
+    Iid(String),
+    Uid(i32),
+}
+
+impl From<&LevelSelection> for SavedLevel {
+    fn from(level_selection: &LevelSelection) -> Self {
+        match level_selection {
+            LevelSelection::Identifier(identifier) => SavedLevel::Identifier(identifier.clone()),
+            LevelSelection::Index(index) => SavedLevel::Index(*index),
+            LevelSelection::Iid(iid) => SavedLevel::Iid(iid.clone()),
+            LevelSelection::Uid(uid) => SavedLevel::Uid(*uid),
+        }
+    }
+}
+
+impl From<SavedLevel> for LevelSelection {
+    fn from(level: SavedLevel) -> Self {
+        match level {
+            SavedLevel::Identifier(identifier) => LevelSelection::Identifier(identifier),
+            SavedLevel::Index(index) => LevelSelection::Index(index),
+            SavedLevel::Iid(iid) => LevelSelection::Iid(iid),
+            SavedLevel::Uid(uid) => LevelSelection::Uid(uid),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedClock {
+    pub day: u32,
+    pub minutes: f32,
+    pub minutes_per_second: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedUnit {
+    pub iid: String,
+    // Only used in messages, entities are found by `iid`.
+    pub name: String,
+    pub translation: [f32; 3],
+    pub facing: FaceDirection,
+    pub coordinate: Coordinate,
+    pub inventory: Option<Vec<SavedItem>>,
+    pub console: Option<SavedConsole>,
+    pub mind: Option<SavedMind>,
+    pub appearance: Option<Appearance>,
+}
+
+// Item in the inventory slot `index`. Items are saved by name, so saves don't depend on the order of `ItemDictionary`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedItem {
+    pub index: usize,
+    pub name: String,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SavedConsole {
+    pub messages: Vec<String>,
+    pub history: Vec<String>,
+}
+
+// What an NPC remembers and thinks of others.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedMind {
+    pub memories: Vec<Memory>,
+    pub relationships: HashMap<String, Relationship>,
+    pub plan: String,
+}
+
+// Tilled soil, and the crop planted in it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedSoil {
+    pub cell: (i32, i32),
+    pub watered: bool,
+    pub crop: Option<SavedCrop>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedCrop {
+    // Name in `CROP_KINDS`.
+    pub kind: String,
+    pub days_grown: u32,
+}
+
+// Units of a loaded save that are restored as soon as their entities spawn.
+#[derive(Resource)]
+pub struct PendingLoad {
+    units: Vec<SavedUnit>,
+    timer: Timer,
+}
+
+pub fn save_path(slot: u32) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("slot_{}.ron", slot))
+}
+
+fn write_save(slot: u32, data: &SaveData) -> Result<PathBuf, String> {
+    let path = save_path(slot);
+    let text = ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default())
+        .map_err(|err| format!("Failed to serialize save: {}", err))?;
+    fs::create_dir_all(SAVE_DIRECTORY)
+        .and_then(|_| fs::write(&path, text))
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    Ok(path)
+}
+
+fn read_save(slot: u32) -> Result<SaveData, String> {
+    // Only the version is read first, so saves of other versions give a clear error instead of a parse error.
+    #[derive(Deserialize)]
+    struct SaveHeader {
+        version: u32,
+    }
+
+    let path = save_path(slot);
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let header: SaveHeader = ron::from_str(&text)
+        .map_err(|err| format!("{} is not a save file: {}", path.display(), err))?;
+
+    match header.version {
+        SAVE_VERSION => {
+            ron::from_str(&text).map_err(|err| format!("{} is broken: {}", path.display(), err))
+        }
+        version if version > SAVE_VERSION => Err(format!(
+            "{} is saved by a newer version ({}) of the game",
+            path.display(),
+            version
+        )),
+        version => Err(format!(
+            "{} has an unsupported save version {}",
+            path.display(),
+            version
+        )),
+    }
+}
+
+fn saved_console(children: &Children, console_query: &Query<&ConsoleData>) -> Option<SavedConsole> {
+    children
+        .iter()
+        .find_map(|&child| console_query.get(child).ok())
+        .map(|data| SavedConsole {
+            messages: data.messages.clone(),
+            history: data.history.clone(),
+        })
+}
+
+// Returns false if the entity has no `ConsoleData` yet.
+fn restore_console(
+    children: Option<&Children>,
+    console_query: &mut Query<&mut ConsoleData>,
+    saved: &SavedConsole,
+) -> bool {
+    let Some(children) = children else {
+        return false;
+    };
+    for &child in children.iter() {
+        if let Ok(mut data) = console_query.get_mut(child) {
+            data.messages = saved.messages.clone();
+            data.history = saved.history.clone();
+            data.history_index = None;
+            return true;
+        }
+    }
+    false
+}
+
+// Results are printed to the developer console, since saving and loading also happen without it.
+fn report(
+    console_writer: &mut EventWriter<PrintConsoleEvent>,
+    dev_console_query: &Query<(Entity, &Children), With<DevConsole>>,
+    result: Result<String, String>,
+) {
+    let message = match result {
+        Ok(message) => {
+            info!("{}", message);
+            message
+        }
+        Err(message) => {
+            error!("{}", message);
+            message
+        }
+    };
+
+    if let Ok((dev_console, _)) = dev_console_query.get_single() {
+        console_writer.send(PrintConsoleEvent {
+            npc: dev_console,
+            message,
+        });
+    }
+}
+
+// Quick save writes slot 0 and quick load reads it while playing.
+pub fn quick_save_and_load(
+    action_input: Res<ActionInput>,
+    app_state: Res<State<AppState>>,
+    mut save_writer: EventWriter<SaveGameEvent>,
+    mut load_writer: EventWriter<LoadGameEvent>,
+) {
+    if app_state.0 != AppState::MainGame {
+        return;
+    }
+    if action_input.just_pressed(Action::QuickSave) {
+        save_writer.send(SaveGameEvent { slot: 0 });
+    }
+    if action_input.just_pressed(Action::QuickLoad) {
+        load_writer.send(LoadGameEvent { slot: 0 });
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn save_game(
+    mut save_events: EventReader<SaveGameEvent>,
+    level_selection: Res<LevelSelection>,
+    clock: Res<GameClock>,
+    item_dictionary: Res<ItemDictionary>,
+    unit_query: Query<
+        (
+            &EntityInstance,
+            &Name,
+            &Transform,
+            &Facing,
+            &Coordinate,
+            Option<&Inventory>,
+            Option<&Children>,
+            Option<(&MemoryStream, &Relationships, &DailyPlan)>,
+            Option<&Appearance>,
+        ),
+        Or<(With<Player>, With<NPC>)>,
+    >,
+    soil_query: Query<&Soil>,
+    crop_query: Query<&Crop>,
+    console_query: Query<&ConsoleData>,
+    dev_console_query: Query<(Entity, &Children), With<DevConsole>>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    for SaveGameEvent { slot } in save_events.iter() {
+        let units = unit_query
+            .iter()
+            // Units spawned outside of LDtk, e.g. by the developer console, can't be found again after loading.
+            .filter(|(entity_instance, ..)| !entity_instance.iid.is_empty())
+            .map(
+                |(
+                    entity_instance,
+                    name,
+                    transform,
+                    facing,
+                    coordinate,
+                    inventory,
+                    children,
+                    mind,
+                    appearance,
+                )| {
+                    SavedUnit {
+                        iid: entity_instance.iid.clone(),
+                        name: name.to_string(),
+                        translation: transform.translation.to_array(),
+                        facing: facing.direction.clone(),
+                        coordinate: coordinate.clone(),
+                        inventory: inventory.map(|inventory| {
+                            inventory
+                                .items
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, item)| item.entity != Entity::from_raw(0))
+                                .filter_map(|(index, item)| {
+                                    Some(SavedItem {
+                                        index,
+                                        name: item_dictionary
+                                            .get(item.item_info_index)?
+                                            .name
+                                            .clone(),
+                                        count: item.count,
+                                    })
+                                })
+                                .collect()
+                        }),
+                        console: children
+                            .and_then(|children| saved_console(children, &console_query)),
+                        mind: mind.map(|(memory, relationships, plan)| SavedMind {
+                            memories: memory.memories.clone(),
+                            relationships: relationships.0.clone(),
+                            plan: plan.0.clone(),
+                        }),
+                        appearance: appearance.cloned(),
+                    }
+                },
+            )
+            .collect();
+
+        let data = SaveData {
+            version: SAVE_VERSION,
+            level: SavedLevel::from(level_selection.as_ref()),
+            clock: SavedClock {
+                day: clock.day,
+                minutes: clock.minutes,
+                minutes_per_second: clock.minutes_per_second,
+            },
+            units,
+            dev_console: dev_console_query
+                .get_single()
+                .ok()
+                .and_then(|(_, children)| saved_console(children, &console_query)),
+            farm: soil_query
+                .iter()
+                .map(|soil| SavedSoil {
+                    cell: soil.cell,
+                    watered: soil.watered,
+                    crop: crop_query
+                        .iter()
+                        .find(|crop| crop.cell == soil.cell)
+                        .map(|crop| SavedCrop {
+                            kind: crop.kind().name.to_string(),
+                            days_grown: crop.days_grown,
+                        }),
+                })
+                .collect(),
+        };
+
+        let result = write_save(*slot, &data)
+            .map(|path| format!("Saved slot {} to {}", slot, path.display()));
+        report(&mut console_writer, &dev_console_query, result);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn load_game(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadGameEvent>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut clock: ResMut<GameClock>,
+    crop_atlas: Res<CropAtlas>,
+    mut entity_map: ResMut<EntityGridMap>,
+    soil_query: Query<Entity, With<Soil>>,
+    crop_query: Query<(Entity, &Coordinate), With<Crop>>,
+    mut console_query: Query<&mut ConsoleData>,
+    dev_console_query: Query<(Entity, &Children), With<DevConsole>>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    for LoadGameEvent { slot } in load_events.iter() {
+        let data = match read_save(*slot) {
+            Ok(data) => data,
+            Err(err) => {
+                report(&mut console_writer, &dev_console_query, Err(err));
+                continue;
+            }
+        };
+
+        *level_selection = data.level.into();
+        clock.day = data.clock.day;
+        clock.minutes = data.clock.minutes;
+        clock.minutes_per_second = data.clock.minutes_per_second;
+
+        // Soil and crops aren't LDtk entities, so they are respawned instead of restored.
+        for soil in soil_query.iter() {
+            commands.entity(soil).despawn_recursive();
+        }
+        for (crop, coordinate) in crop_query.iter() {
+            despawn_crop(&mut commands, &mut entity_map, crop, coordinate);
+        }
+        for saved_soil in data.farm.iter() {
+            spawn_soil(&mut commands, saved_soil.cell, saved_soil.watered);
+            let Some(saved_crop) = &saved_soil.crop else {
+                continue;
+            };
+            if let Some(kind) = crop_kind(&saved_crop.kind) {
+                let crop = Crop {
+                    cell: saved_soil.cell,
+                    kind,
+                    days_grown: saved_crop.days_grown,
+                };
+                spawn_crop(&mut commands, &crop_atlas, crop);
+            }
+        }
+
+        if let (Some(saved), Ok((_, children))) =
+            (&data.dev_console, dev_console_query.get_single())
+        {
+            restore_console(Some(children), &mut console_query, saved);
+        }
+
+        // Units are restored by `apply_pending_load` once the level has spawned them.
+        commands.insert_resource(PendingLoad {
+            units: data.units,
+            timer: Timer::from_seconds(LOAD_TIMEOUT_SECONDS, TimerMode::Once),
+        });
+        report(
+            &mut console_writer,
+            &dev_console_query,
+            Ok(format!("Loaded slot {}", slot)),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn apply_pending_load(
+    mut commands: Commands,
+    pending: Option<ResMut<PendingLoad>>,
+    time: Res<Time>,
+    item_dictionary: Res<ItemDictionary>,
+    mut entity_map: ResMut<EntityGridMap>,
+    mut unit_query: Query<(
+        Entity,
+        &EntityInstance,
+        &mut Transform,
+        &mut Facing,
+        &mut Coordinate,
+        Option<&mut Inventory>,
+        Option<&Children>,
+        Option<(&mut MemoryStream, &mut Relationships, &mut DailyPlan)>,
+        Option<&mut Appearance>,
+    )>,
+    mut console_query: Query<&mut ConsoleData>,
+    dev_console_query: Query<(Entity, &Children), With<DevConsole>>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Some(mut pending) = pending else {
+        return;
+    };
+
+    pending.units.retain(|saved| {
+        let Some((
+            entity,
+            _,
+            mut transform,
+            mut facing,
+            mut coordinate,
+            inventory,
+            children,
+            mind,
+            appearance,
+        )) = unit_query
+            .iter_mut()
+            .find(|(_, entity_instance, ..)| entity_instance.iid == saved.iid)
+        else {
+            return true;
+        };
+
+        // `Appearance` is inserted a frame after its unit too.
+        if let Some(saved_appearance) = &saved.appearance {
+            let Some(mut appearance) = appearance else {
+                return true;
+            };
+            if *appearance != *saved_appearance {
+                *appearance = saved_appearance.clone();
+            }
+        }
+
+        // `ConsoleData` is spawned a frame after its NPC, so wait for it.
+        if let Some(saved_console) = &saved.console {
+            if !restore_console(children, &mut console_query, saved_console) {
+                return true;
+            }
+        }
+
+        transform.translation = Vec3::from_array(saved.translation);
+        facing.direction = saved.facing.clone();
+
+        if let (Some((mut memory, mut relationships, mut plan)), Some(saved_mind)) =
+            (mind, &saved.mind)
+        {
+            memory.memories = saved_mind.memories.clone();
+            memory.unreflected_importance = 0.;
+            memory.last_reflection = None;
+            relationships.0 = saved_mind.relationships.clone();
+            plan.0 = saved_mind.plan.clone();
+        }
+
+        // Move the entity in `EntityGridMap` too, since it is only updated by the difference of coordinates.
+        for x in coordinate.min_x..=coordinate.max_x {
+            for y in coordinate.min_y..=coordinate.max_y {
+                entity_map.delete((x, y), entity);
+            }
+        }
+        *coordinate = saved.coordinate.clone();
+        for x in coordinate.min_x..=coordinate.max_x {
+            for y in coordinate.min_y..=coordinate.max_y {
+                entity_map.insert((x, y), entity);
+            }
+        }
+
+        if let (Some(mut inventory), Some(saved_items)) = (inventory, &saved.inventory) {
+            for item in inventory.items.iter_mut() {
+                if item.entity != Entity::from_raw(0) {
+                    if let Some(mut item_entity) = commands.get_entity(item.entity) {
+                        item_entity.despawn();
+                    }
+                    *item = Item::default();
+                }
+            }
+            for saved_item in saved_items.iter() {
+                let Some(slot) = inventory.items.get_mut(saved_item.index) else {
+                    continue;
+                };
+                let Some(item_info_index) = item_dictionary.find(&saved_item.name) else {
+                    error!(
+                        "Unknown item {} in the inventory of {}",
+                        saved_item.name, saved.name
+                    );
+                    continue;
+                };
+                let item_entity = commands.spawn_empty().id();
+                *slot = Item {
+                    location: Location::Inventory,
+                    item_info_index,
+                    count: saved_item.count,
+                    entity: item_entity,
+                };
+                commands.entity(item_entity).insert(*slot);
+            }
+        }
+
+        false
+    });
+
+    if pending.units.is_empty() {
+        commands.remove_resource::<PendingLoad>();
+        return;
+    }
+
+    if pending.timer.tick(time.delta()).finished() {
+        let missing: Vec<&str> = pending
+            .units
+            .iter()
+            .map(|saved| saved.name.as_str())
+            .collect();
+        report(
+            &mut console_writer,
+            &dev_console_query,
+            Err(format!("Couldn't find saved units: {}", missing.join(", "))),
+        );
+        commands.remove_resource::<PendingLoad>();
+    }
+}
+
+fn slot_arg(input: &ConsoleCommandInput) -> Result<u32, String> {
+    let slot = input.args.int("slot").unwrap_or(0);
+    if (0..SAVE_SLOTS as i64).contains(&slot) {
+        Ok(slot as u32)
+    } else {
+        Err(format!("slot should be between 0 and {}", SAVE_SLOTS - 1))
+    }
+}
+
+pub fn save_command(
+    In(input): In<ConsoleCommandInput>,
+    mut save_writer: EventWriter<SaveGameEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    match slot_arg(&input) {
+        Ok(slot) => save_writer.send(SaveGameEvent { slot }),
+        Err(message) => console_writer.send(PrintConsoleEvent {
+            npc: input.console,
+            message,
+        }),
+    }
+}
+
+pub fn load_command(
+    In(input): In<ConsoleCommandInput>,
+    mut load_writer: EventWriter<LoadGameEvent>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    match slot_arg(&input) {
+        Ok(slot) => load_writer.send(LoadGameEvent { slot }),
+        Err(message) => console_writer.send(PrintConsoleEvent {
+            npc: input.console,
+            message,
+        }),
+    }
+}
@@ -0,0 +1,448 @@
+use bevy::{
+    prelude::{
+        warn, Added, AssetServer, Assets, Changed, Children, Color, Commands, Component,
+        DespawnRecursiveExt, Entity, Handle, Or, Query, Res, ResMut, Resource, With, Without,
+    },
+    reflect::Reflect,
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+};
+use bevy_ecs_ldtk::{prelude::LdtkFields, EntityInstance};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use super::{
+    spawn_sprite_layer, BodyAtlases, LayerAtlases, SheetKind, SpriteLayer, PLAYER_BODY_PATH,
+    TOOLS_PATH,
+};
+use crate::units::{Player, NPC};
+
+// Styles characters can wear are read from here at startup.
+pub const APPEARANCE_CONFIG_PATH: &str = "config/appearance.ron";
+
+// A sheet that can be worn. It has a sheet for each body layout, so the player and NPCs can both wear it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayerStyle {
+    pub name: String,
+    // Laid out like `player_base.png`. Styles without it aren't offered to the player.
+    #[serde(default)]
+    pub player: Option<String>,
+    // Laid out like `npc_1.png`. Styles without it aren't given to NPCs.
+    #[serde(default)]
+    pub npc: Option<String>,
+}
+
+impl LayerStyle {
+    fn sheet(&self, player: bool) -> Option<&str> {
+        if player {
+            self.player.as_deref()
+        } else {
+            self.npc.as_deref()
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceCatalog {
+    // Bodies replacing the default body sheets. Arms of the player are read from the same sheet.
+    pub bodies: Vec<LayerStyle>,
+    pub hair: Vec<LayerStyle>,
+    pub clothes: Vec<LayerStyle>,
+    // Tools replacing `tools.png`. Only the player holds tools, so only `player` sheets are used.
+    pub tools: Vec<LayerStyle>,
+    // Colors multiplied with the body and arms, and with hair. Sheets of hair are drawn light so any color shows.
+    pub skin_tones: Vec<[f32; 3]>,
+    pub hair_colors: Vec<[f32; 3]>,
+}
+
+// There are no hair or clothes sheets in the assets yet, so only the colors of the base bodies can be changed by default.
+impl Default for AppearanceCatalog {
+    fn default() -> Self {
+        AppearanceCatalog {
+            bodies: Vec::new(),
+            hair: Vec::new(),
+            clothes: Vec::new(),
+            tools: Vec::new(),
+            skin_tones: vec![
+                [1., 1., 1.],
+                [1., 0.87, 0.76],
+                [0.87, 0.68, 0.52],
+                [0.64, 0.46, 0.33],
+                [0.45, 0.32, 0.24],
+            ],
+            hair_colors: vec![
+                [0.25, 0.18, 0.12],
+                [0.1, 0.1, 0.12],
+                [0.9, 0.76, 0.42],
+                [0.72, 0.32, 0.16],
+                [0.82, 0.82, 0.84],
+            ],
+        }
+    }
+}
+
+impl AppearanceCatalog {
+    // Reads `APPEARANCE_CONFIG_PATH`, or uses the default styles if it is missing or invalid.
+    pub fn load() -> Self {
+        let loaded = fs::read_to_string(APPEARANCE_CONFIG_PATH)
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                ron::from_str::<AppearanceCatalog>(&text).map_err(|err| err.to_string())
+            });
+        loaded.unwrap_or_else(|err| {
+            warn!(
+                "Using the default appearances, failed to load {}: {}",
+                APPEARANCE_CONFIG_PATH, err
+            );
+            AppearanceCatalog::default()
+        })
+    }
+
+    // Whether the player can change `part`. Hair colors only show on hair, so they need a hair style too.
+    pub fn offers(&self, part: AppearancePart) -> bool {
+        match part {
+            AppearancePart::SkinTone => !self.skin_tones.is_empty(),
+            AppearancePart::HairColor => {
+                !self.hair_colors.is_empty() && self.offers(AppearancePart::Hair)
+            }
+            _ => self.styles(part).iter().any(|style| style.player.is_some()),
+        }
+    }
+
+    fn styles(&self, part: AppearancePart) -> &[LayerStyle] {
+        match part {
+            AppearancePart::Body => &self.bodies,
+            AppearancePart::Hair => &self.hair,
+            AppearancePart::Clothes => &self.clothes,
+            AppearancePart::Tool => &self.tools,
+            AppearancePart::SkinTone | AppearancePart::HairColor => &[],
+        }
+    }
+
+    fn colors(&self, part: AppearancePart) -> &[[f32; 3]] {
+        match part {
+            AppearancePart::SkinTone => &self.skin_tones,
+            AppearancePart::HairColor => &self.hair_colors,
+            _ => &[],
+        }
+    }
+
+    // Sheet of the style named `name` for the layout of the character. `None` if it has none, or no style is worn.
+    fn sheet(&self, part: AppearancePart, name: &Option<String>, player: bool) -> Option<&str> {
+        let name = name.as_ref()?;
+        let style = self.styles(part).iter().find(|style| &style.name == name);
+        if style.is_none() {
+            warn!("Unknown {} style: {}", part.label(), name);
+        }
+        style?.sheet(player)
+    }
+
+    fn color(&self, part: AppearancePart, index: usize) -> Color {
+        let colors = self.colors(part);
+        if colors.is_empty() {
+            return Color::WHITE;
+        }
+        let [r, g, b] = colors[index % colors.len()];
+        Color::rgb(r, g, b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppearancePart {
+    Body,
+    SkinTone,
+    Hair,
+    HairColor,
+    Clothes,
+    Tool,
+}
+
+impl AppearancePart {
+    pub const ALL: [AppearancePart; 6] = [
+        AppearancePart::Body,
+        AppearancePart::SkinTone,
+        AppearancePart::Hair,
+        AppearancePart::HairColor,
+        AppearancePart::Clothes,
+        AppearancePart::Tool,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AppearancePart::Body => "Body",
+            AppearancePart::SkinTone => "Skin tone",
+            AppearancePart::Hair => "Hair",
+            AppearancePart::HairColor => "Hair color",
+            AppearancePart::Clothes => "Clothes",
+            AppearancePart::Tool => "Tools",
+        }
+    }
+
+    // Name of the LDtk field setting the part.
+    fn field(&self) -> &'static str {
+        match self {
+            AppearancePart::Body => "body",
+            AppearancePart::SkinTone => "skin_tone",
+            AppearancePart::Hair => "hair",
+            AppearancePart::HairColor => "hair_color",
+            AppearancePart::Clothes => "clothes",
+            AppearancePart::Tool => "tool",
+        }
+    }
+
+    // Shown when no style is worn. Bodies and tools fall back to the default sheets.
+    fn none_label(&self) -> &'static str {
+        match self {
+            AppearancePart::Body | AppearancePart::Tool => "Default",
+            _ => "None",
+        }
+    }
+}
+
+// Layers a character is drawn with, by the names of their styles in `AppearanceCatalog`.
+// Colors are indices into the colors of the catalog, so they survive edits of the catalog.
+#[derive(Component, Clone, Debug, Default, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Appearance {
+    pub body: Option<String>,
+    pub skin_tone: usize,
+    pub hair: Option<String>,
+    pub hair_color: usize,
+    pub clothes: Option<String>,
+    pub tool: Option<String>,
+}
+
+impl Appearance {
+    fn style(&self, part: AppearancePart) -> Option<&Option<String>> {
+        match part {
+            AppearancePart::Body => Some(&self.body),
+            AppearancePart::Hair => Some(&self.hair),
+            AppearancePart::Clothes => Some(&self.clothes),
+            AppearancePart::Tool => Some(&self.tool),
+            AppearancePart::SkinTone | AppearancePart::HairColor => None,
+        }
+    }
+
+    fn color(&self, part: AppearancePart) -> Option<usize> {
+        match part {
+            AppearancePart::SkinTone => Some(self.skin_tone),
+            AppearancePart::HairColor => Some(self.hair_color),
+            _ => None,
+        }
+    }
+
+    fn style_mut(&mut self, part: AppearancePart) -> Option<&mut Option<String>> {
+        match part {
+            AppearancePart::Body => Some(&mut self.body),
+            AppearancePart::Hair => Some(&mut self.hair),
+            AppearancePart::Clothes => Some(&mut self.clothes),
+            AppearancePart::Tool => Some(&mut self.tool),
+            AppearancePart::SkinTone | AppearancePart::HairColor => None,
+        }
+    }
+
+    fn color_mut(&mut self, part: AppearancePart) -> Option<&mut usize> {
+        match part {
+            AppearancePart::SkinTone => Some(&mut self.skin_tone),
+            AppearancePart::HairColor => Some(&mut self.hair_color),
+            _ => None,
+        }
+    }
+
+    // Wears the next style of the player, or none after the last one. Colors wrap around.
+    pub fn cycle(&mut self, part: AppearancePart, catalog: &AppearanceCatalog) {
+        let colors = catalog.colors(part).len();
+        if let Some(color) = self.color_mut(part) {
+            if colors > 0 {
+                *color = (*color + 1) % colors;
+            }
+            return;
+        }
+        let names: Vec<&String> = catalog
+            .styles(part)
+            .iter()
+            .filter(|style| style.player.is_some())
+            .map(|style| &style.name)
+            .collect();
+        if let Some(style) = self.style_mut(part) {
+            let next = match style {
+                Some(name) => names
+                    .iter()
+                    .position(|&other| other == name)
+                    .and_then(|index| names.get(index + 1)),
+                None => names.first(),
+            };
+            *style = next.map(|&name| name.clone());
+        }
+    }
+
+    // Label of a button of the character creation screen, like `Hair: Short`.
+    pub fn describe(&self, part: AppearancePart, catalog: &AppearanceCatalog) -> String {
+        let colors = catalog.colors(part).len().max(1);
+        let value = match (self.color(part), self.style(part)) {
+            (Some(color), _) => format!("{}/{}", color % colors + 1, colors),
+            (_, Some(Some(name))) => name.clone(),
+            _ => part.none_label().to_string(),
+        };
+        format!("{}: {}", part.label(), value)
+    }
+
+    // Fields of an LDtk entity: strings `body`, `hair`, `clothes` and `tool`, and ints `skin_tone` and `hair_color`.
+    // Missing and null fields are picked at random when `randomize` is set, and left at the default otherwise.
+    pub fn from_ldtk_fields(
+        entity_instance: &EntityInstance,
+        catalog: &AppearanceCatalog,
+        randomize: bool,
+    ) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut appearance = Appearance::default();
+        for part in AppearancePart::ALL {
+            let field = part.field();
+            if let Some(color) = appearance.color_mut(part) {
+                let colors = catalog.colors(part).len();
+                *color = match entity_instance.get_maybe_int_field(field) {
+                    Ok(Some(index)) => (*index).max(0) as usize,
+                    _ if randomize && colors > 0 => rng.gen_range(0..colors),
+                    _ => 0,
+                };
+            } else if let Some(style) = appearance.style_mut(part) {
+                *style = match entity_instance.get_maybe_string_field(field) {
+                    Ok(Some(name)) => Some(name.clone()),
+                    _ if randomize => {
+                        // Wearing nothing is as likely as any style.
+                        let names: Vec<Option<&String>> = catalog
+                            .styles(part)
+                            .iter()
+                            .filter(|style| style.npc.is_some())
+                            .map(|style| Some(&style.name))
+                            .chain([None])
+                            .collect();
+                        names.choose(&mut rng).copied().flatten().cloned()
+                    }
+                    _ => None,
+                };
+            }
+        }
+        appearance
+    }
+}
+
+// The player keeps the appearance of their LDtk entity until they change it, and NPCs fill missing fields at random.
+#[allow(clippy::type_complexity)]
+pub fn insert_appearances(
+    mut commands: Commands,
+    catalog: Res<AppearanceCatalog>,
+    query: Query<
+        (Entity, &EntityInstance, Option<&Player>),
+        (Or<(Added<Player>, Added<NPC>)>, Without<Appearance>),
+    >,
+) {
+    for (entity, entity_instance, player) in query.iter() {
+        let appearance = Appearance::from_ldtk_fields(entity_instance, &catalog, player.is_none());
+        commands.entity(entity).insert(appearance);
+    }
+}
+
+// Swaps the sheets and colors of layers when an appearance changes. Hair and clothes are spawned again.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn apply_appearance(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    catalog: Res<AppearanceCatalog>,
+    body_atlases: Res<BodyAtlases>,
+    mut layer_atlases: ResMut<LayerAtlases>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    character_query: Query<
+        (Entity, &Appearance, &Children, Option<&Player>),
+        (Or<(With<Player>, With<NPC>)>, Changed<Appearance>),
+    >,
+    mut layer_query: Query<(
+        Entity,
+        &SpriteLayer,
+        &mut Handle<TextureAtlas>,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (character, appearance, children, player) in character_query.iter() {
+        let player = player.is_some();
+        let mut atlas = |path: &str, kind: SheetKind| {
+            layer_atlases.get(path, kind, &asset_server, &mut texture_atlases)
+        };
+        let body_sheet = catalog.sheet(AppearancePart::Body, &appearance.body, player);
+        let body_atlas = match (body_sheet, player) {
+            (Some(path), true) => atlas(path, SheetKind::PlayerBody),
+            (Some(path), false) => atlas(path, SheetKind::NpcBody),
+            (None, true) => body_atlases.player.clone(),
+            (None, false) => body_atlases.npc.clone(),
+        };
+        let arm_atlas = atlas(body_sheet.unwrap_or(PLAYER_BODY_PATH), SheetKind::PlayerArm);
+        let tool_sheet = catalog.sheet(AppearancePart::Tool, &appearance.tool, true);
+        let tool_atlas = atlas(tool_sheet.unwrap_or(TOOLS_PATH), SheetKind::Tool);
+        let skin_tone = catalog.color(AppearancePart::SkinTone, appearance.skin_tone);
+
+        let mut frame = (0, false);
+        let mut layers = layer_query.iter_many_mut(children);
+        while let Some((entity, layer, mut texture_atlas, mut sprite)) = layers.fetch_next() {
+            match layer {
+                SpriteLayer::Body => {
+                    *texture_atlas = body_atlas.clone();
+                    sprite.color = skin_tone;
+                    frame = (sprite.index, sprite.flip_x);
+                }
+                SpriteLayer::Arm => {
+                    *texture_atlas = arm_atlas.clone();
+                    sprite.color = skin_tone;
+                }
+                SpriteLayer::Weapon => *texture_atlas = tool_atlas.clone(),
+                SpriteLayer::Hair | SpriteLayer::Clothes => {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+
+        let kind = if player {
+            SheetKind::PlayerBody
+        } else {
+            SheetKind::NpcBody
+        };
+        let worn = [
+            (
+                SpriteLayer::Clothes,
+                AppearancePart::Clothes,
+                &appearance.clothes,
+                Color::WHITE,
+            ),
+            (
+                SpriteLayer::Hair,
+                AppearancePart::Hair,
+                &appearance.hair,
+                catalog.color(AppearancePart::HairColor, appearance.hair_color),
+            ),
+        ];
+        for (layer, part, name, color) in worn {
+            let Some(path) = catalog.sheet(part, name, player) else {
+                continue;
+            };
+            let (index, flip_x) = frame;
+            let texture_atlas = atlas(path, kind);
+            let entity = spawn_sprite_layer(
+                &mut commands,
+                character,
+                layer,
+                SpriteSheetBundle {
+                    texture_atlas,
+                    sprite: TextureAtlasSprite {
+                        index,
+                        flip_x,
+                        color,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            );
+            // Worn layers play the clips of the body, so they show the same frames.
+            commands.entity(entity).insert(kind);
+        }
+    }
+}
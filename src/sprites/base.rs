@@ -1,11 +1,9 @@
 use bevy::{
-    prelude::{Bundle, Component, Deref, DerefMut, Query, Res, Transform, With},
+    prelude::{Bundle, Component, Deref, DerefMut, Vec2},
     reflect::Reflect,
-    sprite::TextureAtlasSprite,
-    time::{Time, Timer, TimerMode},
+    time::{Timer, TimerMode},
 };
-
-use crate::units::{Player, NPC};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Default, Bundle)]
 pub struct AnimationBundle {
@@ -14,12 +12,33 @@ pub struct AnimationBundle {
     pub animation_timer: AnimationTimer,
 }
 
+// Frame of the clip of `animation_state` that layers of a character show. Clips are played by `play_animation_clips`.
 #[derive(Component, Default, Clone, Reflect)]
 pub struct AnimationIndices {
-    pub first: usize,
-    pub last: usize,
     pub current: usize,
     pub animation_state: AnimationState,
+    // State whose clip is shown. The clip of `animation_state` starts from its first frame when they differ.
+    playing: AnimationState,
+    restart: bool,
+}
+
+impl AnimationIndices {
+    // Plays the clip of `state` from its first frame, even if it is already playing, like repeated attacks.
+    pub fn restart(&mut self, state: AnimationState) {
+        self.animation_state = state;
+        self.restart = true;
+    }
+
+    // Moves to the first frame if the state changed or a restart was asked for. Returns whether it did.
+    pub fn start_clip(&mut self) -> bool {
+        if !self.restart && self.playing == self.animation_state {
+            return false;
+        }
+        self.playing = self.animation_state.clone();
+        self.restart = false;
+        self.current = 0;
+        true
+    }
 }
 
 #[derive(Component, Default, Clone, PartialEq, Eq, Reflect)]
@@ -28,16 +47,30 @@ pub enum AnimationState {
     Idle,
     Walk,
     Attack,
+    Roll,
     #[allow(dead_code)]
     BeHit,
 }
 
+impl AnimationState {
+    // Name of the clip played in this state, in `ClipSet`s.
+    pub fn clip_name(&self) -> &'static str {
+        match self {
+            AnimationState::Idle => "idle",
+            AnimationState::Walk => "walk",
+            AnimationState::Attack => "attack",
+            AnimationState::Roll => "roll",
+            AnimationState::BeHit => "be_hit",
+        }
+    }
+}
+
 #[derive(Component, Default, Clone, Reflect, Debug)]
 pub struct Facing {
     pub direction: FaceDirection,
 }
 
-#[derive(Component, Default, Clone, PartialEq, Eq, Reflect, Debug)]
+#[derive(Component, Default, Clone, PartialEq, Eq, Reflect, Debug, Serialize, Deserialize)]
 pub enum FaceDirection {
     Up,
     #[default]
@@ -46,6 +79,33 @@ pub enum FaceDirection {
     Right,
 }
 
+impl FaceDirection {
+    // Direction of the larger axis, so diagonal and analog vectors face the way they mostly go.
+    pub fn from_vector(vector: Vec2) -> Self {
+        if vector.x.abs() > vector.y.abs() {
+            if vector.x > 0. {
+                FaceDirection::Right
+            } else {
+                FaceDirection::Left
+            }
+        } else if vector.y > 0. {
+            FaceDirection::Up
+        } else {
+            FaceDirection::Down
+        }
+    }
+
+    pub fn to_vector(&self) -> Vec2 {
+        match self {
+            FaceDirection::Up => Vec2::Y,
+            FaceDirection::Down => Vec2::NEG_Y,
+            FaceDirection::Left => Vec2::NEG_X,
+            FaceDirection::Right => Vec2::X,
+        }
+    }
+}
+
+// Time on the current frame of a clip. Its duration is set to the duration of each frame.
 #[derive(Component, Deref, DerefMut, Clone)]
 pub struct AnimationTimer(pub Timer);
 
@@ -55,142 +115,8 @@ impl Default for AnimationTimer {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, Component)]
-pub struct Body;
-
-#[derive(Component, Default, Clone, Debug)]
-pub struct YSort {
-    pub z: f32,
-}
-
 // pub fn sprite_size(mut query: Query<&mut TextureAtlasSprite, Or<(With<Player>, With<NPC>)>>) {
 //     for mut sprite in &mut query {
 //         sprite.custom_size = Some(Vec2::new(24., 24.));
 //     }
 // }
-
-pub fn animate_player_sprite(
-    time: Res<Time>,
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &Facing,
-            &mut AnimationIndices,
-        ),
-        With<Player>,
-    >,
-) {
-    for (mut timer, mut sprite, facing, mut indices) in &mut query {
-        timer.tick(time.delta());
-        if timer.just_finished() {
-            // Facing
-            sprite.flip_x = facing.direction == FaceDirection::Left;
-
-            // Reset to first frame if idle
-            if indices.animation_state == AnimationState::Idle {
-                sprite.index = match facing.direction {
-                    FaceDirection::Down => 0,
-                    FaceDirection::Left | FaceDirection::Right => 6,
-                    FaceDirection::Up => 12,
-                };
-                indices.current = 0;
-                break;
-            }
-
-            // Move to next frame
-            indices.current = if indices.current == indices.last {
-                indices.first
-            } else {
-                indices.current + 1
-            };
-
-            let mut temp_index = indices.current;
-
-            // Walking animation
-            if indices.animation_state == AnimationState::Walk {
-                if indices.current == 2 {
-                    temp_index = 0;
-                } else if indices.current == 3 {
-                    temp_index = 2;
-                }
-
-                sprite.index = match facing.direction {
-                    FaceDirection::Down => temp_index,
-                    FaceDirection::Left | FaceDirection::Right => 6 + temp_index,
-                    FaceDirection::Up => 12 + temp_index,
-                };
-            }
-
-            // Attack animation
-            if indices.animation_state == AnimationState::Attack {
-                sprite.index = match facing.direction {
-                    FaceDirection::Down => 66 + temp_index,
-                    FaceDirection::Left | FaceDirection::Right => 48 + temp_index,
-                    FaceDirection::Up => {
-                        if temp_index == 3 {
-                            63
-                        } else if temp_index == 4 {
-                            62
-                        } else {
-                            36 + temp_index
-                        }
-                    }
-                };
-            }
-        }
-    }
-}
-
-pub fn animate_npc_sprite(
-    time: Res<Time>,
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &Facing,
-            &mut AnimationIndices,
-        ),
-        With<NPC>,
-    >,
-) {
-    for (mut timer, mut sprite, facing, mut indices) in &mut query {
-        timer.tick(time.delta());
-        if timer.just_finished() {
-            // Reset to first frame if idle
-            if indices.animation_state == AnimationState::Idle {
-                sprite.index = match facing.direction {
-                    FaceDirection::Down => 0,
-                    FaceDirection::Right => 4,
-                    FaceDirection::Up => 8,
-                    FaceDirection::Left => 12,
-                };
-                indices.current = 0;
-                break;
-            }
-
-            // Move to next frame
-            indices.current = if indices.current == indices.last {
-                indices.first
-            } else {
-                indices.current + 1
-            };
-
-            // Walking animation
-            if indices.animation_state == AnimationState::Walk {
-                sprite.index = match facing.direction {
-                    FaceDirection::Down => indices.current,
-                    FaceDirection::Right => 4 + indices.current,
-                    FaceDirection::Up => 8 + indices.current,
-                    FaceDirection::Left => 12 + indices.current,
-                };
-            }
-        }
-    }
-}
-
-pub fn y_sort(mut q: Query<(&mut Transform, &YSort)>) {
-    for (mut tf, ysort) in q.iter_mut() {
-        tf.translation.z = ysort.z - (1.0f32 / (1.0f32 + (2.0f32.powf(-0.01 * tf.translation.y))));
-    }
-}
@@ -0,0 +1,274 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::{
+        AssetServer, Assets, Children, Commands, Entity, EventWriter, Handle, Quat, Query, Res,
+        Resource, Transform, Visibility, With,
+    },
+    reflect::TypeUuid,
+    sprite::{Anchor, TextureAtlasSprite},
+    time::Time,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+use super::{
+    AnimationIndices, AnimationState, AnimationTimer, Body, FaceDirection, Facing, SheetKind,
+};
+
+// Events sent by frames of the default clips.
+pub const HIT_EVENT: &str = "hit";
+pub const FOOTSTEP_EVENT: &str = "footstep";
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+// Sent when a layer of a character shows a frame with events, like the frame of a swing that hits.
+#[derive(Debug)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+// Where a held sprite, like a tool, is attached to the hand for a frame.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attachment {
+    // Point of the sprite at the hand, from -0.5 to 0.5 of its size, like `Anchor::Custom`.
+    pub anchor: (f32, f32),
+    // Radians around the hand, counterclockwise.
+    pub rotation: f32,
+    // Radians around the horizontal axis, which shortens the sprite like it points away from the camera.
+    pub tilt: f32,
+}
+
+impl Attachment {
+    fn mirrored(&self) -> Self {
+        Attachment {
+            anchor: (-self.anchor.0, self.anchor.1),
+            rotation: -self.rotation,
+            tilt: self.tilt,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipFrame {
+    // Index in the atlas of the layer.
+    pub index: usize,
+    // Seconds, overriding `Clip::frame_duration`.
+    pub duration: Option<f32>,
+    pub events: Vec<String>,
+    pub attachment: Option<Attachment>,
+}
+
+// Frames of a state for each direction. Characters facing left show the right frames mirrored when `left` is empty.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Clip {
+    pub frame_duration: f32,
+    // Clips that don't loop hold their last frame until the state changes.
+    pub looping: bool,
+    pub down: Vec<ClipFrame>,
+    pub up: Vec<ClipFrame>,
+    pub left: Vec<ClipFrame>,
+    pub right: Vec<ClipFrame>,
+}
+
+impl Default for Clip {
+    fn default() -> Self {
+        Clip {
+            frame_duration: DEFAULT_FRAME_DURATION,
+            looping: true,
+            down: Vec::new(),
+            up: Vec::new(),
+            left: Vec::new(),
+            right: Vec::new(),
+        }
+    }
+}
+
+impl Clip {
+    // Frames facing `direction`, and whether they are mirrored.
+    fn frames(&self, direction: &FaceDirection) -> (&[ClipFrame], bool) {
+        match direction {
+            FaceDirection::Down => (&self.down, false),
+            FaceDirection::Up => (&self.up, false),
+            FaceDirection::Right => (&self.right, false),
+            FaceDirection::Left if self.left.is_empty() => (&self.right, true),
+            FaceDirection::Left => (&self.left, false),
+        }
+    }
+
+    fn duration(&self, frame: &ClipFrame) -> Duration {
+        Duration::from_secs_f32(frame.duration.unwrap_or(self.frame_duration).max(0.001))
+    }
+}
+
+// Clips of the layers drawn from one sheet layout, by the names of states, like `walk`.
+// Read from `.clips.ron` files in `assets/animations`, so clips are added and changed without touching the code.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6b3e2f0c-8d51-4c1e-9a57-2f4d8c0b7e13"]
+pub struct ClipSet {
+    pub clips: BTreeMap<String, Clip>,
+}
+
+impl ClipSet {
+    // Layers without a clip for a state show their idle clip, and are hidden without either.
+    fn clip(&self, state: &AnimationState) -> Option<&Clip> {
+        self.clips
+            .get(state.clip_name())
+            .or_else(|| self.clips.get(AnimationState::Idle.clip_name()))
+    }
+}
+
+#[derive(Default)]
+pub struct ClipSetLoader;
+
+impl AssetLoader for ClipSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let clip_set: ClipSet = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(clip_set));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["clips.ron"]
+    }
+}
+
+// Clips of each sheet layout.
+#[derive(Resource, Default)]
+pub struct AnimationClips(HashMap<SheetKind, Handle<ClipSet>>);
+
+pub fn load_animation_clips(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let kinds = [
+        SheetKind::PlayerBody,
+        SheetKind::PlayerArm,
+        SheetKind::NpcBody,
+        SheetKind::Tool,
+    ];
+    commands.insert_resource(AnimationClips(
+        kinds
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(kind.clips_path())))
+            .collect(),
+    ));
+}
+
+// Advances the clip of each character by the frames of its body, and shows the same frame on every layer.
+// Layers look up the frame in the clips of their own sheet, so arms and tools can use other indices than bodies.
+#[allow(clippy::type_complexity)]
+pub fn play_animation_clips(
+    time: Res<Time>,
+    animation_clips: Res<AnimationClips>,
+    clip_sets: Res<Assets<ClipSet>>,
+    mut character_query: Query<(
+        Entity,
+        &Facing,
+        &mut AnimationIndices,
+        &mut AnimationTimer,
+        &Children,
+    )>,
+    body_query: Query<&SheetKind, With<Body>>,
+    mut layer_query: Query<(
+        &SheetKind,
+        &mut TextureAtlasSprite,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+    mut event_writer: EventWriter<AnimationEvent>,
+) {
+    let clip = |kind: &SheetKind, state: &AnimationState| {
+        let clip_set = clip_sets.get(animation_clips.0.get(kind)?)?;
+        clip_set.clip(state)
+    };
+
+    for (entity, facing, mut indices, mut timer, children) in character_query.iter_mut() {
+        // Clips wait for the body and for their files to load.
+        let Some(body_kind) = body_query.iter_many(children).next() else {
+            continue;
+        };
+        let Some(body_clip) = clip(body_kind, &indices.animation_state) else {
+            continue;
+        };
+        let (frames, _) = body_clip.frames(&facing.direction);
+        if frames.is_empty() {
+            continue;
+        }
+
+        timer.tick(time.delta());
+        let entered = if indices.start_clip() {
+            true
+        } else if timer.just_finished() {
+            let next = indices.current + 1;
+            if next < frames.len() {
+                indices.current = next;
+                true
+            } else if body_clip.looping {
+                indices.current = 0;
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+        if entered {
+            let duration = body_clip.duration(&frames[indices.current % frames.len()]);
+            timer.set_duration(duration);
+            timer.reset();
+        }
+
+        let mut layers = layer_query.iter_many_mut(children);
+        while let Some((kind, mut sprite, mut transform, mut visibility)) = layers.fetch_next() {
+            let frame = clip(kind, &indices.animation_state).and_then(|clip| {
+                let (frames, mirrored) = clip.frames(&facing.direction);
+                let frame = frames.get(indices.current % frames.len().max(1))?;
+                Some((frame, mirrored))
+            });
+            let Some((frame, mirrored)) = frame else {
+                if *visibility != Visibility::Hidden {
+                    *visibility = Visibility::Hidden;
+                }
+                continue;
+            };
+
+            if entered {
+                for name in frame.events.iter() {
+                    event_writer.send(AnimationEvent {
+                        entity,
+                        name: name.clone(),
+                    });
+                }
+            }
+
+            if *visibility != Visibility::Inherited {
+                *visibility = Visibility::Inherited;
+            }
+            if sprite.index != frame.index || sprite.flip_x != mirrored {
+                sprite.index = frame.index;
+                sprite.flip_x = mirrored;
+            }
+            let attachment = match (frame.attachment, mirrored) {
+                (Some(attachment), true) => attachment.mirrored(),
+                (Some(attachment), false) => attachment,
+                (None, _) => Attachment::default(),
+            };
+            let anchor = attachment.anchor.into();
+            if sprite.anchor.as_vec() != anchor {
+                sprite.anchor = Anchor::Custom(anchor);
+            }
+            let rotation =
+                Quat::from_rotation_z(attachment.rotation) * Quat::from_rotation_x(attachment.tilt);
+            if transform.rotation != rotation {
+                transform.rotation = rotation;
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::{
+    prelude::{
+        Added, AssetServer, Assets, BuildChildren, Children, Commands, Component, Entity, Handle,
+        Image, Or, Query, Res, ResMut, Resource, Transform, Vec2, With, Without,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    utils::HashMap,
+};
+
+use super::{DepthRange, FaceDirection, Facing};
+use crate::units::{Player, NPC};
+
+// Layers of a character are spread over less than one pixel of depth, so they never interleave with characters one pixel
+// above or below. Weapons can go behind the body, so there are steps on both sides of it.
+// f32 keeps about 1e-6 of precision at the unit band, so the steps stay apart in worlds up to about 100000 pixels high.
+const LAYER_STEPS: f32 = 2. * (SpriteLayer::Weapon as u8 + 1) as f32;
+
+// Child sprite of a character. Layers are drawn in this order, from back to front.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteLayer {
+    Body,
+    Clothes,
+    Hair,
+    Arm,
+    Weapon,
+}
+
+impl SpriteLayer {
+    // Weapons are held in front of the body, so they go behind it when the character faces up.
+    fn z(&self, facing: &FaceDirection, step: f32) -> f32 {
+        let order = *self as u8 as f32;
+        if *self == SpriteLayer::Weapon && *facing == FaceDirection::Up {
+            -order * step
+        } else {
+            order * step
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Component)]
+pub struct Body;
+
+pub const PLAYER_BODY_PATH: &str = "char/player_base.png";
+pub const NPC_BODY_PATH: &str = "char/npc_1.png";
+pub const TOOLS_PATH: &str = "char/tools.png";
+
+// Layouts of character sheets. Layers worn over a body, like hair and clothes, use the layout of the body.
+// Each layer has the layout of its sheet, which picks the clips it plays.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SheetKind {
+    PlayerBody,
+    // Arms are on the right half of player body sheets.
+    PlayerArm,
+    NpcBody,
+    Tool,
+}
+
+impl SheetKind {
+    pub fn atlas(&self, texture: Handle<Image>) -> TextureAtlas {
+        let (tile_size, columns, rows, offset) = match self {
+            SheetKind::PlayerBody => (Vec2::new(16., 32.), 6, 21, None),
+            SheetKind::PlayerArm => (Vec2::new(16., 32.), 12, 21, Some(Vec2::new(96., 0.))),
+            SheetKind::NpcBody => (Vec2::new(16., 32.), 4, 4, None),
+            SheetKind::Tool => (Vec2::new(16., 24.), 6, 6, Some(Vec2::new(0., 24.))),
+        };
+        TextureAtlas::from_grid(texture, tile_size, columns, rows, None, offset)
+    }
+
+    pub fn clips_path(&self) -> &'static str {
+        match self {
+            SheetKind::PlayerBody => "animations/player_body.clips.ron",
+            SheetKind::PlayerArm => "animations/player_arm.clips.ron",
+            SheetKind::NpcBody => "animations/npc_body.clips.ron",
+            SheetKind::Tool => "animations/tool.clips.ron",
+        }
+    }
+}
+
+// Atlases of character sheets by path and layout, so characters wearing the same sheet share it.
+#[derive(Resource, Default)]
+pub struct LayerAtlases(HashMap<(String, SheetKind), Handle<TextureAtlas>>);
+
+impl LayerAtlases {
+    pub fn get(
+        &mut self,
+        path: &str,
+        kind: SheetKind,
+        asset_server: &AssetServer,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Handle<TextureAtlas> {
+        self.0
+            .entry((path.to_string(), kind))
+            .or_insert_with(|| texture_atlases.add(kind.atlas(asset_server.load(path))))
+            .clone()
+    }
+}
+
+// Default body sheets of characters, worn until their appearance is applied.
+#[derive(Resource)]
+pub struct BodyAtlases {
+    pub player: Handle<TextureAtlas>,
+    pub npc: Handle<TextureAtlas>,
+}
+
+pub fn load_body_atlases(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut layer_atlases: ResMut<LayerAtlases>,
+) {
+    let mut atlas = |path: &str, kind: SheetKind| {
+        layer_atlases.get(path, kind, &asset_server, &mut texture_atlases)
+    };
+    commands.insert_resource(BodyAtlases {
+        player: atlas(PLAYER_BODY_PATH, SheetKind::PlayerBody),
+        npc: atlas(NPC_BODY_PATH, SheetKind::NpcBody),
+    });
+}
+
+// Spawns a layer as a child of `character`.
+pub fn spawn_sprite_layer(
+    commands: &mut Commands,
+    character: Entity,
+    layer: SpriteLayer,
+    sprite_sheet_bundle: SpriteSheetBundle,
+) -> Entity {
+    let child = commands.spawn((sprite_sheet_bundle, layer)).id();
+    commands.entity(character).add_child(child);
+    child
+}
+
+#[allow(clippy::type_complexity)]
+pub fn spawn_body_sprite(
+    mut commands: Commands,
+    body_atlases: Res<BodyAtlases>,
+    query: Query<(Entity, Option<&Player>), Or<(Added<Player>, Added<NPC>)>>,
+) {
+    for (entity, player) in query.iter() {
+        let (texture_atlas, kind) = if player.is_some() {
+            (body_atlases.player.clone(), SheetKind::PlayerBody)
+        } else {
+            (body_atlases.npc.clone(), SheetKind::NpcBody)
+        };
+        let body = spawn_sprite_layer(
+            &mut commands,
+            entity,
+            SpriteLayer::Body,
+            SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite::new(0),
+                ..Default::default()
+            },
+        );
+        commands.entity(body).insert((Body, kind));
+    }
+}
+
+// Keeps layers in order inside the depth of their character, which `y_sort` sets.
+pub fn order_sprite_layers(
+    depth_range: Res<DepthRange>,
+    character_query: Query<(&Facing, &Children), Without<SpriteLayer>>,
+    mut layer_query: Query<(&SpriteLayer, &mut Transform), With<TextureAtlasSprite>>,
+) {
+    let step = depth_range.pixel_depth() / LAYER_STEPS;
+    for (facing, children) in character_query.iter() {
+        let mut layers = layer_query.iter_many_mut(children);
+        while let Some((layer, mut transform)) = layers.fetch_next() {
+            let z = layer.z(&facing.direction, step);
+            if transform.translation.z != z {
+                transform.translation.z = z;
+            }
+        }
+    }
+}
@@ -1,13 +1,21 @@
-use bevy::prelude::{App, IntoSystemConfig, IntoSystemConfigs, Plugin};
+use bevy::prelude::{AddAsset, App, IntoSystemConfig, IntoSystemConfigs, Plugin};
 
+pub mod appearance;
 pub mod arm;
 pub mod base;
+pub mod clips;
+pub mod layers;
 pub mod movement_detection;
 pub mod weapon;
+pub mod y_sort;
+pub use appearance::*;
 pub use arm::*;
 pub use base::*;
+pub use clips::*;
+pub use layers::*;
 pub use movement_detection::*;
 pub use weapon::*;
+pub use y_sort::*;
 
 use crate::units;
 
@@ -15,12 +23,16 @@ pub struct SpritesPlugin;
 
 impl Plugin for SpritesPlugin {
     fn build(&self, app: &mut App) {
-        app
+        app.init_resource::<DepthRange>()
+            .init_resource::<LayerAtlases>()
+            .insert_resource(AppearanceCatalog::load())
+            .add_asset::<ClipSet>()
+            .init_asset_loader::<ClipSetLoader>()
+            .add_event::<AnimationEvent>()
+            .add_startup_system(load_body_atlases)
+            .add_startup_system(load_animation_clips)
             // .add_system(systems::sprite_size)
-            .add_system(animate_player_sprite)
-            .add_system(animate_npc_sprite)
-            .add_system(animate_arm_sprites.after(animate_player_sprite))
-            .add_system(animate_weapon_sprites.after(animate_arm_sprites))
+            .add_system(spawn_body_sprite)
             .add_system(spawn_arm_sprite.after(units::setup))
             .add_system(spawn_weapon_sprite.after(spawn_arm_sprite))
             .add_systems(
@@ -28,10 +40,14 @@ impl Plugin for SpritesPlugin {
                     change_animation_state_when_move,
                     change_animation_state_with_navigation,
                     set_to_idle_when_stop,
-                    change_animation_indices_with_state,
+                    play_animation_clips,
                 )
                     .chain(),
             )
-            .add_system(y_sort);
+            .add_system(insert_appearances)
+            .add_system(apply_appearance)
+            .add_system(order_sprite_layers.after(play_animation_clips))
+            .add_system(update_depth_range)
+            .add_system(y_sort.after(update_depth_range));
     }
 }
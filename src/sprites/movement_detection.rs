@@ -6,6 +6,10 @@ use crate::physics::MoveLock;
 
 use super::{AnimationIndices, AnimationState, FaceDirection, Facing};
 
+// Cosine of the angle within which the current facing is kept, about 50 degrees.
+// Analog sticks held near a diagonal would flicker between two directions otherwise.
+const KEEP_FACING_COS: f32 = 0.64;
+
 pub fn change_animation_state_when_move(
     mut query: Query<(&Velocity, &MoveLock, &mut Facing, &mut AnimationIndices), Changed<Velocity>>,
 ) {
@@ -17,18 +21,9 @@ pub fn change_animation_state_when_move(
             if !(x_speed.round() == 0. && y_speed.round() == 0.) {
                 indices.animation_state = AnimationState::Walk;
 
-                if x_speed.abs() > y_speed.abs() {
-                    if x_speed > 0. {
-                        facing.direction = FaceDirection::Right;
-                    } else {
-                        facing.direction = FaceDirection::Left;
-                    }
-                } else {
-                    if y_speed > 0. {
-                        facing.direction = FaceDirection::Up;
-                    } else {
-                        facing.direction = FaceDirection::Down;
-                    }
+                let heading = velocity.linvel.normalize_or_zero();
+                if heading.dot(facing.direction.to_vector()) < KEEP_FACING_COS {
+                    facing.direction = FaceDirection::from_vector(velocity.linvel);
                 }
             }
         }
@@ -60,20 +55,7 @@ pub fn change_animation_state_with_navigation(
 
         if !((dest_diff.x.round(), dest_diff.y.round()) == (0., 0.)) {
             indices.animation_state = AnimationState::Walk;
-
-            if dest_diff.x.abs() > dest_diff.y.abs() {
-                if dest_diff.x > 0. {
-                    facing.direction = FaceDirection::Right;
-                } else {
-                    facing.direction = FaceDirection::Left;
-                }
-            } else {
-                if dest_diff.y > 0. {
-                    facing.direction = FaceDirection::Up;
-                } else {
-                    facing.direction = FaceDirection::Down;
-                }
-            }
+            facing.direction = FaceDirection::from_vector(dest_diff);
         }
     }
 }
@@ -98,20 +80,3 @@ pub fn set_to_idle_when_stop(
         }
     }
 }
-
-pub fn change_animation_indices_with_state(
-    mut query: Query<&mut AnimationIndices, Changed<AnimationIndices>>,
-) {
-    for mut indices in &mut query {
-        if indices.animation_state == AnimationState::Walk {
-            indices.first = 0;
-            indices.last = 3;
-        } else if indices.animation_state == AnimationState::Attack {
-            indices.first = 0;
-            indices.last = 4;
-        } else {
-            indices.first = 0;
-            indices.last = 0;
-        }
-    }
-}
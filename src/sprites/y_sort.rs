@@ -0,0 +1,96 @@
+use bevy::prelude::{
+    Assets, Component, DetectChanges, Query, Ref, Res, ResMut, Resource, Transform,
+};
+use bevy_ecs_ldtk::{prelude::LdtkFields, EntityInstance, LdtkAsset};
+
+// Band of units, above the LDtk layers. Used when the `ysort` field of an LDtk entity is missing.
+pub const UNIT_BAND: f32 = 5.;
+// Depths leave room at both ends of the band, so the layers of characters don't reach the next or previous band.
+const MIN_DEPTH: f32 = 0.01;
+const MAX_DEPTH: f32 = 0.99;
+
+// Sorts sprites by y within a depth band. Sprites are drawn between `z` and `z + 1`, lower ones in front.
+// Sprites of different bands never interleave, e.g. units always cover soil and LDtk layers.
+#[derive(Component, Clone, Debug)]
+pub struct YSort {
+    pub z: f32,
+}
+
+impl Default for YSort {
+    fn default() -> Self {
+        YSort { z: UNIT_BAND }
+    }
+}
+
+// LDtk entities can set their band with an int `ysort` field.
+pub fn y_sort_from_ldtk_field(entity_instance: &EntityInstance) -> YSort {
+    entity_instance
+        .get_int_field("ysort")
+        .map_or_else(|_| YSort::default(), |&z| YSort { z: z as f32 })
+}
+
+// Vertical extent of the LDtk world, spread over a band so sorting is equally fine everywhere on the map.
+#[derive(Resource, Clone, Debug)]
+pub struct DepthRange {
+    pub min_y: f32,
+    pub max_y: f32,
+}
+
+impl Default for DepthRange {
+    fn default() -> Self {
+        DepthRange {
+            min_y: -8192.,
+            max_y: 8192.,
+        }
+    }
+}
+
+impl DepthRange {
+    // Fraction of a band at `y`. Sprites outside of the world are clamped to its edges.
+    pub fn depth(&self, y: f32) -> f32 {
+        let fraction = ((self.max_y - y) / (self.max_y - self.min_y)).clamp(0., 1.);
+        MIN_DEPTH + fraction * (MAX_DEPTH - MIN_DEPTH)
+    }
+
+    // Depth between sprites one pixel apart in y.
+    pub fn pixel_depth(&self) -> f32 {
+        (MAX_DEPTH - MIN_DEPTH) / (self.max_y - self.min_y)
+    }
+}
+
+pub fn update_depth_range(
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    mut depth_range: ResMut<DepthRange>,
+) {
+    if !ldtk_assets.is_changed() {
+        return;
+    }
+    // Levels are placed at `-world_y - px_hei`, since y points down in LDtk.
+    let levels = ldtk_assets
+        .iter()
+        .flat_map(|(_, ldtk_asset)| ldtk_asset.project.levels.iter());
+    let (min_y, max_y) = levels.fold((f32::MAX, f32::MIN), |(min_y, max_y), level| {
+        (
+            min_y.min(-(level.world_y + level.px_hei) as f32),
+            max_y.max(-level.world_y as f32),
+        )
+    });
+    if min_y < max_y {
+        *depth_range = DepthRange { min_y, max_y };
+    }
+}
+
+pub fn y_sort(depth_range: Res<DepthRange>, mut query: Query<(&mut Transform, Ref<YSort>)>) {
+    let all = depth_range.is_changed();
+    for (mut transform, ysort) in query.iter_mut() {
+        if !all && !transform.is_changed() && !ysort.is_changed() {
+            continue;
+        }
+        let z = ysort.z + depth_range.depth(transform.translation.y);
+        // Only written when it differs, so sorted sprites don't count as changed, and aren't extracted again, every frame.
+        // Systems that write sprites, tiles or the camera every frame do the same.
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
@@ -12,18 +12,23 @@ impl Plugin for StatePlugin {
             // use `AppState` to define current game status, and we can use this when we want to run system logic only once on game loading. ex: `add_system(ui::build_ui.in_schedule(OnEnter(state::AppState::MainGame)))`
             .add_state::<AppState>()
             .add_system(move_lock_system.in_schedule(OnExit(AppState::MainGame)))
+            // Units spawn while the title menu is shown, so they are locked every frame until the game starts.
+            .add_system(move_lock_system.in_set(OnUpdate(AppState::MainMenu)))
+            .add_system(move_lock_system.in_set(OnUpdate(AppState::CharacterCreation)))
             .add_system(move_unlock_system.in_set(OnUpdate(AppState::MainGame)));
     }
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum AppState {
-    #[default]
     MainGame,
     ConsoleOpenedState,
     InventoryOpenedState,
+    WorldMapOpenedState,
     GamePausedState,
+    #[default]
     MainMenu,
+    CharacterCreation,
     ControlMenu,
 }
 
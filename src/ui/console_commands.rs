@@ -0,0 +1,612 @@
+use bevy::{
+    ecs::{event::Events, system::BoxedSystem},
+    prelude::{
+        App, Entity, EventWriter, IVec2, In, IntoSystem, Name, Query, Res, Resource, With, World,
+    },
+};
+use std::{collections::BTreeMap, str::CharIndices};
+
+use super::{DevConsole, EnteredConsoleCommandEvent, PrintConsoleEvent};
+
+// Kind of value an argument is parsed into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    // A single token. Use quotes to include spaces.
+    Text,
+    // The rest of the line as it was typed, from its first token. Only valid as the last argument.
+    Rest,
+    Int,
+    // Grid coordinate written as `(x,y)`.
+    Coordinate,
+    // Entity looked up by its `Name`, case-insensitive.
+    Entity,
+}
+
+#[derive(Clone, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+        ArgSpec {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+        ArgSpec {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    fn usage(&self) -> String {
+        let name = if self.kind == ArgKind::Rest {
+            format!("{}...", self.name)
+        } else {
+            self.name.to_string()
+        };
+
+        if self.optional {
+            format!("[{}]", name)
+        } else {
+            format!("<{}>", name)
+        }
+    }
+}
+
+// Which consoles a command can be entered in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConsoleScope {
+    // Consoles of NPCs. The command acts on the NPC that owns the console.
+    #[default]
+    Npc,
+    // The developer console. The command acts on the world state.
+    Developer,
+    Any,
+}
+
+impl ConsoleScope {
+    // Scope of a console, depending on whether it is the developer console.
+    pub fn of(is_developer_console: bool) -> Self {
+        if is_developer_console {
+            ConsoleScope::Developer
+        } else {
+            ConsoleScope::Npc
+        }
+    }
+
+    pub fn allows(&self, console_scope: ConsoleScope) -> bool {
+        *self == ConsoleScope::Any || *self == console_scope
+    }
+}
+
+// Name, argument schema and help text of a console command.
+#[derive(Clone, Debug)]
+pub struct ConsoleCommandSpec {
+    pub name: &'static str,
+    pub args: Vec<ArgSpec>,
+    pub help: &'static str,
+    pub scope: ConsoleScope,
+}
+
+impl ConsoleCommandSpec {
+    pub fn new(name: &'static str, help: &'static str) -> Self {
+        ConsoleCommandSpec {
+            name,
+            args: Vec::new(),
+            help,
+            scope: ConsoleScope::default(),
+        }
+    }
+
+    pub fn arg(mut self, arg: ArgSpec) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn scope(mut self, scope: ConsoleScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for arg in self.args.iter() {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+        usage
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleArg {
+    Text(String),
+    Int(i64),
+    Coordinate(IVec2),
+    Entity(Entity),
+}
+
+// Parsed arguments of a command, looked up by the names declared in its `ConsoleCommandSpec`.
+#[derive(Clone, Debug, Default)]
+pub struct ConsoleArgs(Vec<(&'static str, ConsoleArg)>);
+
+impl ConsoleArgs {
+    pub fn get(&self, name: &str) -> Option<&ConsoleArg> {
+        self.0
+            .iter()
+            .find(|(arg_name, _)| *arg_name == name)
+            .map(|(_, arg)| arg)
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ConsoleArg::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(ConsoleArg::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn coordinate(&self, name: &str) -> Option<IVec2> {
+        match self.get(name) {
+            Some(ConsoleArg::Coordinate(coordinate)) => Some(*coordinate),
+            _ => None,
+        }
+    }
+
+    pub fn entity(&self, name: &str) -> Option<Entity> {
+        match self.get(name) {
+            Some(ConsoleArg::Entity(entity)) => Some(*entity),
+            _ => None,
+        }
+    }
+}
+
+// Input of command handler systems. `console` is the entity that owns the console the command was entered in.
+pub struct ConsoleCommandInput {
+    pub console: Entity,
+    pub args: ConsoleArgs,
+}
+
+struct RegisteredCommand {
+    spec: ConsoleCommandSpec,
+    // Taken out of the registry while it runs, so handlers can still read the registry itself.
+    handler: Option<BoxedSystem<ConsoleCommandInput, ()>>,
+    initialized: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommandRegistry {
+    commands: BTreeMap<&'static str, RegisteredCommand>,
+}
+
+impl ConsoleCommandRegistry {
+    pub fn register(
+        &mut self,
+        spec: ConsoleCommandSpec,
+        handler: BoxedSystem<ConsoleCommandInput, ()>,
+    ) {
+        self.commands.insert(
+            spec.name,
+            RegisteredCommand {
+                spec,
+                handler: Some(handler),
+                initialized: false,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ConsoleCommandSpec> {
+        self.commands.get(name).map(|command| &command.spec)
+    }
+
+    // Specs of every command, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &ConsoleCommandSpec> {
+        self.commands.values().map(|command| &command.spec)
+    }
+
+    // Specs of the commands that can be entered in a console of `scope`, sorted by name.
+    pub fn iter_scope(&self, scope: ConsoleScope) -> impl Iterator<Item = &ConsoleCommandSpec> {
+        self.iter().filter(move |spec| spec.scope.allows(scope))
+    }
+}
+
+pub trait ConsoleCommandAppExt {
+    // Registers a console command. The handler is a system that takes `In<ConsoleCommandInput>`.
+    fn add_console_command<Params>(
+        &mut self,
+        spec: ConsoleCommandSpec,
+        handler: impl IntoSystem<ConsoleCommandInput, (), Params>,
+    ) -> &mut Self;
+}
+
+impl ConsoleCommandAppExt for App {
+    fn add_console_command<Params>(
+        &mut self,
+        spec: ConsoleCommandSpec,
+        handler: impl IntoSystem<ConsoleCommandInput, (), Params>,
+    ) -> &mut Self {
+        self.init_resource::<ConsoleCommandRegistry>();
+        self.world
+            .resource_mut::<ConsoleCommandRegistry>()
+            .register(spec, Box::new(IntoSystem::into_system(handler)));
+        self
+    }
+}
+
+// Token of a command line, and the byte offset in the line right after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub end: usize,
+}
+
+// Reads the first token of `line` after byte offset `from`. Returns `None` if only whitespace is left.
+// Tokens are split by whitespace. Text in double or single quotes is kept as one token, `\` escapes the next
+// character inside double quotes, and text in parentheses is kept as one token with its spaces, so `go (3, 4)` has
+// two tokens. A quote or `(` without its closing pair is an ordinary character.
+pub fn next_token(line: &str, from: usize) -> Option<Token> {
+    let start = from + line[from..].find(|c: char| !c.is_whitespace())?;
+    let mut text = String::new();
+    let mut chars = line[start..].char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                return Some(Token {
+                    text,
+                    end: start + offset,
+                })
+            }
+            // Single quotes only start a quoted token, so apostrophes inside words (`what's`) are kept as they are.
+            '"' | '\'' if (c == '"' || offset == 0) && has_closing_quote(chars.clone(), c) => {
+                while let Some((_, quoted)) = chars.next() {
+                    match quoted {
+                        quoted if quoted == c => break,
+                        '\\' if c == '"' => text.extend(chars.next().map(|(_, escaped)| escaped)),
+                        quoted => text.push(quoted),
+                    }
+                }
+            }
+            '(' if chars.clone().any(|(_, c)| c == ')') => {
+                text.push(c);
+                for (_, c) in chars.by_ref() {
+                    text.push(c);
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            c => text.push(c),
+        }
+    }
+
+    Some(Token {
+        text,
+        end: line.len(),
+    })
+}
+
+// Whether a quote opened with `quote` is closed later in the line, skipping escaped double quotes.
+fn has_closing_quote(mut chars: CharIndices, quote: char) -> bool {
+    while let Some((_, c)) = chars.next() {
+        if c == quote {
+            return true;
+        }
+        if c == '\\' && quote == '"' {
+            chars.next();
+        }
+    }
+    false
+}
+
+pub fn parse_coordinate(token: &str) -> Option<IVec2> {
+    let elements: Vec<&str> = token
+        .trim_matches(|p| p == '(' || p == ')')
+        .split(',')
+        .collect();
+
+    if let [x, y] = elements[..] {
+        Some(IVec2::new(x.trim().parse().ok()?, y.trim().parse().ok()?))
+    } else {
+        None
+    }
+}
+
+// Looks up an entity by its `Name`, case-insensitive.
+pub fn find_entity_by_name(world: &mut World, name: &str) -> Option<Entity> {
+    let mut query = world.query::<(Entity, &Name)>();
+    query
+        .iter(world)
+        .find(|(_, entity_name)| entity_name.as_str().eq_ignore_ascii_case(name))
+        .map(|(entity, _)| entity)
+}
+
+fn parse_args(
+    world: &mut World,
+    spec: &ConsoleCommandSpec,
+    line: &str,
+    from: usize,
+) -> Result<ConsoleArgs, String> {
+    let mut args = Vec::new();
+    let mut position = from;
+
+    for arg_spec in spec.args.iter() {
+        let token = if arg_spec.kind == ArgKind::Rest {
+            // The rest of the line is taken as it was typed, so quotes and parentheses in it are kept.
+            let rest = line[position..].trim();
+            position = line.len();
+            (!rest.is_empty()).then(|| rest.to_string())
+        } else {
+            next_token(line, position).map(|token| {
+                position = token.end;
+                token.text
+            })
+        };
+
+        let Some(token) = token else {
+            if arg_spec.optional {
+                continue;
+            }
+            return Err(format!("Missing argument {}", arg_spec.usage()));
+        };
+
+        let arg =
+            match arg_spec.kind {
+                ArgKind::Text | ArgKind::Rest => ConsoleArg::Text(token),
+                ArgKind::Int => ConsoleArg::Int(token.parse().map_err(|_| {
+                    format!("{} should be a number, got \"{}\"", arg_spec.name, token)
+                })?),
+                ArgKind::Coordinate => {
+                    ConsoleArg::Coordinate(parse_coordinate(&token).ok_or_else(|| {
+                        format!(
+                            "{} should be a coordinate like (x,y), got \"{}\"",
+                            arg_spec.name, token
+                        )
+                    })?)
+                }
+                ArgKind::Entity => ConsoleArg::Entity(
+                    find_entity_by_name(world, &token)
+                        .ok_or_else(|| format!("There is no one called \"{}\"", token))?,
+                ),
+            };
+        args.push((arg_spec.name, arg));
+    }
+
+    if next_token(line, position).is_some() {
+        return Err("Too many arguments".to_string());
+    }
+
+    Ok(ConsoleArgs(args))
+}
+
+// Parses `EnteredConsoleCommandEvent`s and runs the handler of each registered command.
+// A failing command only prints its error, so the rest of the commands entered in the same frame still run.
+pub fn dispatch_console_commands(world: &mut World) {
+    let events: Vec<EnteredConsoleCommandEvent> = world
+        .resource_mut::<Events<EnteredConsoleCommandEvent>>()
+        .drain()
+        .collect();
+
+    for EnteredConsoleCommandEvent {
+        npc: console,
+        message,
+    } in events
+    {
+        // Don't do anything if the string is empty
+        let Some(name_token) = next_token(&message, 0) else {
+            continue;
+        };
+        let name = name_token.text.as_str();
+
+        if name != "clear" {
+            // first send what the user typed
+            print(world, console, format!("> {}", message.trim()));
+        }
+
+        let scope = ConsoleScope::of(world.get::<DevConsole>(console).is_some());
+        let Some(spec) = world
+            .resource::<ConsoleCommandRegistry>()
+            .get(name)
+            .filter(|spec| spec.scope.allows(scope))
+            .cloned()
+        else {
+            print(
+                world,
+                console,
+                format!("I didn't understand the command: \"{}\"", name),
+            );
+            continue;
+        };
+
+        let args = match parse_args(world, &spec, &message, name_token.end) {
+            Ok(args) => args,
+            Err(err) => {
+                print(world, console, format!("{}\nUsage: {}", err, spec.usage()));
+                continue;
+            }
+        };
+
+        let (handler, initialized) = {
+            let mut registry = world.resource_mut::<ConsoleCommandRegistry>();
+            let command = registry
+                .commands
+                .get_mut(spec.name)
+                .expect("command spec was just found in the registry");
+            (command.handler.take(), command.initialized)
+        };
+        let Some(mut handler) = handler else {
+            continue;
+        };
+
+        if !initialized {
+            handler.initialize(world);
+        }
+        handler.run(ConsoleCommandInput { console, args }, world);
+        handler.apply_buffers(world);
+
+        let mut registry = world.resource_mut::<ConsoleCommandRegistry>();
+        if let Some(command) = registry.commands.get_mut(spec.name) {
+            command.handler = Some(handler);
+            command.initialized = true;
+        }
+    }
+}
+
+fn print(world: &mut World, console: Entity, message: String) {
+    world.send_event(PrintConsoleEvent {
+        npc: console,
+        message,
+    });
+}
+
+// `help` lists every command, `help <command>` shows the usage of one command.
+pub fn display_help(
+    registry: &ConsoleCommandRegistry,
+    command: Option<&str>,
+    scope: ConsoleScope,
+) -> String {
+    if let Some(command) = command {
+        return match registry
+            .get(command)
+            .filter(|spec| spec.scope.allows(scope))
+        {
+            Some(spec) => {
+                let mut res = format!("\n{}\n    {}\n", spec.usage(), spec.help);
+                for arg in spec.args.iter() {
+                    res.push_str(&format!("    {} : {:?}\n", arg.usage(), arg.kind));
+                }
+                res
+            }
+            None => format!("There is no command called \"{}\"", command),
+        };
+    }
+
+    let mut res = String::from("\nSHOWING AVAILABLE COMMANDS\n");
+
+    let underline = "==========================\n\n";
+    res.push_str(underline);
+
+    for spec in registry.iter_scope(scope) {
+        res.push_str(&format!("- {} : {}\n", spec.usage(), spec.help));
+    }
+    res.push_str("\nType 'help <command>' for details.");
+
+    res
+}
+
+pub fn help_command(
+    In(input): In<ConsoleCommandInput>,
+    registry: Res<ConsoleCommandRegistry>,
+    dev_console_query: Query<(), With<DevConsole>>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let scope = ConsoleScope::of(dev_console_query.contains(input.console));
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: display_help(&registry, input.args.text("command"), scope),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(line: &str) -> Vec<String> {
+        let mut texts = Vec::new();
+        let mut position = 0;
+        while let Some(token) = next_token(line, position) {
+            position = token.end;
+            texts.push(token.text);
+        }
+        texts
+    }
+
+    fn ask_spec() -> ConsoleCommandSpec {
+        ConsoleCommandSpec::new("ask", "")
+            .arg(ArgSpec::required("npc", ArgKind::Text))
+            .arg(ArgSpec::required("question", ArgKind::Rest))
+    }
+
+    #[test]
+    fn tokens_are_split_by_whitespace() {
+        assert_eq!(texts("  give  bob 3 "), ["give", "bob", "3"]);
+        assert!(texts("   ").is_empty());
+    }
+
+    #[test]
+    fn quoted_text_is_one_token() {
+        assert_eq!(
+            texts(r#"say "hello there" 'old friend'"#),
+            ["say", "hello there", "old friend"]
+        );
+        assert_eq!(texts(r#"say "a \"quote\"""#), ["say", r#"a "quote""#]);
+        assert_eq!(texts("ask what's new"), ["ask", "what's", "new"]);
+    }
+
+    #[test]
+    fn unclosed_quotes_are_text() {
+        assert_eq!(texts(r#"say "hello"#), ["say", r#""hello"#]);
+        assert_eq!(texts("say 'twas cold"), ["say", "'twas", "cold"]);
+        assert_eq!(texts(r#"say "a \" b"#), ["say", r#""a"#, r#"\""#, "b"]);
+    }
+
+    #[test]
+    fn parentheses_are_one_token() {
+        assert_eq!(texts("go (3, 4) fast"), ["go", "(3, 4)", "fast"]);
+        assert_eq!(parse_coordinate("( 3, 4 )"), Some(IVec2::new(3, 4)));
+    }
+
+    #[test]
+    fn unclosed_parenthesis_is_text() {
+        assert_eq!(texts("ask bob (so what"), ["ask", "bob", "(so", "what"]);
+    }
+
+    #[test]
+    fn next_token_returns_where_it_ends() {
+        let line = "go (3, 4)  fast";
+        let token = next_token(line, 2).unwrap();
+        assert_eq!(token.text, "(3, 4)");
+        assert_eq!(&line[token.end..], "  fast");
+        assert!(next_token(line, line.len()).is_none());
+    }
+
+    #[test]
+    fn rest_argument_is_the_raw_remainder_of_the_line() {
+        let line = "ask bob what's  new (in biology)? ";
+        let args = parse_args(&mut World::new(), &ask_spec(), line, 3).unwrap();
+        assert_eq!(args.text("npc"), Some("bob"));
+        assert_eq!(args.text("question"), Some("what's  new (in biology)?"));
+    }
+
+    #[test]
+    fn rest_argument_keeps_unbalanced_quotes() {
+        for question in [r#"he said "hi"#, "'twas cold", r#""quoted" and not"#] {
+            let line = format!("ask bob {}", question);
+            let args = parse_args(&mut World::new(), &ask_spec(), &line, 3).unwrap();
+            assert_eq!(args.text("question"), Some(question));
+        }
+    }
+
+    #[test]
+    fn extra_and_missing_arguments_are_errors() {
+        let spec = ConsoleCommandSpec::new("go", "")
+            .arg(ArgSpec::required("destination", ArgKind::Coordinate));
+        assert!(parse_args(&mut World::new(), &spec, "go (1, 2) now", 2).is_err());
+        assert!(parse_args(&mut World::new(), &spec, "go", 2).is_err());
+        assert!(parse_args(&mut World::new(), &ask_spec(), "ask bob", 3).is_err());
+    }
+}
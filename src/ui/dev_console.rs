@@ -0,0 +1,544 @@
+use bevy::prelude::{
+    default, App, BuildChildren, Color, Commands, Component, DespawnRecursiveExt, Entity,
+    EventWriter, Handle, In, Name, NextState, Plugin, Query, Res, ResMut, Sprite, SpriteBundle,
+    State, Transform, Vec2, Vec3, With,
+};
+use bevy_ecs_ldtk::{LdtkAsset, Respawn};
+
+use super::{
+    ActiveConsole, ArgKind, ArgSpec, BarkEvent, ConsoleCommandAppExt, ConsoleCommandInput,
+    ConsoleCommandSpec, ConsoleData, ConsoleScope, Inventory, Item, ItemDictionary, Location,
+    PrintConsoleEvent,
+};
+use crate::{
+    clock::{parse_time_of_day, GameClock},
+    combat::GodMode,
+    constants::{GRID_OFFSET, GRID_SIZE},
+    debug_overlay::DebugOverlay,
+    input::{Action, ActionInput},
+    maps::{Coordinate, EntityGridMap, TileGridMap},
+    state::AppState,
+    units::{spawn_npc, Player, NPC},
+};
+
+pub struct DevConsolePlugin;
+
+impl Plugin for DevConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_dev_console)
+            .add_system(toggle_dev_console)
+            .add_console_command(
+                ConsoleCommandSpec::new("teleport", "moves the player, or target, to (x, y)")
+                    .arg(ArgSpec::required("destination", ArgKind::Coordinate))
+                    .arg(ArgSpec::optional("target", ArgKind::Entity))
+                    .scope(ConsoleScope::Developer),
+                teleport_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("spawn", "spawns an npc or an item next to the player")
+                    .arg(ArgSpec::required("kind", ArgKind::Text))
+                    .arg(ArgSpec::required("name", ArgKind::Text))
+                    .arg(ArgSpec::optional("at", ArgKind::Coordinate))
+                    .scope(ConsoleScope::Developer),
+                spawn_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("time", "shows or sets the time of day as hh:mm")
+                    .arg(ArgSpec::optional("time", ArgKind::Text))
+                    .arg(ArgSpec::optional("day", ArgKind::Int))
+                    .scope(ConsoleScope::Developer),
+                time_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("god", "makes the player, or target, ignore every hit")
+                    .arg(ArgSpec::optional("target", ArgKind::Entity))
+                    .scope(ConsoleScope::Developer),
+                god_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("kill", "removes target from the world")
+                    .arg(ArgSpec::required("target", ArgKind::Entity))
+                    .scope(ConsoleScope::Developer),
+                kill_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new(
+                    "give",
+                    "puts items into the player's, or target's, inventory",
+                )
+                .arg(ArgSpec::required("item", ArgKind::Text))
+                .arg(ArgSpec::optional("count", ArgKind::Int))
+                .arg(ArgSpec::optional("target", ArgKind::Entity))
+                .scope(ConsoleScope::Developer),
+                give_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new(
+                    "list",
+                    "lists named entities. filter by npc, player, item or name",
+                )
+                .arg(ArgSpec::optional("filter", ArgKind::Text))
+                .scope(ConsoleScope::Developer),
+                list_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new(
+                    "overlay",
+                    "toggles the debug overlay, or one of its layers",
+                )
+                .arg(ArgSpec::optional("layer", ArgKind::Text))
+                .scope(ConsoleScope::Developer),
+                overlay_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("reload", "respawns the level from the ldtk file")
+                    .scope(ConsoleScope::Developer),
+                reload_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("say", "shows a line in a speech bubble above target")
+                    .arg(ArgSpec::required("target", ArgKind::Entity))
+                    .arg(ArgSpec::required("line", ArgKind::Rest))
+                    .scope(ConsoleScope::Developer),
+                say_command,
+            );
+    }
+}
+
+// Owner of the developer console. Unlike NPC consoles, commands entered here act on the world state.
+#[derive(Component, Default)]
+pub struct DevConsole;
+
+pub fn spawn_dev_console(mut commands: Commands) {
+    let data = commands
+        .spawn(ConsoleData {
+            messages: vec!["Developer console. Type 'help' for commands.".to_string()],
+            ..default()
+        })
+        .id();
+    commands.spawn(DevConsole).push_children(&[data]);
+}
+
+// `Action::DevConsole` opens the developer console while playing, and closes it again.
+pub fn toggle_dev_console(
+    action_input: Res<ActionInput>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut active_console: ResMut<ActiveConsole>,
+    dev_console_query: Query<Entity, With<DevConsole>>,
+) {
+    if !action_input.just_pressed(Action::DevConsole) {
+        return;
+    }
+    let Ok(dev_console) = dev_console_query.get_single() else {
+        return;
+    };
+
+    match app_state.0 {
+        AppState::MainGame => {
+            active_console.0 = Some(dev_console);
+            next_state.set(AppState::ConsoleOpenedState);
+        }
+        AppState::ConsoleOpenedState if active_console.0 == Some(dev_console) => {
+            next_state.set(AppState::MainGame);
+        }
+        _ => {}
+    }
+}
+
+fn grid_to_translation(x: i32, y: i32) -> Vec2 {
+    Vec2::new(
+        x as f32 * GRID_SIZE + GRID_OFFSET,
+        y as f32 * GRID_SIZE + GRID_OFFSET,
+    )
+}
+
+fn display_name(name_query: &Query<&Name>, entity: Entity) -> String {
+    name_query
+        .get(entity)
+        .map_or(format!("{:?}", entity), |name| name.to_string())
+}
+
+pub fn teleport_command(
+    In(input): In<ConsoleCommandInput>,
+    player_query: Query<Entity, With<Player>>,
+    mut transform_query: Query<&mut Transform>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Some(destination) = input.args.coordinate("destination") else {
+        return;
+    };
+    let Some(target) = input
+        .args
+        .entity("target")
+        .or_else(|| player_query.get_single().ok())
+    else {
+        return;
+    };
+
+    let message = if let Ok(mut transform) = transform_query.get_mut(target) {
+        let translation = grid_to_translation(destination.x, destination.y);
+        transform.translation.x = translation.x;
+        transform.translation.y = translation.y;
+        format!(
+            "{} teleported to ({}, {})",
+            display_name(&name_query, target),
+            destination.x,
+            destination.y
+        )
+    } else {
+        format!("{} can't be moved", display_name(&name_query, target))
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+pub fn spawn_command(
+    In(input): In<ConsoleCommandInput>,
+    mut commands: Commands,
+    item_dictionary: Res<ItemDictionary>,
+    player_query: Query<&Transform, With<Player>>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let (Some(kind), Some(name)) = (input.args.text("kind"), input.args.text("name")) else {
+        return;
+    };
+
+    // Without a position, things are spawned one grid right of the player.
+    let player_translation = player_query
+        .get_single()
+        .map_or(Vec3::ZERO, |transform| transform.translation);
+    let translation = match input.args.coordinate("at") {
+        Some(at) => grid_to_translation(at.x, at.y).extend(player_translation.z),
+        None => player_translation + Vec3::new(GRID_SIZE, 0., 0.),
+    };
+
+    let message = match kind.to_lowercase().as_str() {
+        "npc" => {
+            spawn_npc(&mut commands, name, translation);
+            format!("Spawned NPC {}", name)
+        }
+        "item" => match item_dictionary.find(name) {
+            Some(item_info_index) => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::GOLD,
+                                custom_size: Some(Vec2::splat(GRID_SIZE / 2.)),
+                                ..default()
+                            },
+                            transform: Transform::from_translation(translation),
+                            ..default()
+                        },
+                        Name::new(item_dictionary[item_info_index].name.clone()),
+                    ))
+                    .id();
+                commands.entity(entity).insert(Item {
+                    location: Location::FieldDropped,
+                    item_info_index,
+                    count: 1,
+                    entity,
+                });
+                format!("Spawned item {}", item_dictionary[item_info_index].name)
+            }
+            None => format!("There is no item called \"{}\"", name),
+        },
+        _ => format!("Can't spawn \"{}\". Use npc or item", kind),
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+pub fn time_command(
+    In(input): In<ConsoleCommandInput>,
+    mut clock: ResMut<GameClock>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    if let Some(time) = input.args.text("time") {
+        let Some((hour, minute)) = parse_time_of_day(time) else {
+            console_writer.send(PrintConsoleEvent {
+                npc: input.console,
+                message: format!("time should be like 13:30, got \"{}\"", time),
+            });
+            return;
+        };
+        clock.set_time(hour, minute);
+    }
+    if let Some(day) = input.args.int("day") {
+        clock.day = day.max(1) as u32;
+    }
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: clock.display(),
+    });
+}
+
+pub fn god_command(
+    In(input): In<ConsoleCommandInput>,
+    mut commands: Commands,
+    player_query: Query<Entity, With<Player>>,
+    god_query: Query<(), With<GodMode>>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Some(target) = input
+        .args
+        .entity("target")
+        .or_else(|| player_query.get_single().ok())
+    else {
+        return;
+    };
+
+    let message = if god_query.contains(target) {
+        commands.entity(target).remove::<GodMode>();
+        format!("God mode off for {}", display_name(&name_query, target))
+    } else {
+        commands.entity(target).insert(GodMode);
+        format!("God mode on for {}", display_name(&name_query, target))
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+pub fn kill_command(
+    In(input): In<ConsoleCommandInput>,
+    mut commands: Commands,
+    mut entity_map: ResMut<EntityGridMap>,
+    player_query: Query<(), With<Player>>,
+    coordinate_query: Query<&Coordinate>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Some(target) = input.args.entity("target") else {
+        return;
+    };
+    if player_query.contains(target) {
+        console_writer.send(PrintConsoleEvent {
+            npc: input.console,
+            message: "Can't kill the player".to_string(),
+        });
+        return;
+    }
+
+    // Despawned entities are not removed from `EntityGridMap` by themselves.
+    if let Ok(coordinate) = coordinate_query.get(target) {
+        for x in coordinate.min_x..=coordinate.max_x {
+            for y in coordinate.min_y..=coordinate.max_y {
+                entity_map.delete((x, y), target);
+            }
+        }
+    }
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: format!("Killed {}", display_name(&name_query, target)),
+    });
+    commands.entity(target).despawn_recursive();
+}
+
+pub fn give_command(
+    In(input): In<ConsoleCommandInput>,
+    mut commands: Commands,
+    item_dictionary: Res<ItemDictionary>,
+    player_query: Query<Entity, With<Player>>,
+    mut inventory_query: Query<&mut Inventory>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let Some(item_name) = input.args.text("item") else {
+        return;
+    };
+    let Some(target) = input
+        .args
+        .entity("target")
+        .or_else(|| player_query.get_single().ok())
+    else {
+        return;
+    };
+    let count = input.args.int("count").unwrap_or(1).max(1) as u32;
+
+    let message = match (
+        item_dictionary.find(item_name),
+        inventory_query.get_mut(target),
+    ) {
+        (None, _) => format!("There is no item called \"{}\"", item_name),
+        (_, Err(_)) => format!("{} has no inventory", display_name(&name_query, target)),
+        (Some(item_info_index), Ok(mut inventory)) => {
+            let entity = commands.spawn_empty().id();
+            let item = Item {
+                location: Location::Inventory,
+                item_info_index,
+                count,
+                entity,
+            };
+            commands.entity(entity).insert(item);
+
+            if inventory.push(None, item).is_some() {
+                commands.entity(entity).despawn();
+                format!("{}'s inventory is full", display_name(&name_query, target))
+            } else {
+                format!(
+                    "Gave {} {} to {}",
+                    count,
+                    item_dictionary[item_info_index].name,
+                    display_name(&name_query, target)
+                )
+            }
+        }
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn list_command(
+    In(input): In<ConsoleCommandInput>,
+    entity_query: Query<(
+        Entity,
+        &Name,
+        Option<&Coordinate>,
+        Option<&Player>,
+        Option<&NPC>,
+        Option<&Item>,
+    )>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let filter = input
+        .args
+        .text("filter")
+        .map(|filter| filter.to_lowercase());
+
+    let mut lines: Vec<String> = entity_query
+        .iter()
+        .filter_map(|(entity, name, coordinate, player, npc, item)| {
+            let kind = if player.is_some() {
+                "player"
+            } else if npc.is_some() {
+                "npc"
+            } else if item.is_some() {
+                "item"
+            } else {
+                return None;
+            };
+
+            if let Some(filter) = filter.as_deref() {
+                if filter != kind && !name.as_str().to_lowercase().contains(filter) {
+                    return None;
+                }
+            }
+
+            let position = coordinate.map_or(String::new(), |coordinate| {
+                format!(" at ({}, {})", coordinate.min_x, coordinate.min_y)
+            });
+            Some(format!("- {} {} {:?}{}", kind, name, entity, position))
+        })
+        .collect();
+    lines.sort();
+
+    let message = if lines.is_empty() {
+        "Nothing found".to_string()
+    } else {
+        lines.join("\n")
+    };
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+pub fn overlay_command(
+    In(input): In<ConsoleCommandInput>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let message = match input.args.text("layer") {
+        None => {
+            overlay.enabled = !overlay.enabled;
+            format!(
+                "Debug overlay {}",
+                if overlay.enabled { "on" } else { "off" }
+            )
+        }
+        Some(layer) => {
+            let flag = match layer.to_lowercase().as_str() {
+                "paths" => &mut overlay.show_paths,
+                "ranges" => &mut overlay.show_ranges,
+                "targets" => &mut overlay.show_targets,
+                "scores" => &mut overlay.show_scores,
+                "tiles" => &mut overlay.show_tiles,
+                "occupancy" => &mut overlay.show_occupancy,
+                _ => {
+                    console_writer.send(PrintConsoleEvent {
+                        npc: input.console,
+                        message: format!(
+                            "There is no layer called \"{}\". Use paths, ranges, targets, scores, tiles or occupancy",
+                            layer
+                        ),
+                    });
+                    return;
+                }
+            };
+            *flag = !*flag;
+            let shown = *flag;
+
+            // Showing a layer also shows the overlay, otherwise nothing would change on screen.
+            if shown {
+                overlay.enabled = true;
+            }
+            format!("{} {}", layer, if shown { "on" } else { "off" })
+        }
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message,
+    });
+}
+
+pub fn reload_command(
+    In(input): In<ConsoleCommandInput>,
+    mut commands: Commands,
+    world_query: Query<Entity, With<Handle<LdtkAsset>>>,
+    mut entity_map: ResMut<EntityGridMap>,
+    mut tile_map: ResMut<TileGridMap>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    for world in world_query.iter() {
+        commands.entity(world).insert(Respawn);
+    }
+
+    // Grid maps are filled again while the level spawns.
+    *entity_map = EntityGridMap::new();
+    *tile_map = TileGridMap::new();
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: "Reloading level...".to_string(),
+    });
+}
+
+pub fn say_command(In(input): In<ConsoleCommandInput>, mut bark_writer: EventWriter<BarkEvent>) {
+    let (Some(target), Some(line)) = (input.args.entity("target"), input.args.text("line")) else {
+        return;
+    };
+
+    bark_writer.send(BarkEvent {
+        speaker: target,
+        message: line.to_string(),
+    });
+}
@@ -0,0 +1,353 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowResized},
+};
+
+use super::{FacedNpc, Inventory, ItemDictionary, INVENTORY_COLUMNS};
+use crate::{
+    clock::GameClock,
+    combat::Health,
+    input::{Action, InputMap},
+    physics::Stamina,
+    state::AppState,
+    units::Player,
+};
+
+// Number keys select hotbar slots. They are fixed like the editing keys of the console, and not `Action`s.
+const HOTBAR_KEYS: [KeyCode; INVENTORY_COLUMNS] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+];
+
+const PANEL_COLOR: Color = Color::rgba(0., 0., 0., 0.7);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.1, 0.3, 0.1, 0.9);
+const TEXT_COLOR: Color = Color::rgba(0.4, 1., 0.4, 1.);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HotbarSelection>()
+            .add_system(
+                spawn_hud
+                    .in_schedule(OnEnter(AppState::MainGame))
+                    .run_if(run_once()),
+            )
+            .add_system(update_stat_bars)
+            .add_system(select_hotbar_slot.in_set(OnUpdate(AppState::MainGame)))
+            .add_system(update_hotbar)
+            .add_system(update_clock_text)
+            .add_system(update_interaction_prompt)
+            .add_system(scale_hud_text);
+    }
+}
+
+// Root node of the HUD, drawn under the console and menus.
+#[derive(Component)]
+pub struct Hud;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum StatBar {
+    Health,
+    Stamina,
+}
+
+#[derive(Component)]
+pub struct HotbarSlot(pub usize);
+
+#[derive(Component)]
+pub struct HotbarSlotText(pub usize);
+
+#[derive(Component)]
+pub struct ClockText;
+
+#[derive(Component)]
+pub struct InteractionPrompt;
+
+// Font size of HUD text, as a fraction of the window height.
+#[derive(Component)]
+pub struct HudText(pub f32);
+
+// Index of the selected hotbar slot, which is also an index of the first row of `Inventory::items`.
+#[derive(Resource, Default)]
+pub struct HotbarSelection(pub usize);
+
+pub fn hud_text(asset_server: &AssetServer, scale: f32) -> (TextBundle, HudText) {
+    (
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/VT323-Regular.ttf"),
+                font_size: 16.,
+                color: TEXT_COLOR,
+            },
+        ),
+        HudText(scale),
+    )
+}
+
+fn stat_bar(parent: &mut ChildBuilder, stat: StatBar, color: Color) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(40.)),
+                ..default()
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                stat,
+            ));
+        });
+}
+
+// Sizes are in percent of the window, so the HUD scales with it.
+pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                ..default()
+            },
+            Hud,
+        ))
+        .with_children(|parent| {
+            // Health and stamina bars on the top left.
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Percent(2.),
+                            top: Val::Percent(3.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(20.), Val::Percent(5.)),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::SpaceBetween,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    stat_bar(parent, StatBar::Health, Color::rgb_u8(220, 60, 60));
+                    stat_bar(parent, StatBar::Stamina, Color::rgb_u8(255, 204, 51));
+                });
+
+            // Time and day on the top right.
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Percent(2.),
+                            top: Val::Percent(2.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((hud_text(&asset_server, 0.05), ClockText));
+                });
+
+            // Interaction prompt above the hotbar.
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Percent(16.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(100.), Val::Auto),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((hud_text(&asset_server, 0.04), InteractionPrompt));
+                });
+
+            // Hotbar on the bottom center.
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Percent(3.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(100.), Val::Percent(10.)),
+                        justify_content: JustifyContent::Center,
+                        gap: Size::all(Val::Percent(0.5)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for index in 0..INVENTORY_COLUMNS {
+                        parent
+                            .spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(6.), Val::Percent(100.)),
+                                        flex_direction: FlexDirection::Column,
+                                        justify_content: JustifyContent::SpaceBetween,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: PANEL_COLOR.into(),
+                                    ..default()
+                                },
+                                HotbarSlot(index),
+                            ))
+                            .with_children(|parent| {
+                                let mut number = hud_text(&asset_server, 0.025);
+                                number.0.text.sections[0].value = (index + 1).to_string();
+                                parent.spawn(number);
+                                parent
+                                    .spawn((hud_text(&asset_server, 0.025), HotbarSlotText(index)));
+                            });
+                    }
+                });
+        });
+}
+
+pub fn update_stat_bars(
+    player_query: Query<(Ref<Health>, Ref<Stamina>), With<Player>>,
+    new_bar_query: Query<(), Added<StatBar>>,
+    mut bar_query: Query<(&StatBar, &mut Style)>,
+) {
+    let Ok((health, stamina)) = player_query.get_single() else {
+        return;
+    };
+    if !health.is_changed() && !stamina.is_changed() && new_bar_query.is_empty() {
+        return;
+    }
+
+    for (stat, mut style) in bar_query.iter_mut() {
+        let fraction = match stat {
+            StatBar::Health => health.fraction(),
+            StatBar::Stamina => stamina.fraction(),
+        };
+        style.size.width = Val::Percent(fraction * 100.);
+    }
+}
+
+pub fn select_hotbar_slot(input: Res<Input<KeyCode>>, mut selection: ResMut<HotbarSelection>) {
+    if let Some(index) = HOTBAR_KEYS.iter().position(|key| input.just_pressed(*key)) {
+        selection.0 = index;
+    }
+}
+
+// Shows the first row of the player's inventory, and highlights the selected slot.
+pub fn update_hotbar(
+    selection: Res<HotbarSelection>,
+    item_dictionary: Res<ItemDictionary>,
+    player_query: Query<Ref<Inventory>, With<Player>>,
+    new_slot_query: Query<(), Added<HotbarSlot>>,
+    mut slot_query: Query<(&HotbarSlot, &mut BackgroundColor)>,
+    mut text_query: Query<(&HotbarSlotText, &mut Text)>,
+) {
+    let Ok(inventory) = player_query.get_single() else {
+        return;
+    };
+    let spawned = !new_slot_query.is_empty();
+
+    if selection.is_changed() || spawned {
+        for (slot, mut background_color) in slot_query.iter_mut() {
+            *background_color = if slot.0 == selection.0 {
+                SELECTED_SLOT_COLOR.into()
+            } else {
+                PANEL_COLOR.into()
+            };
+        }
+    }
+
+    if inventory.is_changed() || spawned {
+        for (slot, mut text) in text_query.iter_mut() {
+            text.sections[0].value = match inventory.get_item_info(slot.0, &item_dictionary) {
+                Some(info) => format!("{} x{}", info.name, inventory.items[slot.0].count),
+                None => String::new(),
+            };
+        }
+    }
+}
+
+pub fn update_clock_text(clock: Res<GameClock>, mut text_query: Query<&mut Text, With<ClockText>>) {
+    for mut text in text_query.iter_mut() {
+        let display = clock.display();
+        if text.sections[0].value != display {
+            text.sections[0].value = display;
+        }
+    }
+}
+
+// Shows which binding talks to the NPC in front of the player, like `E: Talk to Isabella`.
+pub fn update_interaction_prompt(
+    app_state: Res<State<AppState>>,
+    faced_npc: Res<FacedNpc>,
+    input_map: Res<InputMap>,
+    name_query: Query<&Name>,
+    mut prompt_query: Query<&mut Text, With<InteractionPrompt>>,
+) {
+    let prompt = match faced_npc.0 {
+        Some(npc) if app_state.0 == AppState::MainGame => {
+            let binding = input_map
+                .bindings(Action::Talk)
+                .first()
+                .map_or_else(|| "?".to_string(), |binding| binding.display());
+            let name = name_query.get(npc).map_or("", |name| name.as_str());
+            format!("{}: Talk to {}", binding, name)
+        }
+        _ => String::new(),
+    };
+
+    for mut text in prompt_query.iter_mut() {
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.clone();
+        }
+    }
+}
+
+pub fn scale_hud_text(
+    mut resized_events: EventReader<WindowResized>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut text_query: Query<(Ref<HudText>, &mut Text)>,
+) {
+    let resized = resized_events.iter().count() > 0;
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    for (scale, mut text) in text_query.iter_mut() {
+        if resized || scale.is_added() {
+            for section in text.sections.iter_mut() {
+                section.style.font_size = scale.0 * window.height();
+            }
+        }
+    }
+}
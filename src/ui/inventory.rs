@@ -1,18 +1,29 @@
 use bevy::{
-    prelude::{Component, Deref, DerefMut, Entity, Res, Resource},
+    prelude::{
+        Commands, Component, Deref, DerefMut, Entity, NextState, Res, ResMut, Resource, State,
+    },
     reflect::{FromReflect, Reflect},
     sprite::TextureAtlasSprite,
 };
 
+use crate::{
+    input::{Action, ActionInput},
+    state::AppState,
+};
+
+pub const INVENTORY_SLOTS: usize = 32;
+// Slots per row of the inventory UI.
+pub const INVENTORY_COLUMNS: usize = 8;
+
 // TODO: `Inventory` and `Item` should be moduled separately in `src/items/mod.rs`.
 // Contains vector of Items. This Component is under the Player/NPC's root Bundle.
-#[derive(Default, Component, Reflect)]
+#[derive(Default, Clone, Component, Reflect)]
 pub struct Inventory {
     pub is_opening: bool,
     pub fully_opened: bool,
     // TODO: Maybe we should consider about the size of Inventory, or data structure of it. (Array, BTreeMap, HashMap, etc...)
     // Currently I choose Array with fixed size, because I think static size is enough for implementing inventory.
-    pub items: [Item; 32],
+    pub items: [Item; INVENTORY_SLOTS],
 }
 
 impl Inventory {
@@ -61,6 +72,51 @@ impl Inventory {
         }
     }
 
+    // Adds `count` to a stack of the same item, or to a new stack in the first empty slot.
+    // Returns false if the inventory is full.
+    pub fn stack(&mut self, commands: &mut Commands, item_info_index: usize, count: u32) -> bool {
+        if let Some(item) = self.items.iter_mut().find(|item| {
+            item.entity != Entity::from_raw(0) && item.item_info_index == item_info_index
+        }) {
+            item.count += count;
+            commands.entity(item.entity).insert(*item);
+            return true;
+        }
+
+        let entity = commands.spawn_empty().id();
+        let item = Item {
+            location: Location::Inventory,
+            item_info_index,
+            count,
+            entity,
+        };
+        commands.entity(entity).insert(item);
+        if self.push(None, item).is_some() {
+            commands.entity(entity).despawn();
+            return false;
+        }
+        true
+    }
+
+    // Uses one of the stack in `index`, and empties the slot when it runs out.
+    pub fn take_one(&mut self, commands: &mut Commands, index: usize) -> bool {
+        let Some(item) = self.items.get_mut(index) else {
+            return false;
+        };
+        if item.entity == Entity::from_raw(0) || item.count == 0 {
+            return false;
+        }
+
+        item.count -= 1;
+        if item.count == 0 {
+            commands.entity(item.entity).despawn();
+            *item = Item::default();
+        } else {
+            commands.entity(item.entity).insert(*item);
+        }
+        true
+    }
+
     pub fn pop(&mut self, index: usize) -> Option<Item> {
         if let Some(item) = self.items.get_mut(index) {
             if item.entity == Entity::from_raw(0) {
@@ -124,9 +180,36 @@ pub enum Location {
 pub struct ItemDictionary(Vec<ItemInfo>);
 
 // `ItemInfo` stored in item_info_index 0 of `ItemDictionary` is empty info.
+// TODO: Load item infos from assets.
 impl Default for ItemDictionary {
     fn default() -> Self {
-        ItemDictionary(vec![ItemInfo::default()])
+        ItemDictionary(vec![
+            ItemInfo::default(),
+            ItemInfo::new("Hoe", "Tills soil for planting.", ItemType::Tool),
+            ItemInfo::new("Watering Can", "Waters tilled soil.", ItemType::Tool),
+            ItemInfo::new("Wheat Seeds", "Grows in 4 days.", ItemType::Seed),
+            ItemInfo::new("Beet Seeds", "Grows in 5 days.", ItemType::Seed),
+            ItemInfo::new("Pepper Seeds", "Grows in 6 days.", ItemType::Seed),
+            ItemInfo::new("Corn Seeds", "Grows in 7 days.", ItemType::Seed),
+            ItemInfo::new("Pumpkin Seeds", "Grows in 8 days.", ItemType::Seed),
+            ItemInfo::new("Wheat", "Golden grain.", ItemType::Produce),
+            ItemInfo::new("Beet", "An earthy red root.", ItemType::Produce),
+            ItemInfo::new("Pepper", "A crunchy red pepper.", ItemType::Produce),
+            ItemInfo::new("Corn", "A sweet ear of corn.", ItemType::Produce),
+            ItemInfo::new("Pumpkin", "A big orange pumpkin.", ItemType::Produce),
+        ])
+    }
+}
+
+impl ItemDictionary {
+    // Returns item_info_index of the item with the given name, case-insensitive.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.0
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, info)| info.name.eq_ignore_ascii_case(name))
+            .map(|(index, _)| index)
     }
 }
 
@@ -138,12 +221,27 @@ pub struct ItemInfo {
     pub item_type: ItemType,
 }
 
+impl ItemInfo {
+    pub fn new(name: &str, description: &str, item_type: ItemType) -> Self {
+        ItemInfo {
+            name: name.to_string(),
+            description: description.to_string(),
+            item_type,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Default, Copy, Clone, Reflect)]
 pub enum ItemType {
     #[default]
     Consumable,
     Weapon,
     Armor,
+    Tool,
+    // Planted in tilled soil.
+    Seed,
+    Produce,
 }
 
 impl Default for Item {
@@ -158,8 +256,54 @@ impl Default for Item {
     }
 }
 
-// Change State to `InventoryOpenedState` when push I key.
-pub fn open_inventory() {}
+// Slot selected with a gamepad or the keyboard, as an index of `Inventory::items`.
+#[derive(Resource, Default)]
+pub struct InventoryCursor(pub usize);
+
+// Toggles `InventoryOpenedState` on `Action::OpenInventory`. `Action::MenuBack` closes it too.
+pub fn open_inventory(
+    action_input: Res<ActionInput>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match app_state.0 {
+        AppState::MainGame if action_input.just_pressed(Action::OpenInventory) => {
+            next_state.set(AppState::InventoryOpenedState)
+        }
+        AppState::InventoryOpenedState
+            if action_input.just_pressed(Action::OpenInventory)
+                || action_input.just_pressed(Action::MenuBack) =>
+        {
+            next_state.set(AppState::MainGame)
+        }
+        _ => {}
+    }
+}
+
+// Move actions select slots of the grid while the inventory is open, since the player can't move then.
+// Only runs in `InventoryOpenedState`, so the d-pad doesn't move a hidden cursor in game.
+pub fn navigate_inventory(action_input: Res<ActionInput>, mut cursor: ResMut<InventoryCursor>) {
+    let (column, row) = (cursor.0 % INVENTORY_COLUMNS, cursor.0 / INVENTORY_COLUMNS);
+    let rows = INVENTORY_SLOTS / INVENTORY_COLUMNS;
+
+    let (column, row) = if action_input.just_pressed(Action::MoveLeft) {
+        (column.saturating_sub(1), row)
+    } else if action_input.just_pressed(Action::MoveRight) {
+        ((column + 1).min(INVENTORY_COLUMNS - 1), row)
+    } else if action_input.just_pressed(Action::MoveUp) {
+        (column, row.saturating_sub(1))
+    } else if action_input.just_pressed(Action::MoveDown) {
+        (column, (row + 1).min(rows - 1))
+    } else {
+        return;
+    };
+    cursor.0 = row * INVENTORY_COLUMNS + column;
+}
+
+// The inventory opens on the first slot again.
+pub fn reset_inventory_cursor(mut cursor: ResMut<InventoryCursor>) {
+    cursor.0 = 0;
+}
 
 // Draw Inventory UI when `InventoryOpenedState` with popping out animation from right side.
 pub fn draw_inventory_ui() {}
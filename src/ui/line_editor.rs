@@ -0,0 +1,243 @@
+use bevy::reflect::{FromReflect, Reflect};
+
+// Maximum number of characters of a console command line.
+pub const MAX_COMMAND_CHARS: usize = 144;
+
+// Single line text buffer with a cursor, used as the console command line.
+// `cursor` is a byte index into `text`, and always lies on a char boundary so multi-byte characters are never split.
+#[derive(Default, Debug, Clone, Reflect, FromReflect)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    // Text before and after the cursor.
+    pub fn split_at_cursor(&self) -> (&str, &str) {
+        self.text.split_at(self.cursor)
+    }
+
+    // Replaces the whole line and moves the cursor to the end.
+    pub fn set(&mut self, text: &str) {
+        self.text.clear();
+        self.cursor = 0;
+        self.insert_str(text);
+    }
+
+    // Returns the whole line and clears the editor.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    // Inserts text at the cursor. Control characters are dropped, and text beyond `MAX_COMMAND_CHARS` is cut at a char boundary.
+    pub fn insert_str(&mut self, text: &str) {
+        let available = MAX_COMMAND_CHARS.saturating_sub(self.text.chars().count());
+        let inserted: String = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(available)
+            .collect();
+
+        self.text.insert_str(self.cursor, &inserted);
+        self.cursor += inserted.len();
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let mut buffer = [0; 4];
+        self.insert_str(c.encode_utf8(&mut buffer));
+    }
+
+    pub fn backspace(&mut self) {
+        let start = self.prev_char_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete(&mut self) {
+        let end = self.next_char_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    pub fn delete_word_before(&mut self) {
+        let start = self.prev_word_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete_word_after(&mut self) {
+        let end = self.next_word_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.prev_char_boundary();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_char_boundary();
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.prev_word_boundary();
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.next_word_boundary();
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    // Word under the cursor, used for tab completion. Returns the byte index where the word starts and the word itself.
+    pub fn word_before_cursor(&self) -> (usize, &str) {
+        let before = &self.text[..self.cursor];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(index, c)| index + c.len_utf8());
+        (start, &before[start..])
+    }
+
+    // Replaces text between `start` and the cursor, used for tab completion.
+    pub fn replace_before_cursor(&mut self, start: usize, replacement: &str) {
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+        self.insert_str(replacement);
+    }
+
+    fn prev_char_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_char_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    // Skips whitespace backwards, then the word itself.
+    fn prev_word_boundary(&self) -> usize {
+        let mut chars = self.text[..self.cursor].char_indices().rev().peekable();
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let mut boundary = chars.peek().map_or(0, |(index, _)| *index);
+        while let Some((index, _)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+            boundary = index;
+        }
+        boundary
+    }
+
+    // Skips the word forwards, then the whitespace after it.
+    fn next_word_boundary(&self) -> usize {
+        let rest = &self.text[self.cursor..];
+        let mut chars = rest.char_indices().peekable();
+        while chars.next_if(|(_, c)| !c.is_whitespace()).is_some() {}
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        self.cursor + chars.peek().map_or(rest.len(), |(index, _)| *index)
+    }
+}
+
+// Longest prefix shared by all candidates, used when tab completion is ambiguous.
+pub fn common_prefix<'a>(candidates: &[&'a str]) -> &'a str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+
+    let mut end = first.len();
+    for candidate in candidates.iter().skip(1) {
+        end = first
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(end.min(candidate.len()), |((index, _), _)| end.min(index));
+    }
+    &first[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_multi_byte_characters_whole() {
+        let mut editor = LineEditor::default();
+        editor.set("héllo");
+        editor.move_home();
+        editor.move_right();
+        editor.move_right();
+        assert_eq!(editor.split_at_cursor(), ("hé", "llo"));
+
+        editor.backspace();
+        assert_eq!(editor.split_at_cursor(), ("h", "llo"));
+
+        editor.delete();
+        assert_eq!(editor.as_str(), "hlo");
+        assert_eq!(editor.split_at_cursor(), ("h", "lo"));
+    }
+
+    #[test]
+    fn drops_control_characters_and_caps_length() {
+        let mut editor = LineEditor::default();
+        editor.insert_str("a\tb\nc");
+        assert_eq!(editor.as_str(), "abc");
+
+        editor.set(&"é".repeat(MAX_COMMAND_CHARS + 10));
+        assert_eq!(editor.as_str().chars().count(), MAX_COMMAND_CHARS);
+        editor.insert_char('x');
+        assert_eq!(editor.as_str().chars().count(), MAX_COMMAND_CHARS);
+        assert_eq!(editor.split_at_cursor().1, "");
+    }
+
+    #[test]
+    fn moves_and_deletes_by_word() {
+        let mut editor = LineEditor::default();
+        editor.set("say hello  world");
+        editor.move_word_left();
+        assert_eq!(editor.split_at_cursor().1, "world");
+        editor.move_word_left();
+        assert_eq!(editor.split_at_cursor().1, "hello  world");
+        editor.move_word_right();
+        assert_eq!(editor.split_at_cursor().1, "world");
+
+        editor.delete_word_before();
+        assert_eq!(editor.split_at_cursor(), ("say ", "world"));
+        editor.delete_word_after();
+        assert_eq!(editor.as_str(), "say ");
+    }
+
+    #[test]
+    fn completes_word_before_cursor() {
+        let mut editor = LineEditor::default();
+        editor.set("give sw");
+        let (start, word) = editor.word_before_cursor();
+        assert_eq!((start, word), (5, "sw"));
+
+        editor.replace_before_cursor(start, "sword");
+        assert_eq!(editor.split_at_cursor(), ("give sword", ""));
+
+        assert_eq!(editor.take(), "give sword");
+        assert_eq!(editor.split_at_cursor(), ("", ""));
+    }
+
+    #[test]
+    fn finds_common_prefix() {
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(common_prefix(&["sword", "swamp"]), "sw");
+        assert_eq!(common_prefix(&["abc", "ab"]), "ab");
+        assert_eq!(common_prefix(&["é1", "é2"]), "é");
+    }
+}
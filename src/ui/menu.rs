@@ -0,0 +1,563 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_rapier2d::prelude::RapierConfiguration;
+
+use crate::{
+    input::{Action, ActionInput, Binding, InputMap},
+    save::{save_path, LoadGameEvent},
+    sprites::{Appearance, AppearanceCatalog, AppearancePart},
+    state::AppState,
+    units::Player,
+};
+
+const BUTTON_COLOR: Color = Color::rgba(0.02, 0.07, 0., 0.9);
+const HOVERED_BUTTON_COLOR: Color = Color::rgba(0.1, 0.3, 0.1, 0.9);
+const DISABLED_TEXT_COLOR: Color = Color::rgba(0.4, 0.4, 0.4, 1.);
+const TEXT_COLOR: Color = Color::rgba(0.4, 1., 0.4, 1.);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlsReturnState>()
+            .init_resource::<Rebinding>()
+            .init_resource::<MenuFocus>()
+            .add_event::<MenuButtonPressedEvent>()
+            .add_system(setup_main_menu.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(setup_pause_menu.in_schedule(OnEnter(AppState::GamePausedState)))
+            .add_system(update_controls_menu.in_set(OnUpdate(AppState::ControlMenu)))
+            .add_system(update_character_menu.in_set(OnUpdate(AppState::CharacterCreation)))
+            .add_system(cancel_rebinding.in_schedule(OnExit(AppState::ControlMenu)))
+            .add_system(despawn_menu.in_schedule(OnExit(AppState::MainMenu)))
+            .add_system(despawn_menu.in_schedule(OnExit(AppState::GamePausedState)))
+            .add_system(despawn_menu.in_schedule(OnExit(AppState::ControlMenu)))
+            .add_system(despawn_menu.in_schedule(OnExit(AppState::CharacterCreation)))
+            // The world is frozen behind every menu, and runs again only in `MainGame`.
+            .add_system(pause_world.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(pause_world.in_schedule(OnEnter(AppState::GamePausedState)))
+            .add_system(resume_world.in_schedule(OnEnter(AppState::MainGame)))
+            // The key that finishes a rebinding must not also go back from the menu or press a button.
+            .add_systems(
+                (handle_menu_keys, click_menu_buttons, navigate_menu).before(capture_rebinding),
+            )
+            .add_system(capture_rebinding.in_set(OnUpdate(AppState::ControlMenu)))
+            .add_systems(
+                (
+                    handle_menu_buttons,
+                    handle_controls_buttons,
+                    handle_character_buttons,
+                )
+                    .after(capture_rebinding),
+            );
+    }
+}
+
+// Root node of the menu on screen. It is despawned when its state exits.
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuButton {
+    NewGame,
+    Continue,
+    Resume,
+    Controls,
+    // A row of the character creation screen. Clicking it wears the next style or color.
+    Appearance(AppearancePart),
+    StartGame,
+    // A row of the controls screen. Clicking it waits for the next key or button.
+    Rebind(Action),
+    ResetControls,
+    Back,
+    Quit,
+}
+
+// Buttons that can't be clicked, like `Continue` without a save.
+#[derive(Component)]
+pub struct DisabledButton;
+
+// Menu to go back to from the controls screen.
+#[derive(Resource)]
+pub struct ControlsReturnState(pub AppState);
+
+impl Default for ControlsReturnState {
+    fn default() -> Self {
+        ControlsReturnState(AppState::MainMenu)
+    }
+}
+
+// Index of the button selected with a gamepad or the keyboard, among the enabled buttons of the menu.
+#[derive(Resource, Default)]
+pub struct MenuFocus(pub Option<usize>);
+
+#[derive(Debug)]
+pub struct MenuButtonPressedEvent {
+    pub button: MenuButton,
+}
+
+// Action waiting for a new binding on the controls screen.
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub action: Option<Action>,
+    // Why the last pressed binding was refused, e.g. another action uses it.
+    pub refused: Option<String>,
+}
+
+fn text_style(asset_server: &AssetServer, font_size: f32, color: Color) -> TextStyle {
+    TextStyle {
+        font: asset_server.load("fonts/VT323-Regular.ttf"),
+        font_size,
+        color,
+    }
+}
+
+// Spawns a full screen menu with a title, lines of text, and a column of buttons.
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    title: &str,
+    lines: &[String],
+    buttons: &[(MenuButton, &str, bool)],
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::all(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.7).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            MenuRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(title, text_style(asset_server, 64., TEXT_COLOR))
+                    .with_style(Style {
+                        margin: UiRect::bottom(Val::Px(24.)),
+                        ..default()
+                    }),
+            );
+
+            for line in lines.iter() {
+                parent.spawn(TextBundle::from_section(
+                    line.as_str(),
+                    text_style(asset_server, 24., TEXT_COLOR),
+                ));
+            }
+
+            for &(button, label, enabled) in buttons.iter() {
+                // Rows of the controls screen are smaller, so every action fits on the screen.
+                let (size, font_size) = match button {
+                    MenuButton::Rebind(_) => (Size::new(Val::Px(520.), Val::Px(22.)), 20.),
+                    MenuButton::Appearance(_) => (Size::new(Val::Px(320.), Val::Px(32.)), 24.),
+                    _ => (Size::new(Val::Px(240.), Val::Px(40.)), 28.),
+                };
+                let mut entity = parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                    button,
+                ));
+                if !enabled {
+                    entity.insert(DisabledButton);
+                }
+                entity.with_children(|parent| {
+                    let color = if enabled {
+                        TEXT_COLOR
+                    } else {
+                        DISABLED_TEXT_COLOR
+                    };
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        text_style(asset_server, font_size, color),
+                    ));
+                });
+            }
+        });
+}
+
+pub fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let has_save = save_path(0).exists();
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        "Smallville",
+        &[],
+        &[
+            (MenuButton::NewGame, "New Game", true),
+            (MenuButton::Continue, "Continue", has_save),
+            (MenuButton::Controls, "Controls", true),
+            (MenuButton::Quit, "Quit", true),
+        ],
+    );
+}
+
+pub fn setup_pause_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        "Paused",
+        &[],
+        &[
+            (MenuButton::Resume, "Resume", true),
+            (MenuButton::Controls, "Controls", true),
+            (MenuButton::Quit, "Quit", true),
+        ],
+    );
+}
+
+// Spawns the controls screen when it is entered, and spawns it again when bindings or the rebinding action change.
+pub fn update_controls_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    menu_query: Query<Entity, With<MenuRoot>>,
+) {
+    if !menu_query.is_empty() && !input_map.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let hint = match (rebinding.action, &rebinding.refused) {
+        (Some(_), Some(refused)) => refused.clone(),
+        (Some(action), None) => format!(
+            "Press a key or button for {}, or {} to cancel",
+            action.label(),
+            input_map.display(Action::Pause)
+        ),
+        (None, _) => "Click an action to rebind it".to_string(),
+    };
+    let labels: Vec<String> = Action::ALL
+        .iter()
+        .map(|&action| {
+            let bindings = if rebinding.action == Some(action) {
+                "...".to_string()
+            } else {
+                input_map.display(action)
+            };
+            format!("{:<20}{:>28}", action.label(), bindings)
+        })
+        .collect();
+    let mut buttons: Vec<(MenuButton, &str, bool)> = Action::ALL
+        .iter()
+        .zip(labels.iter())
+        .map(|(&action, label)| (MenuButton::Rebind(action), label.as_str(), true))
+        .collect();
+    buttons.push((MenuButton::ResetControls, "Reset", true));
+    buttons.push((MenuButton::Back, "Back", true));
+
+    spawn_menu(&mut commands, &asset_server, "Controls", &[hint], &buttons);
+}
+
+// Spawns the character creation screen when it is entered, and spawns it again when the player's appearance changes.
+// The player is drawn behind the menu, so changes are seen as they are made.
+pub fn update_character_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    catalog: Res<AppearanceCatalog>,
+    player_query: Query<Ref<Appearance>, With<Player>>,
+    menu_query: Query<Entity, With<MenuRoot>>,
+) {
+    let appearance = player_query.get_single().ok();
+    let changed = appearance
+        .as_ref()
+        .is_some_and(|appearance| appearance.is_changed());
+    if !menu_query.is_empty() && !changed {
+        return;
+    }
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let appearance =
+        appearance.map_or_else(Appearance::default, |appearance| (*appearance).clone());
+    // Parts without styles or colors in the catalog can't be changed, so they aren't shown.
+    let parts: Vec<AppearancePart> = AppearancePart::ALL
+        .into_iter()
+        .filter(|&part| catalog.offers(part))
+        .collect();
+    let labels: Vec<String> = parts
+        .iter()
+        .map(|&part| appearance.describe(part, &catalog))
+        .collect();
+    let mut buttons: Vec<(MenuButton, &str, bool)> = parts
+        .iter()
+        .zip(labels.iter())
+        .map(|(&part, label)| (MenuButton::Appearance(part), label.as_str(), true))
+        .collect();
+    buttons.push((MenuButton::StartGame, "Start", true));
+
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        "Your Character",
+        &["Click a row to change it".to_string()],
+        &buttons,
+    );
+}
+
+pub fn despawn_menu(
+    mut commands: Commands,
+    menu_query: Query<Entity, With<MenuRoot>>,
+    mut focus: ResMut<MenuFocus>,
+) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+    focus.0 = None;
+}
+
+// Stops time, so timers, animations and the game clock stop, and stops the physics simulation.
+// Systems that don't depend on time, like AI scorers and actions, run only if `world_running`.
+pub fn pause_world(mut time: ResMut<Time>, mut rapier_config: ResMut<RapierConfiguration>) {
+    time.pause();
+    rapier_config.physics_pipeline_active = false;
+}
+
+pub fn world_running(time: Res<Time>) -> bool {
+    !time.is_paused()
+}
+
+pub fn resume_world(mut time: ResMut<Time>, mut rapier_config: ResMut<RapierConfiguration>) {
+    if time.is_paused() {
+        time.unpause();
+    }
+    rapier_config.physics_pipeline_active = true;
+}
+
+// Hover colors for the mouse, and clicks sent as `MenuButtonPressedEvent`.
+#[allow(clippy::type_complexity)]
+pub fn click_menu_buttons(
+    mut button_query: Query<
+        (&Interaction, &MenuButton, &mut BackgroundColor),
+        (Changed<Interaction>, Without<DisabledButton>),
+    >,
+    rebinding: Res<Rebinding>,
+    mut pressed_writer: EventWriter<MenuButtonPressedEvent>,
+) {
+    for (interaction, button, mut background_color) in button_query.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *background_color = HOVERED_BUTTON_COLOR.into(),
+            Interaction::None => *background_color = BUTTON_COLOR.into(),
+            // A click while rebinding is the new binding.
+            Interaction::Clicked if rebinding.action.is_some() => {}
+            Interaction::Clicked => pressed_writer.send(MenuButtonPressedEvent { button: *button }),
+        }
+    }
+}
+
+// Move actions focus the previous or next button, and `Action::MenuConfirm` presses it.
+pub fn navigate_menu(
+    action_input: Res<ActionInput>,
+    rebinding: Res<Rebinding>,
+    mut focus: ResMut<MenuFocus>,
+    menu_query: Query<&Children, With<MenuRoot>>,
+    new_menu_query: Query<(), Added<MenuRoot>>,
+    mut button_query: Query<(&MenuButton, &mut BackgroundColor), Without<DisabledButton>>,
+    mut pressed_writer: EventWriter<MenuButtonPressedEvent>,
+) {
+    if rebinding.action.is_some() {
+        return;
+    }
+    let buttons: Vec<Entity> = menu_query
+        .iter()
+        .flat_map(|children| children.iter().copied())
+        .filter(|&child| button_query.contains(child))
+        .collect();
+    if buttons.is_empty() {
+        return;
+    }
+
+    let last = buttons.len() - 1;
+    if action_input.just_pressed(Action::MoveDown) {
+        focus.0 = Some(focus.0.map_or(0, |index| (index + 1).min(last)));
+    } else if action_input.just_pressed(Action::MoveUp) {
+        focus.0 = Some(focus.0.map_or(0, |index| index.saturating_sub(1)));
+    }
+
+    // The controls screen is spawned again after a rebinding, so the focus is painted again too.
+    let repaint = focus.is_changed() || !new_menu_query.is_empty();
+    if let Some(focused) = focus.0.filter(|_| repaint) {
+        for (index, &entity) in buttons.iter().enumerate() {
+            if let Ok((_, mut background_color)) = button_query.get_mut(entity) {
+                *background_color = if index == focused {
+                    HOVERED_BUTTON_COLOR.into()
+                } else {
+                    BUTTON_COLOR.into()
+                };
+            }
+        }
+    }
+
+    if let Some(index) = focus.0 {
+        if action_input.just_pressed(Action::MenuConfirm) {
+            if let Ok((&button, _)) = button_query.get(buttons[index.min(last)]) {
+                pressed_writer.send(MenuButtonPressedEvent { button });
+            }
+        }
+    }
+}
+
+pub fn handle_menu_buttons(
+    mut pressed_events: EventReader<MenuButtonPressedEvent>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut controls_return_state: ResMut<ControlsReturnState>,
+    mut load_writer: EventWriter<LoadGameEvent>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    for event in pressed_events.iter() {
+        match event.button {
+            MenuButton::NewGame => next_state.set(AppState::CharacterCreation),
+            MenuButton::StartGame | MenuButton::Resume => next_state.set(AppState::MainGame),
+            MenuButton::Continue => {
+                load_writer.send(LoadGameEvent { slot: 0 });
+                next_state.set(AppState::MainGame);
+            }
+            MenuButton::Controls => {
+                controls_return_state.0 = app_state.0;
+                next_state.set(AppState::ControlMenu);
+            }
+            MenuButton::Back => next_state.set(controls_return_state.0),
+            MenuButton::Quit => exit_writer.send(AppExit),
+            MenuButton::Rebind(_) | MenuButton::ResetControls | MenuButton::Appearance(_) => {}
+        }
+    }
+}
+
+pub fn handle_controls_buttons(
+    mut pressed_events: EventReader<MenuButtonPressedEvent>,
+    mut rebinding: ResMut<Rebinding>,
+    mut input_map: ResMut<InputMap>,
+) {
+    for event in pressed_events.iter() {
+        match event.button {
+            MenuButton::Rebind(action) => {
+                rebinding.action = Some(action);
+                rebinding.refused = None;
+            }
+            MenuButton::ResetControls => {
+                rebinding.action = None;
+                match InputMap::reset() {
+                    Ok(reset) => *input_map = reset,
+                    Err(err) => error!("Failed to reset bindings: {}", err),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn handle_character_buttons(
+    mut pressed_events: EventReader<MenuButtonPressedEvent>,
+    catalog: Res<AppearanceCatalog>,
+    mut player_query: Query<&mut Appearance, With<Player>>,
+) {
+    for event in pressed_events.iter() {
+        if let MenuButton::Appearance(part) = event.button {
+            for mut appearance in player_query.iter_mut() {
+                appearance.cycle(part, &catalog);
+            }
+        }
+    }
+}
+
+// Binds the first key, mouse button or gamepad button pressed after a row was clicked, and writes the user config file.
+// The bindings of `Action::Pause` cancel, and bindings used by another action on the same screens are refused.
+pub fn capture_rebinding(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    action_input: Res<ActionInput>,
+    mut rebinding: ResMut<Rebinding>,
+    mut input_map: ResMut<InputMap>,
+) {
+    let Some(action) = rebinding.action else {
+        return;
+    };
+    if action_input.just_pressed(Action::Pause) {
+        rebinding.action = None;
+        return;
+    }
+
+    let binding = keys
+        .get_just_pressed()
+        .next()
+        .map(|&key| Binding::Key(key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|&button| Binding::Mouse(button))
+        })
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.button_type))
+        });
+
+    let Some(binding) = binding else {
+        return;
+    };
+    if let Some(other) = input_map.conflict(action, binding) {
+        rebinding.refused = Some(format!(
+            "{} is used for {}. Press another key or button",
+            binding.display(),
+            other.label()
+        ));
+        return;
+    }
+
+    input_map.rebind(action, binding);
+    if let Err(err) = input_map.save() {
+        error!("Failed to save bindings: {}", err);
+    }
+    rebinding.action = None;
+    rebinding.refused = None;
+}
+
+pub fn cancel_rebinding(mut rebinding: ResMut<Rebinding>) {
+    rebinding.action = None;
+    rebinding.refused = None;
+}
+
+// `Action::Pause` pauses the game, and it or `Action::MenuBack` goes back from the pause menu and the controls screen.
+pub fn handle_menu_keys(
+    action_input: Res<ActionInput>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    controls_return_state: Res<ControlsReturnState>,
+    rebinding: Res<Rebinding>,
+) {
+    // While rebinding, the pause key is the new binding or cancels it.
+    if rebinding.action.is_some() {
+        return;
+    }
+    let pause = action_input.just_pressed(Action::Pause);
+    let back = action_input.just_pressed(Action::MenuBack);
+
+    match app_state.0 {
+        AppState::MainGame if pause => next_state.set(AppState::GamePausedState),
+        AppState::GamePausedState if pause || back => next_state.set(AppState::MainGame),
+        AppState::ControlMenu if pause || back => next_state.set(controls_return_state.0),
+        _ => {}
+    }
+}
@@ -1,38 +1,89 @@
 use bevy::prelude::{
-    App, CoreSet, IntoSystemAppConfig, IntoSystemConfig, IntoSystemConfigs, OnEnter, OnExit,
-    OnUpdate, Plugin,
+    run_once, App, CoreSet, IntoSystemAppConfig, IntoSystemConfig, IntoSystemConfigs, OnEnter,
+    OnExit, OnUpdate, Plugin,
 };
 use bevy_tokio_tasks::TokioTasksPlugin;
 
+pub mod console_commands;
+pub mod dev_console;
+pub mod hud;
 pub mod inventory;
+pub mod line_editor;
+pub mod menu;
 pub mod npc_console;
+pub mod speech_bubble;
+pub mod world_map;
 use crate::state;
+pub use console_commands::*;
+pub use dev_console::*;
+pub use hud::*;
 pub use inventory::*;
+pub use line_editor::*;
+pub use menu::*;
 pub use npc_console::*;
+pub use speech_bubble::*;
+pub use world_map::*;
 
 pub struct ConsolePlugin;
 
 impl Plugin for ConsolePlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(build_ui)
-            // .add_system(build_ui.in_schedule(OnExit(state::AppState::MainMenu)))     // TODO: Implement this after MainMenu UI is implemented.
-            .init_resource::<AskGPT>() // TODO: Remove this after changing `AskGPT` to Component.
+        app.init_resource::<AskGPT>() // TODO: Remove this after changing `AskGPT` to Component.
+            .init_resource::<ActiveConsole>()
+            .init_resource::<FacedNpc>()
+            .init_resource::<ItemDictionary>()
+            .init_resource::<InventoryCursor>()
             .add_plugin(TokioTasksPlugin::default())
-            .add_system(open_npc_console.in_schedule(OnEnter(state::AppState::ConsoleOpenedState)))
+            // Spawned the first time the game starts, and not again when coming back from a menu.
+            .add_system(
+                build_ui
+                    .in_schedule(OnEnter(state::AppState::MainGame))
+                    .run_if(run_once()),
+            )
+            .add_system(open_console.in_schedule(OnEnter(state::AppState::ConsoleOpenedState)))
             .add_systems(
                 (update_logs_area, handle_input_keys, update_enter_command)
                     .in_set(OnUpdate(state::AppState::ConsoleOpenedState)),
             )
             .add_system(
-                commands_handler
+                dispatch_console_commands
                     .in_set(OnUpdate(state::AppState::ConsoleOpenedState))
-                    .run_if(should_run_cmd_handler)
                     .before(handle_input_keys),
             )
+            .add_console_command(
+                ConsoleCommandSpec::new("help", "Displays available commands")
+                    .arg(ArgSpec::optional("command", ArgKind::Text))
+                    .scope(ConsoleScope::Any),
+                help_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("clear", "Clears commands on the screen")
+                    .scope(ConsoleScope::Any),
+                clear_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("motd", "Prints informations about YOUR computer"),
+                motd_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("ask", "ask some questions to chatGPT")
+                    .arg(ArgSpec::required("question", ArgKind::Rest)),
+                ask_command,
+            )
+            .add_console_command(
+                ConsoleCommandSpec::new("go", "order npc to move to (x, y) position")
+                    .arg(ArgSpec::required("destination", ArgKind::Coordinate)),
+                go_command,
+            )
             .add_system(apply_animation.in_base_set(CoreSet::PostUpdate))
-            .add_system(close_npc_console.in_schedule(OnExit(state::AppState::ConsoleOpenedState)))
+            .add_system(close_console.in_schedule(OnExit(state::AppState::ConsoleOpenedState)))
             .add_system(push_message_events_to_console)
             .add_system(interact_with_npc)
+            .add_system(open_inventory)
+            .add_system(navigate_inventory.in_set(OnUpdate(state::AppState::InventoryOpenedState)))
+            .add_system(
+                reset_inventory_cursor.in_schedule(OnExit(state::AppState::InventoryOpenedState)),
+            )
             .add_system(mouse_scroll)
             .add_event::<PrintConsoleEvent>()
             .add_event::<EnteredConsoleCommandEvent>()
@@ -8,10 +8,9 @@ use bevy::{
         mouse::{MouseScrollUnit, MouseWheel},
     },
     prelude::*,
-    window::PrimaryWindow,
+    window::{Ime, PrimaryWindow, ReceivedCharacter},
 };
 use chatgpt::prelude::ChatGPT;
-#[allow(unused_imports)]
 use rand::Rng;
 use seldom_map_nav::prelude::PathTarget;
 use sysinfo::{ProcessorExt, System, SystemExt};
@@ -19,9 +18,18 @@ use sysinfo::{ProcessorExt, System, SystemExt};
 #[cfg(not(target_family = "wasm"))]
 use bevy_tokio_tasks::TokioTasksRuntime;
 
+use super::{
+    common_prefix, summarize_line, BarkEvent, ConsoleCommandInput, ConsoleCommandRegistry,
+    ConsoleScope, DevConsole, LineEditor,
+};
 use crate::{
-    ai::OrderMovementEvent,
+    ai::{
+        format_memories, DailyPlan, MemoryKind, MemoryScorer, MemoryStream, OrderMovementEvent,
+        Persona,
+    },
+    clock::GameClock,
     constants::{GRID_OFFSET, GRID_SIZE},
+    input::{Action, ActionInput},
     maps::{Coordinate, EntityGridMap},
     sprites::{FaceDirection, Facing},
     state::AppState,
@@ -31,7 +39,12 @@ use crate::{
 #[cfg(not(target_family = "wasm"))]
 pub type TasksRuntime = TokioTasksRuntime;
 
-const CONSOLE_HEIGHT: f32 = 0.4;
+// Fraction of the window covered by an open console, from the bottom.
+pub const CONSOLE_HEIGHT: f32 = 0.4;
+// Memories given to chatGPT with questions of the `ask` command.
+const PROMPT_MEMORIES: usize = 5;
+// Said in a speech bubble by NPCs when the player starts talking to them.
+const GREETINGS: [&str; 4] = ["Hello!", "Hi there!", "Oh, hey.", "Need something?"];
 
 #[derive(Component)]
 pub struct LogsArea;
@@ -40,14 +53,70 @@ pub struct CommandLineText;
 #[derive(Component)]
 pub struct ConsoleUI;
 
-// Child component of NPC entities. Stores console messages about what player talks to NPC.
+// Child component of NPC entities and the developer console. Stores console messages about what player talks to NPC.
 #[derive(Default, Component, Debug, Reflect)]
 pub struct ConsoleData {
-    pub typed_command: String,
+    pub command_line: LineEditor,
+    // Text being composed by the IME, shown at the cursor until it is committed.
+    pub preedit: String,
     pub is_opening: bool,
     pub fully_opened: bool,
     pub messages: Vec<String>,
+    // Commands entered in this console, oldest first.
+    pub history: Vec<String>,
+    // Index into `history` while browsing it with Up/Down.
+    pub history_index: Option<usize>,
+    // Line that was being typed before browsing the history.
+    pub draft: String,
 }
+
+impl ConsoleData {
+    pub fn submit_command(&mut self) -> String {
+        let command = self.command_line.take();
+        if !command.trim().is_empty() && self.history.last() != Some(&command) {
+            self.history.push(command.clone());
+        }
+        self.history_index = None;
+        self.draft.clear();
+        command
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let index = match self.history_index {
+            None => {
+                self.draft = self.command_line.as_str().to_string();
+                self.history.len() - 1
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+        self.history_index = Some(index);
+        self.command_line.set(&self.history[index]);
+    }
+
+    pub fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+
+        if index + 1 < self.history.len() {
+            self.history_index = Some(index + 1);
+            self.command_line.set(&self.history[index + 1]);
+        } else {
+            self.history_index = None;
+            let draft = std::mem::take(&mut self.draft);
+            self.command_line.set(&draft);
+        }
+    }
+}
+
+// Entity that owns the console currently shown, which is an NPC or the developer console.
+#[derive(Resource, Default, Debug)]
+pub struct ActiveConsole(pub Option<Entity>);
+
 pub struct PrintConsoleEvent {
     pub npc: Entity,
     pub message: String,
@@ -87,16 +156,13 @@ pub struct GPTResponse {
 
 // pushes messages in event `PrintConsoleEvent` to `ConsoleData.messages`.
 pub fn push_message_events_to_console(
-    npc_query: Query<Entity, With<NPC>>,
     mut data_query: Query<(&Parent, &mut ConsoleData)>,
     mut ev_console_message: EventReader<PrintConsoleEvent>,
 ) {
     for PrintConsoleEvent { npc, message } in ev_console_message.iter() {
         for (parent, mut data) in data_query.iter_mut() {
-            if let Ok(parent_npc) = npc_query.get(parent.get()) {
-                if parent_npc.eq(npc) {
-                    data.messages.push(message.clone());
-                }
+            if parent.get().eq(npc) {
+                data.messages.push(message.clone());
             }
         }
     }
@@ -358,15 +424,24 @@ fn display_bar(width: usize, value: f64, total_value: f64) -> String {
     res
 }
 
+// NPC in front of the player, found by `interact_with_npc`. Used for the interaction prompt of the HUD.
+#[derive(Resource, Default, PartialEq, Eq)]
+pub struct FacedNpc(pub Option<Entity>);
+
 // Open or close console for npc. changes player's `CurrentInteractingNPC` component.
+#[allow(clippy::too_many_arguments)]
 pub fn interact_with_npc(
     entity_map: Res<EntityGridMap>,
     mut player: Query<(&mut CurrentInteractingNPC, &Facing, &Coordinate), With<Player>>,
     npc: Query<Entity, With<NPC>>,
     input: Res<Input<KeyCode>>,
+    action_input: Res<ActionInput>,
     app_state: Res<State<AppState>>,
     mut next_state: ResMut<NextState<AppState>>,
+    mut active_console: ResMut<ActiveConsole>,
+    mut faced_npc: ResMut<FacedNpc>,
 ) {
+    let mut faced = None;
     // TODO: under code should be separate systems. Maybe `fn find_entities_in_range`, and add entities in player range into player entitiy's children entities.
     for (mut interacting_npc, facing, coordinate) in player.iter_mut() {
         let (range_x, range_y) = match facing.direction {
@@ -394,9 +469,13 @@ pub fn interact_with_npc(
                 if let Some(hit_range) = entity_map.get((x, y)) {
                     for npc_entity in hit_range {
                         if npc.contains(*npc_entity) {
-                            if input.just_pressed(KeyCode::E) && app_state.0 == AppState::MainGame {
+                            faced = Some(*npc_entity);
+                            if action_input.just_pressed(Action::Talk)
+                                && app_state.0 == AppState::MainGame
+                            {
                                 next_state.set(AppState::ConsoleOpenedState);
                                 interacting_npc.0 = Some(*npc_entity);
+                                active_console.0 = Some(*npc_entity);
 
                                 #[cfg(debug_assertions)]
                                 info!("Console opened {:?}", app_state);
@@ -409,29 +488,44 @@ pub fn interact_with_npc(
         }
     }
 
-    if (app_state.0 == AppState::ConsoleOpenedState) && input.just_pressed(KeyCode::Escape) {
+    faced_npc.set_if_neq(FacedNpc(faced));
+
+    let close = input.just_pressed(KeyCode::Escape) || action_input.just_pressed(Action::MenuBack);
+    if (app_state.0 == AppState::ConsoleOpenedState) && close {
         next_state.set(AppState::MainGame);
         #[cfg(debug_assertions)]
         info!("Console closed {:?}", app_state);
     }
 }
 
-pub fn open_npc_console(
-    interacting_npc_query: Query<&CurrentInteractingNPC, With<Player>>,
+pub fn open_console(
+    active_console: Res<ActiveConsole>,
     mut anim_data: ResMut<ConsoleAnimation>,
     mut data_query: Query<(&Parent, &mut ConsoleData)>,
+    npc_query: Query<(), With<NPC>>,
+    mut bark_writer: EventWriter<BarkEvent>,
     time: Res<Time>,
-    window: Query<&Window, With<PrimaryWindow>>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
 ) {
-    let Ok(current_window) = window.get_single() else {
+    let Ok(mut current_window) = window.get_single_mut() else {
         return;
     };
+    // Receive composed text from input methods (e.g. Korean, Japanese) while typing commands.
+    current_window.ime_enabled = true;
+
+    if let Some(npc) = active_console
+        .0
+        .filter(|&console| npc_query.contains(console))
+    {
+        let greeting = GREETINGS[rand::thread_rng().gen_range(0..GREETINGS.len())];
+        bark_writer.send(BarkEvent {
+            speaker: npc,
+            message: greeting.to_string(),
+        });
+    }
+
     for (parent, mut data) in data_query.iter_mut() {
-        // TODO: Maybe checking parent entity of ConsoleData is not necessary.
-        if interacting_npc_query
-            .get_single()
-            .is_ok_and(|x| x.0.is_some_and(|y| y.eq(parent)))
-        {
+        if active_console.0 == Some(parent.get()) {
             data.is_opening = true;
 
             anim_data.start_position = Vec2::new(0.0, current_window.height());
@@ -443,37 +537,38 @@ pub fn open_npc_console(
     }
 }
 
-pub fn close_npc_console(
+pub fn close_console(
     mut anim_data: ResMut<ConsoleAnimation>,
+    mut active_console: ResMut<ActiveConsole>,
     mut interacting_npc_query: Query<&mut CurrentInteractingNPC, With<Player>>,
-    npc_query: Query<&Children, With<NPC>>,
-    mut data_query: Query<&mut ConsoleData>,
+    mut data_query: Query<(&Parent, &mut ConsoleData)>,
     time: Res<Time>,
-    window: Query<&Window, With<PrimaryWindow>>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
 ) {
-    let Ok(current_window) = window.get_single() else {
+    let Ok(mut current_window) = window.get_single_mut() else {
         return;
     };
+    current_window.ime_enabled = false;
+
+    for mut interacting_npc in interacting_npc_query.iter_mut() {
+        interacting_npc.0 = None;
+    }
 
-    let Ok(mut interacting_npc) = interacting_npc_query.get_single_mut() else {
+    let Some(console) = active_console.0.take() else {
         return;
     };
 
-    if let Some(interacting_npc_entity) = interacting_npc.0 {
-        if let Ok(npc_children) = npc_query.get(interacting_npc_entity) {
-            for child in npc_children.iter() {
-                if let Ok(mut child_data) = data_query.get_mut(*child) {
-                    child_data.fully_opened = false;
-                    child_data.is_opening = false;
-
-                    anim_data.end_position = Vec2::new(0.0, current_window.height());
-                    anim_data.start_position =
-                        Vec2::new(0.0, (1. - CONSOLE_HEIGHT) * current_window.height());
-                    anim_data.start_time = time.elapsed_seconds_f64();
-                    interacting_npc.0 = None;
-                    break;
-                }
-            }
+    for (parent, mut data) in data_query.iter_mut() {
+        if parent.get() == console {
+            data.fully_opened = false;
+            data.is_opening = false;
+            data.preedit.clear();
+
+            anim_data.end_position = Vec2::new(0.0, current_window.height());
+            anim_data.start_position =
+                Vec2::new(0.0, (1. - CONSOLE_HEIGHT) * current_window.height());
+            anim_data.start_time = time.elapsed_seconds_f64();
+            break;
         }
     }
 }
@@ -504,18 +599,16 @@ pub fn apply_animation(
 
 // shows logs that stored in `ConsoleData.messages` to console.
 pub fn update_logs_area(
-    interacting_npc_query: Query<&CurrentInteractingNPC, With<Player>>,
-    npc_query: Query<&Children, With<NPC>>,
+    active_console: Res<ActiveConsole>,
+    children_query: Query<&Children>,
     data_query: Query<&ConsoleData>,
     asset_server: Res<AssetServer>,
     mut logs_area_query: Query<&mut Text, With<LogsArea>>,
 ) {
-    let Ok(CurrentInteractingNPC(interacting_npc)) = interacting_npc_query.get_single() else {
-        return;
-    };
-
-    if let Some(interacting_npc_entity) = interacting_npc {
-        let children_entity = npc_query.get(*interacting_npc_entity).unwrap();
+    if let Some(console) = active_console.0 {
+        let Ok(children_entity) = children_query.get(console) else {
+            return;
+        };
         for &child in children_entity.iter() {
             if let Ok(data) = data_query.get(child) {
                 let sections = data
@@ -545,336 +638,371 @@ pub fn update_logs_area(
 
 pub fn update_enter_command(
     mut enter_command_text: Query<&mut Text, With<CommandLineText>>,
-    interacting_npc_query: Query<&CurrentInteractingNPC, With<Player>>,
-    npc_query: Query<&Children, With<NPC>>,
-    mut data_query: Query<&mut ConsoleData>,
+    active_console: Res<ActiveConsole>,
+    children_query: Query<&Children>,
+    data_query: Query<&ConsoleData>,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
 ) {
-    // TODO: need to be refactored. functionize this checking CurrentInteractingNPC -> ConsoleData logic.
-    let Ok(CurrentInteractingNPC(interacting_npc)) = interacting_npc_query.get_single() else {
-        return;
-    };
-
-    if let Some(interacting_npc_entity) = interacting_npc {
-        let children_entity = npc_query.get(*interacting_npc_entity).unwrap();
+    if let Some(console) = active_console.0 {
+        let Ok(children_entity) = children_query.get(console) else {
+            return;
+        };
         for &child in children_entity.iter() {
-            if let Ok(mut state) = data_query.get_mut(child) {
+            if let Ok(state) = data_query.get(child) {
                 let mut text = enter_command_text.single_mut();
-                text.sections = vec![];
-
-                if state.typed_command.len() > 144 {
-                    let trimmed_command = state.typed_command[..144].to_string();
-                    state.typed_command = trimmed_command;
-                }
-
-                let mut to_show = String::from(">  ");
-                to_show.push_str(&state.typed_command);
-
-                if (time.elapsed_seconds_f64() * 3.0) as u64 % 2 == 0 {
-                    to_show.push('_');
-                }
-
-                text.sections.push(TextSection {
-                    value: to_show,
-                    style: TextStyle {
-                        font: asset_server.load("fonts/VT323-Regular.ttf"),
-                        font_size: 20.,
-                        color: Color::rgba_u8(102, 255, 102, 255),
-                    },
-                });
+                let (before_cursor, after_cursor) = state.command_line.split_at_cursor();
+
+                // The cursor always takes up its place, so the text does not shift while blinking.
+                let blink = (time.elapsed_seconds_f64() * 3.0) as u64;
+                let cursor_color = if blink.is_multiple_of(2) {
+                    Color::rgba_u8(102, 255, 102, 255)
+                } else {
+                    Color::rgba_u8(102, 255, 102, 0)
+                };
+
+                let style = |color: Color| TextStyle {
+                    font: asset_server.load("fonts/VT323-Regular.ttf"),
+                    font_size: 20.,
+                    color,
+                };
+
+                text.sections = vec![
+                    TextSection::new(
+                        format!(">  {}", before_cursor),
+                        style(Color::rgba_u8(102, 255, 102, 255)),
+                    ),
+                    TextSection::new(
+                        state.preedit.clone(),
+                        style(Color::rgba_u8(255, 255, 102, 255)),
+                    ),
+                    TextSection::new("_", style(cursor_color)),
+                    TextSection::new(after_cursor, style(Color::rgba_u8(102, 255, 102, 255))),
+                ];
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn handle_input_keys(
     mut data_query: Query<(&Parent, &mut ConsoleData)>,
+    mut evr_chars: EventReader<ReceivedCharacter>,
+    mut evr_ime: EventReader<Ime>,
     mut evr_keys: EventReader<KeyboardInput>,
     keyboard_input: Res<Input<KeyCode>>,
+    action_input: Res<ActionInput>,
     mut ev_writer: EventWriter<EnteredConsoleCommandEvent>,
-    npc_query: Query<Entity, With<NPC>>,
-    // asset_server: Res<AssetServer>,
-    // audio: Res<Audio>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+    dev_console_query: Query<(), With<DevConsole>>,
+    name_query: Query<&Name, Or<(With<Player>, With<NPC>)>>,
+    registry: Res<ConsoleCommandRegistry>,
 ) {
+    // Events are always drained, so keys pressed before the console fully opened (like E) are not typed afterwards.
+    let chars: Vec<char> = evr_chars.iter().map(|ev| ev.char).collect();
+    let imes: Vec<Ime> = evr_ime.iter().cloned().collect();
+    let key_codes: Vec<KeyCode> = evr_keys
+        .iter()
+        .filter(|ev| ev.state.is_pressed())
+        .filter_map(|ev| ev.key_code)
+        .collect();
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
     for (parent, mut data) in data_query.iter_mut() {
         // if the console is not open yet
         if !data.fully_opened {
             continue;
         }
 
-        // for opened npc console
-        if let Ok(npc) = npc_query.get(parent.get()) {
-            for ev in evr_keys.iter() {
-                if ev.state.is_pressed() {
-                    // let random_key = rand::thread_rng().gen_range(1..10);
-                    // audio.play(asset_server.load(format!("audio/keys/key-{}.mp3", random_key).as_str()));
-
-                    if let Some(key_code) = ev.key_code {
-                        match key_code {
-                            KeyCode::Back => {
-                                if !data.typed_command.is_empty() {
-                                    data.typed_command.pop();
-                                }
-                            }
-                            KeyCode::Space => data.typed_command.push(' '),
-                            KeyCode::Tab => data.typed_command.push_str("  "),
-                            KeyCode::Comma => data.typed_command.push(','),
-                            KeyCode::Colon => data.typed_command.push(':'),
-                            KeyCode::Semicolon => data.typed_command.push(';'),
-                            KeyCode::Apostrophe => data.typed_command.push('\''),
-                            KeyCode::At => data.typed_command.push('@'),
-                            KeyCode::LBracket => data.typed_command.push('['),
-                            KeyCode::RBracket => data.typed_command.push(']'),
-                            KeyCode::Minus | KeyCode::NumpadSubtract => {
-                                data.typed_command.push('-')
-                            }
-                            KeyCode::Period | KeyCode::NumpadDecimal => {
-                                data.typed_command.push('.')
-                            }
-                            KeyCode::Asterisk | KeyCode::NumpadMultiply => {
-                                data.typed_command.push('*')
-                            }
-                            KeyCode::Slash | KeyCode::NumpadDivide => {
-                                if keyboard_input.pressed(KeyCode::LShift)
-                                    || keyboard_input.pressed(KeyCode::RShift)
-                                {
-                                    data.typed_command.push('?');
-                                } else {
-                                    data.typed_command.push('/');
-                                }
-                            }
-                            KeyCode::Plus | KeyCode::NumpadAdd => data.typed_command.push('+'),
-                            KeyCode::Key1 | KeyCode::Numpad1 => data.typed_command.push('1'),
-                            KeyCode::Key2 | KeyCode::Numpad2 => data.typed_command.push('2'),
-                            KeyCode::Key3 | KeyCode::Numpad3 => data.typed_command.push('3'),
-                            KeyCode::Key4 | KeyCode::Numpad4 => data.typed_command.push('4'),
-                            KeyCode::Key5 | KeyCode::Numpad5 => data.typed_command.push('5'),
-                            KeyCode::Key6 | KeyCode::Numpad6 => data.typed_command.push('6'),
-                            KeyCode::Key7 | KeyCode::Numpad7 => data.typed_command.push('7'),
-                            KeyCode::Key8 | KeyCode::Numpad8 => data.typed_command.push('8'),
-                            KeyCode::Key9 | KeyCode::Numpad9 => {
-                                if keyboard_input.pressed(KeyCode::LShift)
-                                    || keyboard_input.pressed(KeyCode::RShift)
-                                {
-                                    data.typed_command.push('(');
-                                } else {
-                                    data.typed_command.push('9');
-                                }
-                            }
-                            KeyCode::Key0 | KeyCode::Numpad0 => {
-                                if keyboard_input.pressed(KeyCode::LShift)
-                                    || keyboard_input.pressed(KeyCode::RShift)
-                                {
-                                    data.typed_command.push(')');
-                                } else {
-                                    data.typed_command.push('0');
-                                }
-                            }
+        let console = parent.get();
+        let is_developer_console = dev_console_query.contains(console);
 
-                            KeyCode::LShift
-                            | KeyCode::RShift
-                            | KeyCode::Escape
-                            | KeyCode::LAlt
-                            | KeyCode::RAlt
-                            | KeyCode::LControl
-                            | KeyCode::RControl
-                            | KeyCode::F1
-                            | KeyCode::Up
-                            | KeyCode::Down
-                            | KeyCode::Right
-                            | KeyCode::Left
-                            | KeyCode::F2
-                            | KeyCode::F3
-                            | KeyCode::F4
-                            | KeyCode::F5
-                            | KeyCode::F6
-                            | KeyCode::F7
-                            | KeyCode::F8
-                            | KeyCode::F9
-                            | KeyCode::F10
-                            | KeyCode::F11
-                            | KeyCode::F12
-                            | KeyCode::Insert
-                            | KeyCode::Delete
-                            | KeyCode::Grave
-                            | KeyCode::Backslash => {}
-
-                            KeyCode::Return => {
-                                // sending the command
-
-                                ev_writer.send(EnteredConsoleCommandEvent {
-                                    npc,
-                                    message: data.typed_command.clone(),
-                                });
-                                // clearing the input
-                                data.typed_command.clear();
-                            }
-                            _ => {
-                                let key_code_str = if keyboard_input.pressed(KeyCode::LShift)
-                                    || keyboard_input.pressed(KeyCode::RShift)
-                                {
-                                    format!("{:?}", key_code).to_uppercase()
-                                } else {
-                                    format!("{:?}", key_code).to_lowercase()
-                                };
-
-                                trace!("Pressed key: {:?}", key_code_str);
-                                data.typed_command.push_str(&key_code_str);
-                            }
-                        }
+        // Printable characters, already shifted and composed by the OS keyboard layout.
+        for c in chars.iter() {
+            // The key that closes the developer console is not typed into it.
+            if is_developer_console && action_input.just_pressed(Action::DevConsole) {
+                continue;
+            }
+            data.command_line.insert_char(*c);
+        }
+
+        for ime in imes.iter() {
+            match ime {
+                Ime::Preedit { value, .. } => data.preedit = value.clone(),
+                Ime::Commit { value, .. } => {
+                    data.preedit.clear();
+                    data.command_line.insert_str(value);
+                }
+                Ime::Disabled { .. } => data.preedit.clear(),
+                Ime::Enabled { .. } => {}
+            }
+        }
+
+        for key_code in key_codes.iter() {
+            match key_code {
+                KeyCode::Back if ctrl => data.command_line.delete_word_before(),
+                KeyCode::Back => data.command_line.backspace(),
+                KeyCode::Delete if ctrl => data.command_line.delete_word_after(),
+                KeyCode::Delete => data.command_line.delete(),
+                KeyCode::Left if ctrl => data.command_line.move_word_left(),
+                KeyCode::Left => data.command_line.move_left(),
+                KeyCode::Right if ctrl => data.command_line.move_word_right(),
+                KeyCode::Right => data.command_line.move_right(),
+                KeyCode::Home => data.command_line.move_home(),
+                KeyCode::End => data.command_line.move_end(),
+                KeyCode::A if ctrl => data.command_line.move_home(),
+                KeyCode::E if ctrl => data.command_line.move_end(),
+                KeyCode::W if ctrl => data.command_line.delete_word_before(),
+                KeyCode::U if ctrl => {
+                    data.command_line.take();
+                }
+                KeyCode::V if ctrl => {
+                    if let Some(pasted) = read_clipboard() {
+                        // Only the first line is pasted, since commands are single lines.
+                        data.command_line
+                            .insert_str(pasted.lines().next().unwrap_or_default());
+                    }
+                }
+                KeyCode::Up => data.history_prev(),
+                KeyCode::Down => data.history_next(),
+                KeyCode::Tab => {
+                    let command_names: Vec<&str> = registry
+                        .iter_scope(ConsoleScope::of(is_developer_console))
+                        .map(|spec| spec.name)
+                        .collect();
+                    let names: Vec<&str> = name_query.iter().map(|name| name.as_str()).collect();
+                    if let Some(candidates) =
+                        complete_command_line(&mut data.command_line, &command_names, &names)
+                    {
+                        console_writer.send(PrintConsoleEvent {
+                            npc: console,
+                            message: candidates.join("  "),
+                        });
                     }
                 }
+                KeyCode::Return | KeyCode::NumpadEnter => {
+                    // sending the command
+                    ev_writer.send(EnteredConsoleCommandEvent {
+                        npc: console,
+                        message: data.submit_command(),
+                    });
+                }
+                _ => {}
             }
         }
+
+        // Gamepads can't type, but can move through the history and send a previous command again.
+        if action_input.gamepad_just_pressed(Action::MoveUp) {
+            data.history_prev();
+        }
+        if action_input.gamepad_just_pressed(Action::MoveDown) {
+            data.history_next();
+        }
+        if action_input.gamepad_just_pressed(Action::MoveLeft) {
+            data.command_line.move_left();
+        }
+        if action_input.gamepad_just_pressed(Action::MoveRight) {
+            data.command_line.move_right();
+        }
+        if action_input.gamepad_just_pressed(Action::MenuConfirm) {
+            ev_writer.send(EnteredConsoleCommandEvent {
+                npc: console,
+                message: data.submit_command(),
+            });
+        }
     }
 }
 
+// Completes the word before the cursor with a command name if it is the first word, or with an entity name otherwise.
+// Returns the candidates when the completion is ambiguous, so they can be printed to the console.
+fn complete_command_line(
+    command_line: &mut LineEditor,
+    command_names: &[&str],
+    names: &[&str],
+) -> Option<Vec<String>> {
+    let (start, word) = command_line.word_before_cursor();
+    let candidates: Vec<&str> = if start == 0 {
+        command_names
+            .iter()
+            .copied()
+            .filter(|command| command.starts_with(word))
+            .collect()
+    } else {
+        let word = word.to_lowercase();
+        names
+            .iter()
+            .copied()
+            .filter(|name| name.to_lowercase().starts_with(&word))
+            .collect()
+    };
+
+    match candidates.len() {
+        0 => None,
+        1 => {
+            let completed = format!("{} ", candidates[0]);
+            command_line.replace_before_cursor(start, &completed);
+            None
+        }
+        _ => {
+            let prefix = common_prefix(&candidates);
+            if prefix.len() > word.len() {
+                command_line.replace_before_cursor(start, prefix);
+            }
+            Some(
+                candidates
+                    .iter()
+                    .map(|candidate| candidate.to_string())
+                    .collect(),
+            )
+        }
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read_clipboard() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| warn!("Failed to read clipboard: {:?}", err))
+        .ok()
+}
+
+#[cfg(target_family = "wasm")]
+fn read_clipboard() -> Option<String> {
+    None
+}
+
+pub fn clear_command(
+    In(input): In<ConsoleCommandInput>,
+    npc_query: Query<&Children>,
+    mut data_query: Query<&mut ConsoleData>,
+) {
+    if let Ok(children) = npc_query.get(input.console) {
+        for &child in children.iter() {
+            if let Ok(mut data) = data_query.get_mut(child) {
+                data.messages.clear();
+            }
+        }
+    }
+}
+
+pub fn motd_command(
+    In(input): In<ConsoleCommandInput>,
+    player_query: Query<&Name, With<Player>>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+) {
+    let (Ok(player_name), Ok(npc_name)) =
+        (player_query.get_single(), name_query.get(input.console))
+    else {
+        return;
+    };
+
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: print_motd(player_name, npc_name, &mut System::new(), true),
+    });
+}
+
 // TODO: modify this code, [`EnteredConsoleCommandEvent`], to send command input to the server (that interacts with chatGPT)
 // and returns the output as form of [`PrintConsoleEvent`], and display this to the console.
-pub fn commands_handler(
+pub fn ask_command(
+    In(input): In<ConsoleCommandInput>,
     mut commands: Commands,
-    mut cmd_reader: EventReader<EnteredConsoleCommandEvent>,
-    mut console_writer: EventWriter<PrintConsoleEvent>,
-    mut movement_writer: EventWriter<OrderMovementEvent>,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
     player_query: Query<&Name, With<Player>>,
-    npc_query: Query<(&Children, &Name), With<NPC>>,
-    mut data_query: Query<&mut ConsoleData>,
+    mut npc_query: Query<(&Name, &Persona, &DailyPlan, &mut MemoryStream)>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
 ) {
-    for EnteredConsoleCommandEvent { npc, message: cmd } in cmd_reader.iter() {
-        // Don't do anything if the string is empty
-        if cmd.is_empty() {
-            return;
-        }
-
-        let mut args: Vec<&str> = cmd.trim().split(' ').collect();
+    let Some(ask) = input.args.text("question") else {
+        return;
+    };
 
-        if args[0] != "clear" {
-            // first send what the user typed
-            let mut user_input = String::from("> ");
-            user_input.push_str(cmd.clone().trim());
-            console_writer.send(PrintConsoleEvent {
-                npc: *npc,
-                message: user_input,
-            });
+    // The NPC answers in character, with what they remember about the question.
+    let message = match npc_query.get_mut(input.console) {
+        Ok((name, persona, plan, mut memory)) => {
+            let player_name = player_query
+                .get_single()
+                .map_or("The player", |name| name.as_str());
+            let now = clock.total_minutes();
+            let memories = memory.retrieve(
+                &format!("{} {}", player_name, ask),
+                now,
+                PROMPT_MEMORIES,
+                &scorer,
+            );
+            memory.record(
+                now,
+                MemoryKind::Conversation,
+                format!("{} asked me: \"{}\"", player_name, ask),
+                &scorer,
+            );
+
+            let mut prompt = format!("You are {}, a villager. {}\n", name, persona.0);
+            if !plan.0.is_empty() {
+                prompt += &format!("Your plan for today: {}\n", plan.0);
+            }
+            if !memories.is_empty() {
+                prompt += &format!("What you remember:\n{}", format_memories(&memories));
+            }
+            prompt += &format!(
+                "{} asks you: \"{}\"\nAnswer as {} in a few sentences.",
+                player_name, ask, name
+            );
+            prompt
         }
+        Err(_) => ask.to_string(),
+    };
 
-        if let Ok((children, npc_name)) = npc_query.get(*npc) {
-            for &child in children.iter() {
-                if let Ok(mut data) = data_query.get_mut(child) {
-                    match args[0] {
-                        "clear" => data.messages.clear(),
-                        "help" => console_writer.send(PrintConsoleEvent {
-                            npc: *npc,
-                            message: display_help(),
-                        }),
-                        "motd" => {
-                            let player_name = player_query.get_single().unwrap(); // TODO: need to implement error handling. (it seems that player_query always returns Some(&name), but..)
-                            console_writer.send(PrintConsoleEvent {
-                                npc: *npc,
-                                message: print_motd(
-                                    player_name,
-                                    npc_name,
-                                    &mut System::new(),
-                                    true,
-                                ),
-                            })
-                        }
-                        "ask" => {
-                            if args.len() < 2 {
-                                console_writer.send(PrintConsoleEvent {
-                                    npc: *npc,
-                                    message: "Please specify a question".to_string(),
-                                });
-                                return;
-                            }
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: "Waiting for chatGPT response...".to_string(),
+    });
+    commands.insert_resource(AskGPT {
+        is_processed: false,
+        npc: input.console,
+        message,
+    });
+    // let ask_gpt = commands
+    //     .spawn(AskGPT {
+    //         is_processed: false,
+    //         npc: *npc,
+    //         message: ask.clone(),
+    //     })
+    //     .id();
+    // commands.entity(*npc).push_children(&[ask_gpt]);
+}
 
-                            args.remove(0);
-                            let ask: String = args.join(" ").to_string();
-
-                            console_writer.send(PrintConsoleEvent {
-                                npc: *npc,
-                                message: "Waiting for chatGPT response...".to_string(),
-                            });
-                            commands.insert_resource(AskGPT {
-                                is_processed: false,
-                                npc: *npc,
-                                message: ask.clone(),
-                            });
-                            // let ask_gpt = commands
-                            //     .spawn(AskGPT {
-                            //         is_processed: false,
-                            //         npc: *npc,
-                            //         message: ask.clone(),
-                            //     })
-                            //     .id();
-                            // commands.entity(*npc).push_children(&[ask_gpt]);
-                        }
-                        "go" => {
-                            if args.len() != 2 {
-                                console_writer.send(PrintConsoleEvent {
-                                    npc: *npc,
-                                    message: "Please type command as 'go (x,y)'".to_string(),
-                                });
-                                return;
-                            }
-                            let message = args[1];
-                            let elements: Vec<&str> = message
-                                .trim_matches(|p| p == '(' || p == ')')
-                                .split(',')
-                                .collect();
-
-                            if elements.len() == 2 {
-                                let x: Option<i32> = elements[0].parse().ok();
-                                let y: Option<i32> = elements[1].parse().ok();
-
-                                if let (Some(x), Some(y)) = (x, y) {
-                                    console_writer.send(PrintConsoleEvent {
-                                        npc: *npc,
-                                        message: format!(
-                                            "{} will be move to ({}, {})",
-                                            npc_name.as_str(),
-                                            x,
-                                            y
-                                        ),
-                                    });
-
-                                    let x = x as f32 * GRID_SIZE + GRID_OFFSET;
-                                    let y = y as f32 * GRID_SIZE + GRID_OFFSET;
-                                    // Emit MovementEvent, to order npc to move
-                                    movement_writer.send(OrderMovementEvent {
-                                        mover: *npc,
-                                        destination: PathTarget::Static((x, y).into()),
-                                        speed: 100.,
-                                    })
-                                } else {
-                                    console_writer.send(PrintConsoleEvent {
-                                        npc: *npc,
-                                        message: "Please type correct coordination.".to_string(),
-                                    });
-                                }
-                            } else {
-                                console_writer.send(PrintConsoleEvent {
-                                    npc: *npc,
-                                    message: "Please type correct coordination.".to_string(),
-                                });
-                            }
-                        }
+pub fn go_command(
+    In(input): In<ConsoleCommandInput>,
+    name_query: Query<&Name>,
+    mut console_writer: EventWriter<PrintConsoleEvent>,
+    mut movement_writer: EventWriter<OrderMovementEvent>,
+) {
+    let Some(coordinate) = input.args.coordinate("destination") else {
+        return;
+    };
 
-                        _ => {
-                            console_writer.send(PrintConsoleEvent {
-                                npc: *npc,
-                                message: format!(
-                                    "I didn't understand the command: \"{}\"",
-                                    args[0]
-                                ),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-    }
+    let npc_name = name_query
+        .get(input.console)
+        .map_or("NPC", |name| name.as_str());
+    console_writer.send(PrintConsoleEvent {
+        npc: input.console,
+        message: format!(
+            "{} will be move to ({}, {})",
+            npc_name, coordinate.x, coordinate.y
+        ),
+    });
+
+    let x = coordinate.x as f32 * GRID_SIZE + GRID_OFFSET;
+    let y = coordinate.y as f32 * GRID_SIZE + GRID_OFFSET;
+    // Emit MovementEvent, to order npc to move
+    movement_writer.send(OrderMovementEvent {
+        mover: input.console,
+        destination: PathTarget::Static((x, y).into()),
+        speed: 100.,
+    })
 }
 
 pub fn send_message_to_chatgpt(runtime: ResMut<TasksRuntime>, mut ask_gpt: ResMut<AskGPT>) {
@@ -885,8 +1013,25 @@ pub fn send_message_to_chatgpt(runtime: ResMut<TasksRuntime>, mut ask_gpt: ResMu
     ask_gpt.is_processed = true;
 
     let message = ask_gpt.message.clone();
-    // let npc = ask_gpt.npc;
 
+    request_chatgpt(&runtime, message, |world, response| {
+        let ask_gpt = world.get_resource::<AskGPT>().unwrap();
+        // let gpt_query = ctx.world.query::<&AskGPT>();
+        world.spawn(GPTResponse {
+            npc: ask_gpt.npc,
+            message: response,
+        });
+    });
+    // }
+}
+
+// Sends `message` to chatGPT in the background, and calls `on_response` with the reply on the main thread.
+// Every request to chatGPT, from the player or between NPCs, goes through here.
+pub fn request_chatgpt(
+    runtime: &TasksRuntime,
+    message: String,
+    on_response: impl FnOnce(&mut World, String) + Send + 'static,
+) {
     runtime.spawn_background_task(|mut ctx| async move {
         let openai_key = env!("OPENAI_API_KEY");
         let client = ChatGPT::new(openai_key).unwrap();
@@ -895,60 +1040,49 @@ pub fn send_message_to_chatgpt(runtime: ResMut<TasksRuntime>, mut ask_gpt: ResMu
         match result {
             Ok(response) => {
                 info!("success");
-                ctx.run_on_main_thread(move |ctx| {
-                    let ask_gpt = ctx.world.get_resource::<AskGPT>().unwrap();
-                    // let gpt_query = ctx.world.query::<&AskGPT>();
-                    ctx.world.spawn(GPTResponse {
-                        npc: ask_gpt.npc,
-                        message: response.message().content.clone(),
-                    });
-                    // ask_gpt.is_processed = true;
-                })
-                .await;
+                let content = response.message().content.clone();
+                ctx.run_on_main_thread(move |ctx| on_response(ctx.world, content))
+                    .await;
             }
             Err(_) => {
                 error!("Failed to receive message to chatGPT");
             }
         }
     });
-    // }
 }
 
 pub fn handle_tasks(
     mut commands: Commands,
+    clock: Res<GameClock>,
+    scorer: Res<MemoryScorer>,
     gpt_tasks: Query<(Entity, &GPTResponse)>,
+    mut memory_query: Query<&mut MemoryStream>,
     mut console_writer: EventWriter<PrintConsoleEvent>,
+    mut bark_writer: EventWriter<BarkEvent>,
 ) {
     for (entity, task) in &gpt_tasks {
+        if let Ok(mut memory) = memory_query.get_mut(task.npc) {
+            memory.record(
+                clock.total_minutes(),
+                MemoryKind::Conversation,
+                format!("I answered: \"{}\"", task.message),
+                &scorer,
+            );
+        }
         info!("Polling future: {:?}", task);
         console_writer.send(PrintConsoleEvent {
             npc: task.npc,
             message: task.message.clone(),
         });
+        // The full reply is in the console. The bubble shows its first sentence to everyone nearby.
+        bark_writer.send(BarkEvent {
+            speaker: task.npc,
+            message: summarize_line(&task.message),
+        });
         commands.entity(entity).remove::<GPTResponse>();
     }
 }
 
-fn display_help() -> String {
-    let mut res = String::from("\nSHOWING AVAILABLE COMMANDS\n");
-
-    let underline = "==========================\n\n";
-    res.push_str(underline);
-
-    res.push_str("- help : Displays this message\n");
-    res.push_str("- clear : Clears commands on the screen\n");
-    res.push_str("- motd : Prints informations about YOUR computer\n");
-    res.push_str("- ask <questions> : ask some questions to chatGPT\n");
-    res.push_str("-go <(x,y)>: order npc to move to (x, y) position");
-
-    res
-}
-
-// TODO
-pub fn should_run_cmd_handler() -> bool {
-    true
-}
-
 pub fn mouse_scroll(
     mut mouse_wheel_events: EventReader<MouseWheel>,
     mut query_list: Query<(&mut ScrollingList, &mut Style, &Parent, &Node)>,
@@ -0,0 +1,328 @@
+use bevy::{
+    math::Rect,
+    prelude::*,
+    sprite::Anchor,
+    text::{Text2dBounds, TextLayoutInfo},
+};
+
+// Bubbles start above the head of 16x32 unit sprites, over the channel progress bar.
+const BUBBLE_OFFSET_Y: f32 = 24.;
+const BUBBLE_MAX_WIDTH: f32 = 96.;
+const BUBBLE_PADDING: f32 = 2.;
+const BUBBLE_GAP: f32 = 2.;
+const BUBBLE_COLOR: Color = Color::rgba(0., 0., 0., 0.7);
+const TEXT_COLOR: Color = Color::rgba(0.4, 1., 0.4, 1.);
+// Text is laid out with a large font and scaled down, so it stays sharp under the zoomed camera.
+const FONT_SIZE: f32 = 32.;
+const TEXT_SCALE: f32 = 0.25;
+const SECONDS_PER_CHAR: f32 = 0.03;
+// Bubbles stay a bit longer for longer lines after they are fully revealed.
+const DISMISS_SECONDS: f32 = 2.;
+const DISMISS_SECONDS_PER_CHAR: f32 = 0.05;
+const MAX_BUBBLES_PER_SPEAKER: usize = 3;
+const SUMMARY_MAX_CHARS: usize = 60;
+
+pub struct SpeechBubblePlugin;
+
+impl Plugin for SpeechBubblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BarkEvent>()
+            .add_system(spawn_speech_bubbles)
+            .add_system(reveal_speech_bubbles.after(spawn_speech_bubbles))
+            .add_system(layout_speech_bubbles.after(reveal_speech_bubbles));
+    }
+}
+
+// A short line said out loud by `speaker`, like a greeting, shown in a bubble above them.
+// Unlike `PrintConsoleEvent`, it is seen without opening a console, so NPCs can talk to each other ambiently.
+pub struct BarkEvent {
+    pub speaker: Entity,
+    pub message: String,
+}
+
+// Child entity of the speaker, drawn as the background of the bubble.
+#[derive(Component, Debug)]
+pub struct SpeechBubble {
+    pub message: String,
+    // Number of characters shown by the typewriter effect.
+    pub revealed: usize,
+    pub reveal_timer: Timer,
+    pub dismiss_timer: Timer,
+}
+
+#[derive(Component)]
+pub struct SpeechBubbleText;
+
+impl SpeechBubble {
+    pub fn new(message: String) -> Self {
+        let seconds = DISMISS_SECONDS + DISMISS_SECONDS_PER_CHAR * message.chars().count() as f32;
+        SpeechBubble {
+            message,
+            revealed: 0,
+            reveal_timer: Timer::from_seconds(SECONDS_PER_CHAR, TimerMode::Repeating),
+            dismiss_timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+
+    pub fn is_fully_revealed(&self) -> bool {
+        self.revealed >= self.message.chars().count()
+    }
+}
+
+// First sentence of `message`, cut to fit in a bubble. Used for long replies like the ones from chatGPT.
+pub fn summarize_line(message: &str) -> String {
+    let message = message.trim();
+    let end = message
+        .char_indices()
+        .find(|&(index, c)| {
+            matches!(c, '.' | '!' | '?')
+                && message[index + c.len_utf8()..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map_or(message.len(), |(index, c)| index + c.len_utf8());
+    let sentence = message[..end].trim();
+
+    if sentence.chars().count() > SUMMARY_MAX_CHARS {
+        let cut: String = sentence.chars().take(SUMMARY_MAX_CHARS - 3).collect();
+        format!("{}...", cut.trim_end())
+    } else {
+        sentence.to_string()
+    }
+}
+
+pub fn spawn_speech_bubbles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut bark_events: EventReader<BarkEvent>,
+    children_query: Query<&Children>,
+    bubble_query: Query<(), With<SpeechBubble>>,
+) {
+    for BarkEvent { speaker, message } in bark_events.iter() {
+        if message.trim().is_empty() {
+            continue;
+        }
+        let Some(mut speaker_commands) = commands.get_entity(*speaker) else {
+            continue;
+        };
+
+        // Drops the oldest bubbles, so at most `MAX_BUBBLES_PER_SPEAKER` are stacked with the new one.
+        let bubbles: Vec<Entity> = children_query
+            .get(*speaker)
+            .map(|children| {
+                children
+                    .iter()
+                    .copied()
+                    .filter(|&child| bubble_query.contains(child))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let excess = (bubbles.len() + 1).saturating_sub(MAX_BUBBLES_PER_SPEAKER);
+        for &bubble in bubbles.iter().take(excess) {
+            speaker_commands
+                .commands()
+                .entity(bubble)
+                .despawn_recursive();
+        }
+
+        let style = TextStyle {
+            font: asset_server.load("fonts/VT323-Regular.ttf"),
+            font_size: FONT_SIZE,
+            color: TEXT_COLOR,
+        };
+        // The whole line is laid out from the start, and the unrevealed part is transparent,
+        // so words don't jump to the next line while they are typed.
+        let hidden_style = TextStyle {
+            color: Color::NONE,
+            ..style.clone()
+        };
+
+        let bubble = speaker_commands
+            .commands()
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: BUBBLE_COLOR,
+                        custom_size: Some(Vec2::ZERO),
+                        anchor: Anchor::BottomCenter,
+                        ..default()
+                    },
+                    // Drawn over every unit. Bubbles of units in front are still drawn over the ones behind,
+                    // because the speaker's z is set by `y_sort`.
+                    transform: Transform::from_translation(Vec3::new(0., BUBBLE_OFFSET_Y, 1.)),
+                    ..default()
+                },
+                SpeechBubble::new(message.trim().to_string()),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_sections([
+                            TextSection::new("", style),
+                            TextSection::new(message.trim(), hidden_style),
+                        ]),
+                        text_anchor: Anchor::BottomCenter,
+                        text_2d_bounds: Text2dBounds {
+                            size: Vec2::new(BUBBLE_MAX_WIDTH / TEXT_SCALE, f32::INFINITY),
+                        },
+                        transform: Transform::from_translation(Vec3::new(0., BUBBLE_PADDING, 0.1))
+                            .with_scale(Vec3::splat(TEXT_SCALE)),
+                        ..default()
+                    },
+                    SpeechBubbleText,
+                ));
+            })
+            .id();
+        speaker_commands.push_children(&[bubble]);
+    }
+}
+
+// Types out bubbles one character at a time, and despawns them some time after they are fully shown.
+pub fn reveal_speech_bubbles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bubble_query: Query<(Entity, &mut SpeechBubble, &Children)>,
+    mut text_query: Query<&mut Text, With<SpeechBubbleText>>,
+) {
+    for (entity, mut bubble, children) in bubble_query.iter_mut() {
+        if bubble.is_fully_revealed() {
+            if bubble.dismiss_timer.tick(time.delta()).finished() {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        }
+
+        let ticks = bubble
+            .reveal_timer
+            .tick(time.delta())
+            .times_finished_this_tick() as usize;
+        if ticks == 0 {
+            continue;
+        }
+        bubble.revealed += ticks;
+
+        let split = bubble
+            .message
+            .char_indices()
+            .nth(bubble.revealed)
+            .map_or(bubble.message.len(), |(index, _)| index);
+        let (shown, hidden) = bubble.message.split_at(split);
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = shown.to_string();
+                text.sections[1].value = hidden.to_string();
+            }
+        }
+    }
+}
+
+// Fits bubbles to their text, and stacks them so they don't overlap.
+// Bubbles of one speaker are stacked with the newest at the bottom. Speakers in front, with a lower y, keep their place,
+// and the bubbles of speakers behind them are pushed up.
+pub fn layout_speech_bubbles(
+    parent_query: Query<&Parent, With<SpeechBubble>>,
+    speaker_query: Query<(&GlobalTransform, &Children)>,
+    text_query: Query<&TextLayoutInfo, With<SpeechBubbleText>>,
+    mut bubble_query: Query<(&Children, &mut Sprite, &mut Transform), With<SpeechBubble>>,
+) {
+    let mut speakers: Vec<(Entity, Vec2)> = Vec::new();
+    for parent in parent_query.iter() {
+        let speaker = parent.get();
+        if speakers.iter().any(|&(entity, _)| entity == speaker) {
+            continue;
+        }
+        if let Ok((transform, _)) = speaker_query.get(speaker) {
+            speakers.push((speaker, transform.translation().truncate()));
+        }
+    }
+    speakers.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y));
+
+    let mut placed: Vec<Rect> = Vec::new();
+    for (speaker, position) in speakers {
+        let Ok((_, children)) = speaker_query.get(speaker) else {
+            continue;
+        };
+
+        // Newest first, from the bottom of the stack.
+        let mut stack: Vec<(Entity, Vec2)> = Vec::new();
+        for &child in children.iter().rev() {
+            let Ok((bubble_children, _, _)) = bubble_query.get(child) else {
+                continue;
+            };
+            let text_size = bubble_children
+                .iter()
+                .find_map(|&text| text_query.get(text).ok())
+                .map_or(Vec2::ZERO, |layout| layout.size * TEXT_SCALE);
+            stack.push((child, text_size + Vec2::splat(BUBBLE_PADDING * 2.)));
+        }
+        if stack.is_empty() {
+            continue;
+        }
+
+        let width = stack.iter().map(|(_, size)| size.x).fold(0., f32::max);
+        let height = stack.iter().map(|(_, size)| size.y).sum::<f32>()
+            + BUBBLE_GAP * (stack.len() - 1) as f32;
+        let mut bottom = position.y + BUBBLE_OFFSET_Y;
+        let column = |bottom: f32| {
+            Rect::new(
+                position.x - width / 2.,
+                bottom,
+                position.x + width / 2.,
+                bottom + height,
+            )
+        };
+        while let Some(rect) = placed
+            .iter()
+            .find(|rect| !rect.intersect(column(bottom)).is_empty())
+        {
+            bottom = rect.max.y + BUBBLE_GAP;
+        }
+        placed.push(column(bottom));
+
+        let mut y = bottom - position.y;
+        for (bubble, size) in stack {
+            if let Ok((_, mut sprite, mut transform)) = bubble_query.get_mut(bubble) {
+                if sprite.custom_size != Some(size) {
+                    sprite.custom_size = Some(size);
+                }
+                if transform.translation.y != y {
+                    transform.translation.y = y;
+                }
+            }
+            y += size.y + BUBBLE_GAP;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_first_sentence() {
+        assert_eq!(summarize_line("Hello there. How are you?"), "Hello there.");
+        assert_eq!(summarize_line("  Really?! Yes.  "), "Really?!");
+        assert_eq!(summarize_line("just words "), "just words");
+    }
+
+    #[test]
+    fn ignores_punctuation_inside_words() {
+        assert_eq!(
+            summarize_line("Version 1.5 is out! Great."),
+            "Version 1.5 is out!"
+        );
+        assert_eq!(summarize_line("Wait..."), "Wait...");
+    }
+
+    #[test]
+    fn cuts_long_sentences() {
+        let summary = summarize_line(&"word ".repeat(20));
+        assert!(summary.ends_with("..."));
+        assert!(summary.chars().count() <= SUMMARY_MAX_CHARS);
+        assert!(!summary.trim_end_matches("...").ends_with(' '));
+
+        let summary = summarize_line(&"é".repeat(SUMMARY_MAX_CHARS + 1));
+        assert_eq!(summary.chars().count(), SUMMARY_MAX_CHARS);
+    }
+}
@@ -0,0 +1,469 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::HashSet,
+    window::PrimaryWindow,
+};
+use bevy_ecs_ldtk::{prelude::LdtkFields, LdtkAsset, LdtkLevel};
+use seldom_map_nav::prelude::PathTarget;
+
+use super::hud_text;
+use crate::{
+    ai::OrderMovementEvent,
+    input::{Action, ActionInput},
+    maps::{Coordinate, EntityGridMap, TileGridMap, TileType},
+    state::AppState,
+    units::{Player, NPC},
+};
+
+// The minimap shows this many cells on each side, centered on the player.
+const MINIMAP_CELLS: u32 = 48;
+const PLAYER_MARKER_COLOR: [u8; 4] = [255, 255, 255, 255];
+const NPC_MARKER_COLOR: [u8; 4] = [255, 204, 51, 255];
+const EMPTY_CELL_COLOR: [u8; 4] = [0, 0, 0, 160];
+// Margin around the world on the full-screen map, as a fraction of the window.
+const WORLD_MAP_MARGIN: f32 = 0.05;
+const WORLD_MAP_BACKGROUND: Color = Color::rgba(0., 0., 0., 0.85);
+const UNKNOWN_LEVEL_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 1.);
+const POINT_OF_INTEREST_COLOR: Color = Color::rgb(0.4, 1., 0.4);
+const MARKER_SIZE: f32 = 6.;
+const TRAVEL_SPEED: f32 = 100.;
+// Pixels per second the cursor moves on the world map with the stick, the d-pad or the move keys.
+const MAP_CURSOR_SPEED: f32 = 400.;
+
+pub struct WorldMapPlugin;
+
+impl Plugin for WorldMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KnownLevels>()
+            .add_startup_system(create_minimap_image)
+            .add_system(
+                spawn_minimap
+                    .in_schedule(OnEnter(AppState::MainGame))
+                    .run_if(run_once()),
+            )
+            .add_system(draw_minimap)
+            .add_system(update_known_levels)
+            .add_system(open_world_map)
+            .add_system(spawn_world_map.in_schedule(OnEnter(AppState::WorldMapOpenedState)))
+            .add_system(despawn_world_map.in_schedule(OnExit(AppState::WorldMapOpenedState)))
+            .add_systems(
+                (
+                    update_world_map_markers,
+                    move_map_cursor,
+                    travel_to_clicked_point.after(move_map_cursor),
+                )
+                    .in_set(OnUpdate(AppState::WorldMapOpenedState)),
+            );
+    }
+}
+
+// Identifiers of levels that have been loaded, which are drawn with their points of interest on the world map.
+// Neighbors of the current level are loaded too, so levels are known once the player is next to them.
+#[derive(Resource, Default)]
+pub struct KnownLevels(pub HashSet<String>);
+
+#[derive(Resource)]
+pub struct MinimapImage(pub Handle<Image>);
+
+#[derive(Component)]
+pub struct Minimap;
+
+// Root of the full-screen map. Positions in LDtk world space, where y points down, are drawn at `offset + position * scale`.
+#[derive(Component)]
+pub struct WorldMap {
+    pub offset: Vec2,
+    pub scale: f32,
+}
+
+impl WorldMap {
+    // Window position, from the top left, of a position in the game world.
+    fn to_window(&self, position: Vec2) -> Vec2 {
+        self.offset + Vec2::new(position.x, -position.y) * self.scale
+    }
+
+    fn to_world(&self, window_position: Vec2) -> Vec2 {
+        let position = (window_position - self.offset) / self.scale;
+        Vec2::new(position.x, -position.y)
+    }
+}
+
+// Marks the player or an NPC on the world map.
+#[derive(Component)]
+pub struct WorldMapMarker(pub Entity);
+
+fn tile_color(tile_type: TileType) -> [u8; 4] {
+    match tile_type {
+        TileType::Wall => [60, 50, 50, 255],
+        TileType::Floor => [120, 150, 100, 255],
+        TileType::Road => [170, 160, 140, 255],
+        TileType::Grass => [80, 150, 70, 255],
+        TileType::Mud => [110, 80, 50, 255],
+        TileType::Water => [60, 100, 190, 255],
+        TileType::DeepWater => [30, 60, 140, 255],
+    }
+}
+
+fn color_of([r, g, b, a]: [u8; 4]) -> Color {
+    Color::rgba_u8(r, g, b, a)
+}
+
+pub fn create_minimap_image(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: MINIMAP_CELLS,
+            height: MINIMAP_CELLS,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &EMPTY_CELL_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    commands.insert_resource(MinimapImage(images.add(image)));
+}
+
+// Square in the top right corner, under the clock of the HUD.
+pub fn spawn_minimap(mut commands: Commands, minimap_image: Res<MinimapImage>) {
+    commands.spawn((
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Percent(2.),
+                    top: Val::Percent(9.),
+                    ..default()
+                },
+                size: Size::new(Val::Auto, Val::Percent(22.)),
+                aspect_ratio: Some(1.),
+                ..default()
+            },
+            image: UiImage {
+                texture: minimap_image.0.clone(),
+                ..default()
+            },
+            ..default()
+        },
+        Minimap,
+    ));
+}
+
+// Redraws the cells around the player from `TileGridMap`, with units from `EntityGridMap` over them.
+pub fn draw_minimap(
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+    tile_grid_map: Res<TileGridMap>,
+    entity_map: Res<EntityGridMap>,
+    player_query: Query<(Entity, &Coordinate), With<Player>>,
+    npc_query: Query<(), With<NPC>>,
+    mut last_center: Local<Option<(i32, i32)>>,
+) {
+    let Ok((player, coordinate)) = player_query.get_single() else {
+        return;
+    };
+    let center = (
+        (coordinate.min_x + coordinate.max_x) / 2,
+        (coordinate.min_y + coordinate.max_y) / 2,
+    );
+    if *last_center == Some(center) && !tile_grid_map.is_changed() && !entity_map.is_changed() {
+        return;
+    }
+    *last_center = Some(center);
+    let Some(image) = images.get_mut(&minimap_image.0) else {
+        return;
+    };
+
+    let half = MINIMAP_CELLS as i32 / 2;
+    for row in 0..MINIMAP_CELLS as i32 {
+        for column in 0..MINIMAP_CELLS as i32 {
+            // Rows of the image go down, and cells go up.
+            let cell = (center.0 - half + column, center.1 + half - row);
+            let units = entity_map.get(cell);
+            let color = if units.is_some_and(|units| units.contains(&player)) {
+                PLAYER_MARKER_COLOR
+            } else if units.is_some_and(|units| units.iter().any(|&unit| npc_query.contains(unit)))
+            {
+                NPC_MARKER_COLOR
+            } else {
+                tile_grid_map
+                    .get(cell)
+                    .map_or(EMPTY_CELL_COLOR, |(_, tile_type)| tile_color(*tile_type))
+            };
+            let index = ((row * MINIMAP_CELLS as i32 + column) * 4) as usize;
+            image.data[index..index + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+pub fn update_known_levels(
+    ldtk_levels: Res<Assets<LdtkLevel>>,
+    mut known_levels: ResMut<KnownLevels>,
+    level_query: Query<&Handle<LdtkLevel>, Added<Handle<LdtkLevel>>>,
+) {
+    for level_handle in level_query.iter() {
+        if let Some(ldtk_level) = ldtk_levels.get(level_handle) {
+            known_levels.0.insert(ldtk_level.level.identifier.clone());
+        }
+    }
+}
+
+// Toggles `WorldMapOpenedState` on `Action::WorldMap`. `Action::Pause` and `Action::MenuBack` close it too.
+pub fn open_world_map(
+    action_input: Res<ActionInput>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match app_state.0 {
+        AppState::MainGame if action_input.just_pressed(Action::WorldMap) => {
+            next_state.set(AppState::WorldMapOpenedState)
+        }
+        AppState::WorldMapOpenedState
+            if action_input.just_pressed(Action::WorldMap)
+                || action_input.just_pressed(Action::Pause)
+                || action_input.just_pressed(Action::MenuBack) =>
+        {
+            next_state.set(AppState::MainGame)
+        }
+        _ => {}
+    }
+}
+
+fn absolute_style(position: Vec2, size: Vec2) -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(position.x),
+            top: Val::Px(position.y),
+            ..default()
+        },
+        size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+        ..default()
+    }
+}
+
+// Draws every level of the LDtk world in its layout, scaled to fit the window.
+// Known levels show their background color and entrances, unknown ones are greyed out.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_world_map(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    known_levels: Res<KnownLevels>,
+    world_query: Query<&Handle<LdtkAsset>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    player_query: Query<Entity, With<Player>>,
+    npc_query: Query<(Entity, &Name), With<NPC>>,
+) {
+    let (Some(ldtk_asset), Ok(window)) = (
+        world_query
+            .iter()
+            .find_map(|ldtk_handle| ldtk_assets.get(ldtk_handle)),
+        window_query.get_single(),
+    ) else {
+        return;
+    };
+    let levels = &ldtk_asset.project.levels;
+    if levels.is_empty() {
+        return;
+    }
+
+    let min = levels.iter().fold(Vec2::splat(f32::MAX), |min, level| {
+        min.min(Vec2::new(level.world_x as f32, level.world_y as f32))
+    });
+    let max = levels.iter().fold(Vec2::splat(f32::MIN), |max, level| {
+        max.max(Vec2::new(
+            (level.world_x + level.px_wid) as f32,
+            (level.world_y + level.px_hei) as f32,
+        ))
+    });
+    let window_size = Vec2::new(window.width(), window.height());
+    let available = window_size * (1. - 2. * WORLD_MAP_MARGIN);
+    let scale = (available / (max - min)).min_element();
+    let world_map = WorldMap {
+        offset: (window_size - (max - min) * scale) / 2. - min * scale,
+        scale,
+    };
+
+    let mut root = commands.spawn(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        background_color: WORLD_MAP_BACKGROUND.into(),
+        // Over the HUD, under the fade overlay of level transitions.
+        z_index: ZIndex::Global(4),
+        ..default()
+    });
+    root.with_children(|parent| {
+        for level in levels.iter() {
+            let known = known_levels.0.contains(&level.identifier);
+            let origin = Vec2::new(level.world_x as f32, level.world_y as f32);
+            let size = Vec2::new(level.px_wid as f32, level.px_hei as f32);
+            parent
+                .spawn(NodeBundle {
+                    style: absolute_style(world_map.offset + origin * scale, size * scale),
+                    background_color: if known {
+                        level.bg_color.into()
+                    } else {
+                        UNKNOWN_LEVEL_COLOR.into()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let mut label = hud_text(&asset_server, 0.03);
+                    label.0.text.sections[0].value = if known {
+                        level.identifier.clone()
+                    } else {
+                        "?".to_string()
+                    };
+                    parent.spawn(label);
+                });
+            if !known {
+                continue;
+            }
+
+            // Points of interest are the entrances of the level.
+            let entrances = level
+                .layer_instances
+                .iter()
+                .flatten()
+                .flat_map(|layer| layer.entity_instances.iter())
+                .filter(|entity_instance| entity_instance.identifier == "Entrance");
+            for entrance in entrances {
+                let position = world_map.offset + (origin + entrance.px.as_vec2()) * scale
+                    - Vec2::splat(MARKER_SIZE / 2.);
+                parent
+                    .spawn(NodeBundle {
+                        style: absolute_style(position, Vec2::splat(MARKER_SIZE)),
+                        background_color: POINT_OF_INTEREST_COLOR.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        let mut label = hud_text(&asset_server, 0.025);
+                        label.0.text.sections[0].value = entrance
+                            .get_string_field("name")
+                            .cloned()
+                            .unwrap_or_default();
+                        label.0.style.position_type = PositionType::Absolute;
+                        label.0.style.position.left = Val::Px(MARKER_SIZE);
+                        parent.spawn(label);
+                    });
+            }
+        }
+
+        for player in player_query.iter() {
+            parent.spawn(marker_bundle(player, color_of(PLAYER_MARKER_COLOR)));
+        }
+        for (npc, name) in npc_query.iter() {
+            parent
+                .spawn(marker_bundle(npc, color_of(NPC_MARKER_COLOR)))
+                .with_children(|parent| {
+                    let mut label = hud_text(&asset_server, 0.025);
+                    label.0.text.sections[0].value = name.to_string();
+                    label.0.style.position_type = PositionType::Absolute;
+                    label.0.style.position.left = Val::Px(MARKER_SIZE);
+                    parent.spawn(label);
+                });
+        }
+    });
+    root.insert(world_map);
+}
+
+fn marker_bundle(unit: Entity, color: Color) -> (NodeBundle, WorldMapMarker) {
+    (
+        NodeBundle {
+            style: absolute_style(Vec2::ZERO, Vec2::splat(MARKER_SIZE)),
+            background_color: color.into(),
+            // Units are drawn over points of interest.
+            z_index: ZIndex::Local(1),
+            ..default()
+        },
+        WorldMapMarker(unit),
+    )
+}
+
+pub fn despawn_world_map(mut commands: Commands, world_map_query: Query<Entity, With<WorldMap>>) {
+    for world_map in world_map_query.iter() {
+        commands.entity(world_map).despawn_recursive();
+    }
+}
+
+pub fn update_world_map_markers(
+    world_map_query: Query<&WorldMap>,
+    unit_query: Query<(&GlobalTransform, Option<&Visibility>), Without<WorldMapMarker>>,
+    mut marker_query: Query<(&WorldMapMarker, &mut Style, &mut Visibility)>,
+) {
+    let Ok(world_map) = world_map_query.get_single() else {
+        return;
+    };
+    for (marker, mut style, mut marker_visibility) in marker_query.iter_mut() {
+        let Ok((transform, visibility)) = unit_query.get(marker.0) else {
+            *marker_visibility = Visibility::Hidden;
+            continue;
+        };
+        // Dead units are hidden, and so are their markers.
+        *marker_visibility = match visibility {
+            Some(Visibility::Hidden) => Visibility::Hidden,
+            _ => Visibility::Inherited,
+        };
+        let position =
+            world_map.to_window(transform.translation().truncate()) - Vec2::splat(MARKER_SIZE / 2.);
+        style.position.left = Val::Px(position.x);
+        style.position.top = Val::Px(position.y);
+    }
+}
+
+// Without a mouse, the cursor is moved over the world map like the player.
+pub fn move_map_cursor(
+    action_input: Res<ActionInput>,
+    time: Res<Time>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let movement = action_input.movement();
+    if movement == Vec2::ZERO {
+        return;
+    }
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+    let size = Vec2::new(window.width(), window.height());
+    let cursor_position = window.cursor_position().unwrap_or(size / 2.);
+    let cursor_position = (cursor_position + movement * MAP_CURSOR_SPEED * time.delta_seconds())
+        .clamp(Vec2::ZERO, size);
+    window.set_cursor_position(Some(cursor_position));
+}
+
+// Moving to the cursor, or confirming, on a point of the world map closes it, and sends the player there.
+pub fn travel_to_clicked_point(
+    action_input: Res<ActionInput>,
+    mut next_state: ResMut<NextState<AppState>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    world_map_query: Query<&WorldMap>,
+    player_query: Query<Entity, With<Player>>,
+    mut movement_writer: EventWriter<OrderMovementEvent>,
+) {
+    if !action_input.just_pressed(Action::MoveToCursor)
+        && !action_input.just_pressed(Action::MenuConfirm)
+    {
+        return;
+    }
+    let (Ok(window), Ok(world_map), Ok(player)) = (
+        window_query.get_single(),
+        world_map_query.get_single(),
+        player_query.get_single(),
+    ) else {
+        return;
+    };
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    // The cursor position starts from the bottom left of the window, and UI nodes from the top left.
+    let window_position = Vec2::new(cursor_position.x, window.height() - cursor_position.y);
+
+    movement_writer.send(OrderMovementEvent {
+        mover: player,
+        destination: PathTarget::Static(world_map.to_world(window_position)),
+        speed: TRAVEL_SPEED,
+    });
+    next_state.set(AppState::MainGame);
+}
@@ -1,18 +1,21 @@
-use bevy::{
-    prelude::{Added, App, Bundle, Component, Entity, Name, Or, Plugin, Query},
-    sprite::SpriteSheetBundle,
+use bevy::prelude::{
+    default, Added, App, Bundle, Commands, Component, Entity, Name, Or, Plugin, Query,
+    SpatialBundle, Transform, Vec3,
 };
 use bevy_ecs_ldtk::{
     prelude::{LdtkEntity, LdtkEntityAppExt, LdtkFields},
     EntityInstance, Worldly,
 };
+use rand::Rng;
 
 use crate::{
-    combat::{Delay, Hurtbox},
+    ai::{DailyPlan, MemoryStream, Persona, Relationships},
+    combat::{Delay, Health, Hurtbox},
     constants::UNIT_SIZE,
     maps::Coordinate,
-    physics::{ColliderBundle, MoveLock},
-    sprites::{AnimationBundle, YSort},
+    physics::{ColliderBundle, MoveLock, Stamina},
+    sprites::{y_sort_from_ldtk_field, AnimationBundle, YSort},
+    ui::Inventory,
 };
 
 pub struct UnitsPlugin;
@@ -29,10 +32,8 @@ impl Plugin for UnitsPlugin {
 
 #[derive(Clone, Default, Bundle, LdtkEntity)]
 pub struct PlayerBundle {
-    #[sprite_sheet_bundle("char/player_base.png", 16.0, 32.0, 6, 21, 0.0, 0.0, 0)]
-    #[bundle]
-    // TODO: move SpriteSheetBundle to child entity, to order body and other sprites.
-    pub sprite_sheet_bundle: SpriteSheetBundle,
+    // Sprites are `SpriteLayer` children, spawned by `spawn_body_sprite` and others.
+    // bevy_ecs_ldtk inserts the `SpatialBundle` of every spawned entity.
     #[from_entity_instance]
     #[bundle]
     pub collider_bundle: ColliderBundle,
@@ -45,10 +46,14 @@ pub struct PlayerBundle {
     pub unit_size: UnitSize,
     #[bundle]
     pub animation_bundle: AnimationBundle,
+    #[with(y_sort_from_ldtk_field)]
     pub ysort: YSort,
     pub delay: Delay,
+    pub health: Health,
     pub move_lock: MoveLock,
+    pub stamina: Stamina,
     pub coordinate: Coordinate,
+    pub inventory: Inventory,
     // The whole EntityInstance can be stored directly as an EntityInstance component
     #[from_entity_instance]
     entity_instance: EntityInstance,
@@ -56,23 +61,26 @@ pub struct PlayerBundle {
 
 #[derive(Clone, Default, Bundle, LdtkEntity)]
 pub struct NPCBundle {
-    #[sprite_sheet_bundle("char/npc_1.png", 16.0, 32.0, 4, 4, 0.0, 0.0, 0)]
-    #[bundle]
-    // TODO: move SpriteSheetBundle to child entity, to order body and other sprites.
-    pub sprite_sheet_bundle: SpriteSheetBundle,
     #[from_entity_instance]
     #[bundle]
     pub collider_bundle: ColliderBundle,
     #[with(name_from_ldtk_field)]
     pub name: Name,
     pub npc: NPC,
+    #[with(persona_from_ldtk_field)]
+    pub persona: Persona,
+    pub memory: MemoryStream,
+    pub relationships: Relationships,
+    pub plan: DailyPlan,
     #[worldly]
     pub worldly: Worldly,
     pub unit_size: UnitSize,
     #[bundle]
     pub animation_bundle: AnimationBundle,
+    #[with(y_sort_from_ldtk_field)]
     pub ysort: YSort,
     pub delay: Delay,
+    pub health: Health,
     pub hurtbox: Hurtbox,
     pub move_lock: MoveLock,
     pub coordinate: Coordinate,
@@ -97,6 +105,44 @@ pub struct CurrentInteractingNPC(pub Option<Entity>);
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct NPC;
 
+// Spawns an NPC that is not placed in the LDtk level, e.g. by the `spawn` developer command.
+// Its body sprite is spawned by `spawn_body_sprite` like placed NPCs.
+pub fn spawn_npc(commands: &mut Commands, name: &str, translation: Vec3) -> Entity {
+    let entity_instance = EntityInstance {
+        identifier: "NPC".to_string(),
+        iid: random_iid(),
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NPCBundle {
+                collider_bundle: ColliderBundle::from(&entity_instance),
+                name: Name::new(name.to_string()),
+                entity_instance,
+                ..default()
+            },
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+        ))
+        .id()
+}
+
+// Random UUID in the format of LDtk iids, so spawned units are told apart from placed ones and each other.
+fn random_iid() -> String {
+    let mut rng = rand::thread_rng();
+    let bytes: [u8; 16] = rng.gen();
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-4{}-{:x}{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        8 | (bytes[8] & 0x3),
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
+
 fn name_from_ldtk_field(entity_instance: &EntityInstance) -> Name {
     Name::new(
         entity_instance
@@ -106,11 +152,26 @@ fn name_from_ldtk_field(entity_instance: &EntityInstance) -> Name {
     )
 }
 
-pub fn setup(mut query: Query<(&mut UnitSize, &mut YSort), Or<(Added<Player>, Added<NPC>)>>) {
-    for (mut unit_size, mut ysort) in &mut query {
+// The `persona` field is optional, so NPCs can be placed without writing one.
+fn persona_from_ldtk_field(entity_instance: &EntityInstance) -> Persona {
+    let persona = entity_instance
+        .get_maybe_string_field("persona")
+        .ok()
+        .cloned()
+        .flatten();
+    Persona(persona.unwrap_or_else(|| {
+        format!(
+            "{} lives in this village.",
+            name_from_ldtk_field(entity_instance)
+        )
+    }))
+}
+
+#[allow(clippy::type_complexity)]
+pub fn setup(mut query: Query<&mut UnitSize, Or<(Added<Player>, Added<NPC>)>>) {
+    for mut unit_size in &mut query {
         // TODO: This is hard-coded for now. unit_size can be differ for each entity.
         unit_size.width = UNIT_SIZE;
         unit_size.height = UNIT_SIZE;
-        ysort.z = 5.0;
     }
 }